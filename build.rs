@@ -0,0 +1,31 @@
+//! Embed the git commit the binary was built from, when available.
+//!
+//! Exposed to the crate as `VIBETORRENT_GIT_SHA`; builds outside a git checkout
+//! (e.g. from a source tarball) simply leave it unset.
+
+use std::process::Command;
+
+fn main() {
+    // Allow CI to pass the SHA in explicitly
+    println!("cargo:rerun-if-env-changed=VIBETORRENT_GIT_SHA");
+    if let Ok(sha) = std::env::var("VIBETORRENT_GIT_SHA") {
+        println!("cargo:rustc-env=VIBETORRENT_GIT_SHA={}", sha);
+        return;
+    }
+
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    let sha = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|sha| sha.trim().to_string())
+        .filter(|sha| !sha.is_empty());
+
+    if let Some(sha) = sha {
+        println!("cargo:rustc-env=VIBETORRENT_GIT_SHA={}", sha);
+    }
+}
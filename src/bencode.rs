@@ -0,0 +1,176 @@
+//! Minimal bencode decoder
+//!
+//! Just enough of the format to inspect `.torrent` files before they are handed
+//! to rTorrent.
+
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Int(i64),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Dict(BTreeMap<Vec<u8>, Value>),
+}
+
+impl Value {
+    /// Look up `key` if this is a dictionary
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Dict(dict) => dict.get(key.as_bytes()),
+            _ => None,
+        }
+    }
+}
+
+/// Decode a complete bencoded value; trailing bytes are an error
+pub fn decode(data: &[u8]) -> Result<Value, String> {
+    let mut parser = Parser { data, pos: 0 };
+    let value = parser.value(0)?;
+    if parser.pos != data.len() {
+        return Err(format!("trailing data at byte {}", parser.pos));
+    }
+    Ok(value)
+}
+
+/// Does `data` look like a torrent file: a dictionary with an `info` dictionary?
+pub fn is_torrent(data: &[u8]) -> bool {
+    data.first() == Some(&b'd')
+        && decode(data)
+            .map(|root| matches!(root.get("info"), Some(Value::Dict(_))))
+            .unwrap_or(false)
+}
+
+/// The torrent's infohash: SHA-1 of the raw `info` dictionary, in uppercase
+/// hex like rTorrent's `d.hash`
+pub fn info_hash(data: &[u8]) -> Option<String> {
+    let mut parser = Parser { data, pos: 0 };
+    if parser.peek().ok()? != b'd' {
+        return None;
+    }
+    parser.pos += 1;
+    while parser.peek().ok()? != b'e' {
+        let key = parser.bytes().ok()?;
+        let start = parser.pos;
+        let value = parser.value(1).ok()?;
+        if key == b"info" {
+            return matches!(value, Value::Dict(_)).then(|| {
+                sha1_smol::Sha1::from(&data[start..parser.pos]).digest().to_string().to_uppercase()
+            });
+        }
+    }
+    None
+}
+
+/// Nesting limit so a hostile upload can't overflow the stack
+const MAX_DEPTH: usize = 64;
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn value(&mut self, depth: usize) -> Result<Value, String> {
+        if depth > MAX_DEPTH {
+            return Err("nesting too deep".to_string());
+        }
+
+        match self.peek()? {
+            b'i' => {
+                self.pos += 1;
+                let digits = self.take_until(b'e')?;
+                let text = std::str::from_utf8(digits).map_err(|_| "invalid integer".to_string())?;
+                text.parse()
+                    .map(Value::Int)
+                    .map_err(|_| format!("invalid integer {:?}", text))
+            }
+            b'l' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                while self.peek()? != b'e' {
+                    items.push(self.value(depth + 1)?);
+                }
+                self.pos += 1;
+                Ok(Value::List(items))
+            }
+            b'd' => {
+                self.pos += 1;
+                let mut dict = BTreeMap::new();
+                while self.peek()? != b'e' {
+                    let key = self.bytes()?;
+                    let value = self.value(depth + 1)?;
+                    dict.insert(key, value);
+                }
+                self.pos += 1;
+                Ok(Value::Dict(dict))
+            }
+            b'0'..=b'9' => self.bytes().map(Value::Bytes),
+            other => Err(format!("unexpected byte {:?} at {}", other as char, self.pos)),
+        }
+    }
+
+    /// A `<length>:<bytes>` string
+    fn bytes(&mut self) -> Result<Vec<u8>, String> {
+        let digits = self.take_until(b':')?;
+        let len: usize = std::str::from_utf8(digits)
+            .ok()
+            .and_then(|text| text.parse().ok())
+            .ok_or_else(|| "invalid string length".to_string())?;
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.data.len())
+            .ok_or_else(|| "string runs past end of data".to_string())?;
+        let bytes = self.data[self.pos..end].to_vec();
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn peek(&self) -> Result<u8, String> {
+        self.data.get(self.pos).copied().ok_or_else(|| "unexpected end of data".to_string())
+    }
+
+    /// Bytes up to (not including) `delimiter`, consuming the delimiter
+    fn take_until(&mut self, delimiter: u8) -> Result<&[u8], String> {
+        let start = self.pos;
+        let offset = self.data[start..]
+            .iter()
+            .position(|&b| b == delimiter)
+            .ok_or_else(|| "unexpected end of data".to_string())?;
+        self.pos = start + offset + 1;
+        Ok(&self.data[start..start + offset])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TORRENT: &[u8] = b"d8:announce20:http://t.example/ann4:infod6:lengthi5e4:name5:a.txtee";
+
+    #[test]
+    fn info_hash_is_the_sha1_of_the_raw_info_dictionary() {
+        // SHA-1 of `d6:lengthi5e4:name5:a.txte`
+        assert_eq!(info_hash(TORRENT).as_deref(), Some("FA3671E2915FE9E91A61556FB0E70EE4A44A87BB"));
+    }
+
+    #[test]
+    fn torrents_need_an_info_dictionary() {
+        assert!(is_torrent(TORRENT));
+        assert!(!is_torrent(b"d4:infoi1ee"));
+        assert!(!is_torrent(b"d8:announce3:urle"));
+        assert!(!is_torrent(b"<html>not a torrent</html>"));
+        assert_eq!(info_hash(b"d4:infoi1ee"), None);
+        assert_eq!(info_hash(b"d8:announce3:urle"), None);
+        assert_eq!(info_hash(b"l4:infoe"), None);
+    }
+
+    #[test]
+    fn malformed_data_is_an_error() {
+        assert!(decode(b"i12").is_err());
+        assert!(decode(b"5:abc").is_err());
+        assert!(decode(b"i1ei2e").is_err());
+        assert!(decode(b"99999999999999999999999:x").is_err());
+        let deep = [vec![b'l'; MAX_DEPTH + 2], vec![b'e'; MAX_DEPTH + 2]].concat();
+        assert!(decode(&deep).is_err());
+        assert_eq!(decode(b"li-3e2:hie"), Ok(Value::List(vec![Value::Int(-3), Value::Bytes(b"hi".to_vec())])));
+    }
+}
@@ -1,10 +1,84 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::rtorrent::{ProgressMode, RateUnit};
+use crate::services::transitions::Transition;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub scgi_socket: String,
     pub bind_address: String,
+    /// Serve a view-only dashboard: every mutating route answers 403
+    #[serde(default)]
+    pub read_only: bool,
+    /// Seconds between SSE keep-alive comments; lower it when a reverse proxy
+    /// closes idle streams sooner
+    #[serde(default = "default_sse_keepalive_secs")]
+    pub sse_keepalive_secs: u64,
+    /// Text of the SSE keep-alive comment
+    #[serde(default = "default_sse_keepalive_text")]
+    pub sse_keepalive_text: String,
+    /// Shell command run when a torrent finishes downloading, with `VT_HASH`,
+    /// `VT_NAME` and `VT_PATH` in its environment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_complete_command: Option<String>,
+    /// Seconds before a still-running `on_complete_command` is killed
+    #[serde(default = "default_on_complete_timeout_secs")]
+    pub on_complete_timeout_secs: u64,
+    /// URL that receives a JSON POST for each torrent event
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook_url: Option<String>,
+    /// Which events are sent to `webhook_url`: `added`, `completed`, `error`
+    #[serde(default = "default_webhook_events")]
+    pub webhook_events: Vec<String>,
+    /// Extra environment variables sent in every SCGI request header, for
+    /// endpoints behind middleware that expects e.g. `HTTPS` or auth variables
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub scgi_env: BTreeMap<String, String>,
+    /// Origins (e.g. `https://dash.example.com`) allowed to call `/api/*` from
+    /// a browser; empty keeps the API same-origin
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cors_allowed_origins: Vec<String>,
+    /// Rows the torrent list shows until "Show all" is clicked; unset shows
+    /// everything. Sidebar counts always cover the whole list.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_limit: Option<usize>,
+    /// Tries for each add-by-URL when rTorrent can't be reached, with backoff
+    /// in between; faults from rTorrent are never retried
+    #[serde(default = "default_add_url_attempts")]
+    pub add_url_attempts: u32,
+    /// `total` measures progress against the whole torrent, `wanted` against
+    /// the files selected for download
+    #[serde(default)]
+    pub progress_mode: ProgressMode,
+    /// `bytes` shows rates as KB/s and MB/s, `bits` as Kbps and Mbps
+    #[serde(default)]
+    pub rate_unit: RateUnit,
+    /// Largest add-torrent upload accepted, in MB, all files together; read
+    /// at startup
+    #[serde(default = "default_max_upload_mb")]
+    pub max_upload_mb: u64,
+    /// Seconds an add-torrent upload may take to arrive before it's rejected
+    #[serde(default = "default_upload_timeout_secs")]
+    pub upload_timeout_secs: u64,
+    /// Remove torrents that have been seeding this many days without any
+    /// traffic; unset (the default) never removes anything. Starred torrents
+    /// are kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_remove_days: Option<u64>,
+    /// Delete the data of torrents removed by `idle_remove_days` too
+    #[serde(default)]
+    pub idle_remove_delete_data: bool,
+    /// Only log what `idle_remove_days` would remove
+    #[serde(default)]
+    pub idle_remove_dry_run: bool,
+    /// PEM certificate chain; with `tls_key` the UI is served over HTTPS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_cert: Option<PathBuf>,
+    /// PEM private key for `tls_cert`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_key: Option<PathBuf>,
 }
 
 impl Default for Config {
@@ -12,67 +86,235 @@ impl Default for Config {
         Self {
             scgi_socket: "/tmp/rtorrent.sock".to_string(),
             bind_address: "0.0.0.0:3000".to_string(),
+            read_only: false,
+            sse_keepalive_secs: default_sse_keepalive_secs(),
+            sse_keepalive_text: default_sse_keepalive_text(),
+            on_complete_command: None,
+            on_complete_timeout_secs: default_on_complete_timeout_secs(),
+            webhook_url: None,
+            webhook_events: default_webhook_events(),
+            scgi_env: BTreeMap::new(),
+            cors_allowed_origins: Vec::new(),
+            default_limit: None,
+            add_url_attempts: default_add_url_attempts(),
+            progress_mode: ProgressMode::default(),
+            rate_unit: RateUnit::default(),
+            max_upload_mb: default_max_upload_mb(),
+            upload_timeout_secs: default_upload_timeout_secs(),
+            idle_remove_days: None,
+            idle_remove_delete_data: false,
+            idle_remove_dry_run: false,
+            tls_cert: None,
+            tls_key: None,
         }
     }
 }
 
-impl Config {
+fn default_sse_keepalive_secs() -> u64 {
+    15
+}
+
+fn default_sse_keepalive_text() -> String {
+    "keep-alive".to_string()
+}
+
+fn default_on_complete_timeout_secs() -> u64 {
+    300
+}
+
+fn default_add_url_attempts() -> u32 {
+    3
+}
+
+fn default_max_upload_mb() -> u64 {
+    32
+}
+
+fn default_upload_timeout_secs() -> u64 {
+    60
+}
+
+fn default_webhook_events() -> Vec<String> {
+    ["added", "completed", "error"].map(String::from).to_vec()
+}
+
+/// Where the config file lives.
+///
+/// Built once at startup from `--config` and passed to every load/save, so the
+/// whole process agrees on a single file.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigLocation {
+    explicit: Option<PathBuf>,
+}
+
+impl ConfigLocation {
+    /// `explicit` overrides the search entirely when set
+    pub fn new(explicit: Option<PathBuf>) -> Self {
+        Self { explicit }
+    }
+
     /// Get config file path
-    pub fn config_path() -> PathBuf {
-        // Try to find config in current directory first, then home directory
-        let local_config = PathBuf::from("vibetorrent.json");
-        if local_config.exists() {
-            return local_config;
+    pub fn path(&self) -> PathBuf {
+        let candidates = self.candidates();
+
+        // Prefer an existing config, in search order
+        if let Some(existing) = candidates.iter().find(|p| p.exists()) {
+            return existing.clone();
         }
-        
-        // Try home directory
-        if let Some(home) = dirs_path() {
-            let home_config = home.join(".config").join("vibetorrent").join("config.json");
-            if home_config.exists() {
-                return home_config;
-            }
+
+        // Default to the first candidate
+        candidates[0].clone()
+    }
+
+    /// File for persisted UI state (stars, queue order), next to the config
+    pub fn state_path(&self) -> PathBuf {
+        self.path().with_file_name("vibetorrent-state.json")
+    }
+
+    /// Locations searched for the config file, in order of preference.
+    ///
+    /// The working directory comes first, except inside containers where it is
+    /// usually read-only; there the user config directory is tried first.
+    fn candidates(&self) -> Vec<PathBuf> {
+        if let Some(path) = &self.explicit {
+            return vec![path.clone()];
+        }
+
+        let local_config = PathBuf::from("vibetorrent.json");
+        let user_config = config_home().map(|dir| dir.join("vibetorrent").join("config.json"));
+
+        match user_config {
+            Some(user_config) if is_containerized() => vec![user_config, local_config],
+            Some(user_config) => vec![local_config, user_config],
+            None => vec![local_config],
         }
-        
-        // Default to local
-        local_config
     }
-    
+}
+
+impl Config {
     /// Load config from file
-    pub fn load() -> Option<Self> {
-        let path = Self::config_path();
+    pub fn load(location: &ConfigLocation) -> Option<Self> {
+        let path = location.path();
         if !path.exists() {
             return None;
         }
-        
+
         let content = std::fs::read_to_string(&path).ok()?;
         serde_json::from_str(&content).ok()
     }
-    
-    /// Save config to file
-    pub fn save(&self) -> Result<(), String> {
-        let path = Self::config_path();
-        
-        // Create parent directories if needed
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)
-                .map_err(|e| format!("Failed to create config directory: {}", e))?;
-        }
-        
+
+    /// Save config to file, returning the path that was written.
+    ///
+    /// Writes to the existing config if there is one, otherwise to the first
+    /// writable candidate location. If nothing is writable, the error lists every
+    /// path that was tried and why it failed.
+    pub fn save(&self, location: &ConfigLocation) -> Result<PathBuf, String> {
         let content = serde_json::to_string_pretty(self)
             .map_err(|e| format!("Failed to serialize config: {}", e))?;
-        
-        std::fs::write(&path, content)
-            .map_err(|e| format!("Failed to write config: {}", e))?;
-        
+
+        let mut candidates = location.candidates();
+        if let Some(pos) = candidates.iter().position(|p| p.exists()) {
+            let existing = candidates.remove(pos);
+            candidates.insert(0, existing);
+        }
+
+        let mut failures = Vec::new();
+        for path in candidates {
+            match write_config(&path, &content) {
+                Ok(()) => {
+                    tracing::info!("Saved config to {}", path.display());
+                    return Ok(path);
+                }
+                Err(e) => failures.push(format!("{} ({})", path.display(), e)),
+            }
+        }
+
+        Err(format!(
+            "Failed to save config, no writable location found. Tried: {}. Use --config <path> to choose a writable file.",
+            failures.join("; ")
+        ))
+    }
+
+    /// Values that parse but can't work, checked when the config is loaded at
+    /// startup
+    pub fn validate(&self) -> Result<(), String> {
+        // Sent as an SSE comment line, which a line break would end early
+        if self.sse_keepalive_text.contains(['\r', '\n']) {
+            return Err("sse_keepalive_text must not contain line breaks".to_string());
+        }
+        // A misspelt event would otherwise just never be sent
+        if let Some(unknown) = self.webhook_events.iter().find(|event| !Transition::KINDS.contains(&event.as_str())) {
+            return Err(format!(
+                "unknown webhook_events entry {:?}, expected one of: {}",
+                unknown,
+                Transition::KINDS.join(", ")
+            ));
+        }
         Ok(())
     }
-    
+
     /// Check if config exists
-    pub fn exists() -> bool {
-        Self::config_path().exists()
+    pub fn exists(location: &ConfigLocation) -> bool {
+        location.path().exists()
     }
 }
 
+fn write_config(path: &Path, content: &str) -> Result<(), String> {
+    // Create parent directories if needed
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("cannot create directory: {}", e))?;
+    }
+
+    std::fs::write(path, content).map_err(|e| format!("cannot write: {}", e))
+}
+
+/// Docker and Podman drop marker files at the filesystem root
+fn is_containerized() -> bool {
+    Path::new("/.dockerenv").exists() || Path::new("/run/.containerenv").exists()
+}
+
+/// `$XDG_CONFIG_HOME`, falling back to `~/.config` as the XDG spec prescribes
+fn config_home() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| dirs_path().map(|home| home.join(".config")))
+}
+
 fn dirs_path() -> Option<PathBuf> {
     std::env::var("HOME").ok().map(PathBuf::from)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keepalive_text_with_line_breaks_is_rejected() {
+        assert!(Config::default().validate().is_ok());
+        for text in ["keep\nalive", "keep-alive\r", "\r\n"] {
+            let config: Config = serde_json::from_value(serde_json::json!({
+                "scgi_socket": "/tmp/rtorrent.sock",
+                "bind_address": "0.0.0.0:3000",
+                "sse_keepalive_text": text,
+            }))
+            .unwrap();
+            assert!(config.validate().unwrap_err().contains("sse_keepalive_text"), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn unknown_webhook_events_are_rejected() {
+        let config = |events: &[&str]| Config {
+            webhook_events: events.iter().map(|e| e.to_string()).collect(),
+            ..Config::default()
+        };
+
+        assert!(config(&["added", "completed", "error"]).validate().is_ok());
+        assert!(config(&[]).validate().is_ok());
+        let error = config(&["added", "complete"]).validate().unwrap_err();
+        assert!(error.contains("\"complete\""), "{}", error);
+        assert!(config(&["Added"]).validate().is_err());
+    }
+}
@@ -30,6 +30,12 @@ pub enum AppError {
     BadRequest(String),
 }
 
+impl From<crate::xmlrpc::Error> for AppError {
+    fn from(e: crate::xmlrpc::Error) -> Self {
+        AppError::XmlRpcError(e.to_string())
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let (status, message) = match &self {
@@ -43,7 +49,8 @@ impl IntoResponse for AppError {
             AppError::BadRequest(_) => (StatusCode::BAD_REQUEST, self.to_string()),
         };
         
-        tracing::error!("Error: {}", message);
+        // Logged inside the request span, so the line carries the request id
+        tracing::error!(%status, "Error: {}", message);
         
         (status, message).into_response()
     }
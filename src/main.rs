@@ -1,3 +1,4 @@
+mod bencode;
 mod config;
 mod error;
 mod routes;
@@ -5,57 +6,167 @@ mod rtorrent;
 mod sse;
 mod services;
 mod state;
+mod store;
 mod templates;
+#[cfg(test)]
+mod testing;
+mod theme;
+mod tls;
+mod xmlrpc;
 
 use axum::{
     routing::{get, post},
     Router,
     response::{Response, Html, Redirect, IntoResponse},
-    http::{header, HeaderValue, StatusCode, Request},
-    extract::{Path, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode, Request},
+    extract::{rejection::JsonRejection, DefaultBodyLimit, Path, State},
     body::Body,
-    Form,
+    Form, Json,
     middleware::{self, Next},
 };
 use clap::Parser;
 use rust_embed::Embed;
 use serde::Deserialize;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use tower_http::compression::CompressionLayer;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+use tracing::Instrument;
+use tower_http::compression::{
+    predicate::{DefaultPredicate, NotForContentType, Predicate},
+    CompressionLayer,
+};
+use tower_http::cors::{AllowOrigin, CorsLayer};
 use askama::Template;
 
-use crate::config::Config;
+use crate::config::{Config, ConfigLocation};
 use crate::state::AppState;
+use crate::store::Store;
 use crate::templates::SetupTemplate;
 
 /// Shared state that can be updated at runtime
 pub struct SharedState {
     pub app_state: RwLock<Option<Arc<AppState>>>,
     pub config: RwLock<Option<Config>>,
+    pub config_location: ConfigLocation,
+    /// Serializes setup submissions so test → save → swap runs as one step
+    pub setup_lock: Mutex<()>,
+    /// A config that couldn't reach rTorrent at startup, adopted by
+    /// [`Self::recover`] once rTorrent answers
+    unreachable_config: Mutex<Option<Config>>,
+    /// When [`Self::recover`] last tried to reach rTorrent
+    last_recovery: std::sync::Mutex<Option<Instant>>,
+    /// Upload limit the router was built with. A `max_upload_mb` saved later
+    /// only applies after a restart, so errors report this one.
+    pub max_upload_mb: u64,
+    /// Address this server is listening on, once bound
+    pub listen_addr: std::sync::OnceLock<std::net::SocketAddr>,
 }
 
+/// How often requests may retry an rTorrent that was unreachable at startup
+const RECOVERY_INTERVAL: Duration = Duration::from_secs(5);
+
 impl SharedState {
-    pub fn new(config: Option<Config>) -> Self {
+    pub fn new(config: Option<Config>, unreachable_config: Option<Config>, config_location: ConfigLocation) -> Self {
         let app_state = config.as_ref().map(|c| {
-            Arc::new(AppState::new(c.scgi_socket.clone()))
+            Arc::new(AppState::new(c.clone(), Store::new(config_location.state_path())))
         });
         Self {
             app_state: RwLock::new(app_state),
             config: RwLock::new(config),
+            config_location,
+            setup_lock: Mutex::new(()),
+            unreachable_config: Mutex::new(unreachable_config),
+            last_recovery: std::sync::Mutex::new(None),
+            max_upload_mb: Config::default().max_upload_mb,
+            listen_addr: std::sync::OnceLock::new(),
+        }
+    }
+    
+    /// Limit upload bodies to `max_upload_mb` instead of the default
+    pub fn with_max_upload_mb(mut self, max_upload_mb: u64) -> Self {
+        self.max_upload_mb = max_upload_mb;
+        self
+    }
+    
+    /// Start the app from the config that couldn't reach rTorrent at startup,
+    /// if rTorrent answers now. Returns whether the app is running.
+    ///
+    /// Runs under the setup lock, so a setup submitted meanwhile isn't replaced
+    /// by the older config. rTorrent is tried at most once per
+    /// [`RECOVERY_INTERVAL`]; requests queued behind an attempt that failed
+    /// return straight away instead of each trying again.
+    pub async fn recover(&self) -> bool {
+        let _setup = self.setup_lock.lock().await;
+        if self.is_configured().await {
+            return true;
+        }
+        let Some(config) = self.unreachable_config.lock().await.clone() else {
+            return false;
+        };
+        {
+            let mut last_recovery = self.last_recovery.lock().unwrap_or_else(|e| e.into_inner());
+            if last_recovery.is_some_and(|at| at.elapsed() < RECOVERY_INTERVAL) {
+                return false;
+            }
+            *last_recovery = Some(Instant::now());
         }
+        let socket = config.scgi_socket.clone();
+        if self.update_config(config).await.is_err() {
+            return false;
+        }
+        tracing::info!("rTorrent at {} is reachable again, started with the saved config", socket);
+        true
     }
     
-    pub async fn update_config(&self, config: Config) {
-        let app_state = Arc::new(AppState::new(config.scgi_socket.clone()));
-        *self.app_state.write().await = Some(app_state);
-        *self.config.write().await = Some(config);
+    /// Swap in a new config and the `AppState` built from it, if its rTorrent
+    /// answers. Otherwise the running state is kept and the error says why.
+    ///
+    /// Both slots are replaced while holding both write locks, so no request sees
+    /// the new config with the old state. The previous state's poller is stopped
+    /// explicitly: handlers may still hold clones of its `Arc`, which would keep
+    /// `Drop` from ever running.
+    pub async fn update_config(&self, config: Config) -> Result<(), String> {
+        // A state whose poller can never connect would only log errors
+        let client = crate::rtorrent::RtorrentClient::new(config.scgi_socket.clone())
+            .with_scgi_env(&config.scgi_env);
+        if !client.test_connection().await {
+            return Err(format!(
+                "Cannot connect to rtorrent at '{}'. Please check the socket path and ensure rtorrent is running.",
+                config.scgi_socket
+            ));
+        }
+        
+        // The new state loads the store file, so the old one's pending writes go first
+        if let Some(current) = self.get_app_state().await {
+            current.flush().await;
+        }
+        let app_state = Arc::new(AppState::new(
+            config.clone(),
+            Store::new(self.config_location.state_path()),
+        ));
+        let previous = {
+            let mut app_state_slot = self.app_state.write().await;
+            let mut config_slot = self.config.write().await;
+            *config_slot = Some(config);
+            app_state_slot.replace(app_state)
+        };
+        // Whatever config was waiting for rTorrent is superseded
+        self.unreachable_config.lock().await.take();
+        if let Some(previous) = previous {
+            previous.shutdown();
+        }
+        Ok(())
     }
     
     pub async fn is_configured(&self) -> bool {
         self.config.read().await.is_some()
     }
     
+    pub async fn is_read_only(&self) -> bool {
+        self.config.read().await.as_ref().is_some_and(|c| c.read_only)
+    }
+    
     pub async fn get_app_state(&self) -> Option<Arc<AppState>> {
         self.app_state.read().await.clone()
     }
@@ -77,6 +188,43 @@ struct Args {
     /// Run setup wizard (force)
     #[arg(long)]
     setup: bool,
+    
+    /// Never serve the setup wizard: exit with an error if the config is missing
+    /// or rTorrent is unreachable (for automated deployments)
+    #[arg(
+        long,
+        env = "VIBETORRENT_NO_SETUP",
+        value_parser = clap::builder::FalseyValueParser::new(),
+        conflicts_with = "setup"
+    )]
+    no_setup: bool,
+    
+    /// View-only mode: hide controls and reject every mutating request
+    #[arg(long)]
+    read_only: bool,
+    
+    /// Check the config, the SCGI connection and rTorrent's version, print one
+    /// line per check and exit (non-zero on failure) without serving
+    #[arg(long, conflicts_with = "setup")]
+    check: bool,
+    
+    /// Config file to load and save, instead of searching the default locations
+    #[arg(long, visible_alias = "config-path", value_name = "PATH")]
+    config: Option<std::path::PathBuf>,
+
+    /// Directory with replacement partials (torrent row, stats), rendered at runtime
+    #[arg(long, value_name = "DIR")]
+    templates_dir: Option<std::path::PathBuf>,
+
+    /// Log line format: human-readable, or one JSON object per line for log shippers
+    #[arg(long, env = "VIBETORRENT_LOG_FORMAT", value_enum, default_value_t = LogFormat::Pretty)]
+    log_format: LogFormat,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum LogFormat {
+    Pretty,
+    Json,
 }
 
 // Embed static files into the binary
@@ -84,16 +232,39 @@ struct Args {
 #[folder = "static/"]
 struct StaticFiles;
 
-// Handler to serve embedded static files
-async fn serve_static(Path(path): Path<String>) -> Response<Body> {
+// Handler to serve embedded static files.
+//
+// Single byte ranges are honoured so large assets can be resumed. Responses
+// with `Content-Range` are never compressed by `CompressionLayer`, which keeps
+// the offsets valid.
+async fn serve_static(Path(path): Path<String>, request_headers: HeaderMap) -> Response<Body> {
     let path = path.as_str();
     
     match StaticFiles::get(path) {
         Some(content) => {
-            let mime = mime_guess::from_path(path).first_or_octet_stream();
-            let mut response = Response::new(Body::from(content.data.into_owned()));
-            *response.status_mut() = StatusCode::OK;
+            let data = content.data;
+            let len = data.len();
+            let mut response = match byte_range(request_headers.get(header::RANGE), len) {
+                ByteRange::Full => Response::new(Body::from(data.into_owned())),
+                ByteRange::Partial(start, end) => {
+                    let mut response = Response::new(Body::from(data[start..=end].to_vec()));
+                    *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+                    if let Ok(value) = HeaderValue::from_str(&format!("bytes {}-{}/{}", start, end, len)) {
+                        response.headers_mut().insert(header::CONTENT_RANGE, value);
+                    }
+                    response
+                }
+                ByteRange::Unsatisfiable => {
+                    let mut response = Response::new(Body::empty());
+                    *response.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+                    if let Ok(value) = HeaderValue::from_str(&format!("bytes */{}", len)) {
+                        response.headers_mut().insert(header::CONTENT_RANGE, value);
+                    }
+                    return response;
+                }
+            };
 
+            let mime = mime_guess::from_path(path).first_or_octet_stream();
             let headers = response.headers_mut();
             let content_type = HeaderValue::from_str(mime.as_ref())
                 .unwrap_or_else(|_| HeaderValue::from_static("application/octet-stream"));
@@ -102,6 +273,7 @@ async fn serve_static(Path(path): Path<String>) -> Response<Body> {
                 header::CACHE_CONTROL,
                 HeaderValue::from_static("public, max-age=31536000"),
             );
+            headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
 
             response
         }
@@ -113,14 +285,64 @@ async fn serve_static(Path(path): Path<String>) -> Response<Body> {
     }
 }
 
+/// What to send for a `Range` request header against a body of `len` bytes
+enum ByteRange {
+    /// No usable range: send everything with 200. Malformed and multi-range
+    /// headers land here too, which RFC 9110 allows.
+    Full,
+    /// Inclusive byte offsets
+    Partial(usize, usize),
+    /// 416: the range starts past the end
+    Unsatisfiable,
+}
+
+fn byte_range(header: Option<&HeaderValue>, len: usize) -> ByteRange {
+    let Some(spec) = header
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().strip_prefix("bytes="))
+        .filter(|spec| !spec.contains(','))
+    else {
+        return ByteRange::Full;
+    };
+    let Some((start, end)) = spec.split_once('-').map(|(s, e)| (s.trim(), e.trim())) else {
+        return ByteRange::Full;
+    };
+
+    if start.is_empty() {
+        // Suffix range: the last N bytes
+        return match end.parse::<usize>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(_) if len == 0 => ByteRange::Unsatisfiable,
+            Ok(suffix) => ByteRange::Partial(len.saturating_sub(suffix), len - 1),
+            Err(_) => ByteRange::Full,
+        };
+    }
+
+    let Ok(start) = start.parse::<usize>() else {
+        return ByteRange::Full;
+    };
+    if start >= len {
+        return ByteRange::Unsatisfiable;
+    }
+    let end = if end.is_empty() {
+        len - 1
+    } else {
+        match end.parse::<usize>() {
+            Ok(end) if end >= start => end.min(len - 1),
+            _ => return ByteRange::Full,
+        }
+    };
+    ByteRange::Partial(start, end)
+}
+
 #[derive(Deserialize)]
 struct SetupForm {
     scgi_socket: String,
     bind_address: String,
 }
 
-async fn setup_page(error: Option<String>) -> Html<String> {
-    let config = Config::load().unwrap_or_default();
+async fn setup_page(location: &ConfigLocation, error: Option<String>) -> Html<String> {
+    let config = Config::load(location).unwrap_or_default();
     let template = SetupTemplate {
         scgi_socket: config.scgi_socket,
         bind_address: config.bind_address,
@@ -161,22 +383,29 @@ async fn setup_page(error: Option<String>) -> Html<String> {
         }
 }
 
-async fn setup_get() -> Html<String> {
-    setup_page(None).await
+async fn setup_get(shared: &SharedState) -> Html<String> {
+    setup_page(&shared.config_location, None).await
 }
 
 async fn setup_post(
     State(shared): State<Arc<SharedState>>,
     Form(form): Form<SetupForm>,
 ) -> Response<Body> {
-    let config = Config {
-        scgi_socket: form.scgi_socket.trim().to_string(),
-        bind_address: form.bind_address.trim().to_string(),
+    // One submission at a time: a double-click or second tab waits here and then
+    // applies on top of the first, so the last valid config wins
+    let _setup = shared.setup_lock.lock().await;
+    
+    // Start from the current config so settings the form doesn't show are kept
+    let mut config = match shared.config.read().await.clone() {
+        Some(config) => config,
+        None => Config::load(&shared.config_location).unwrap_or_default(),
     };
+    let previous_bind = std::mem::replace(&mut config.bind_address, form.bind_address.trim().to_string());
+    config.scgi_socket = form.scgi_socket.trim().to_string();
     
     // Validate socket path
     if config.scgi_socket.is_empty() {
-        let html = setup_page(Some("SCGI socket path is required".to_string())).await;
+        let html = setup_page(&shared.config_location, Some("SCGI socket path is required".to_string())).await;
         return Response::builder()
             .status(StatusCode::BAD_REQUEST)
             .header(header::CONTENT_TYPE, "text/html")
@@ -184,13 +413,28 @@ async fn setup_post(
             .unwrap();
     }
     
-    // Test rtorrent connection before saving
-    let client = crate::rtorrent::RtorrentClient::new(config.scgi_socket.clone());
-    if !client.test_connection().await {
-        let html = setup_page(Some(format!(
-            "Cannot connect to rtorrent at '{}'. Please check the socket path and ensure rtorrent is running.",
-            config.scgi_socket
-        ))).await;
+    // A new bind address only applies on the next start; make sure that start
+    // can listen on it. Any address on the port this server holds would fail
+    // here as in use but be free by then, so those aren't tried.
+    let on_current_port = shared
+        .listen_addr
+        .get()
+        .is_some_and(|current| port_of(&config.bind_address) == Some(current.port()));
+    if config.bind_address != previous_bind && !on_current_port {
+        if let Err(e) = tokio::net::TcpListener::bind(&config.bind_address).await {
+            let html = setup_page(&shared.config_location, Some(bind_error_message(&config.bind_address, &e))).await;
+            return Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .header(header::CONTENT_TYPE, "text/html")
+                .body(Body::from(html.0))
+                .unwrap();
+        }
+    }
+    
+    // Apply before saving: update_config keeps the running state when rTorrent
+    // doesn't answer, and a config that doesn't work shouldn't be written either
+    if let Err(e) = shared.update_config(config.clone()).await {
+        let html = setup_page(&shared.config_location, Some(e)).await;
         return Response::builder()
             .status(StatusCode::BAD_REQUEST)
             .header(header::CONTENT_TYPE, "text/html")
@@ -199,8 +443,8 @@ async fn setup_post(
     }
     
     // Save config to file
-    if let Err(e) = config.save() {
-        let html = setup_page(Some(e)).await;
+    if let Err(e) = config.save(&shared.config_location) {
+        let html = setup_page(&shared.config_location, Some(e)).await;
         return Response::builder()
             .status(StatusCode::INTERNAL_SERVER_ERROR)
             .header(header::CONTENT_TYPE, "text/html")
@@ -208,13 +452,37 @@ async fn setup_post(
             .unwrap();
     }
     
-    // Update runtime state - this enables the main app without restart!
-    shared.update_config(config).await;
-    
     // Redirect to main app
     Redirect::to("/").into_response()
 }
 
+/// Why `addr` can't be listened on, phrased for the setup form and startup
+fn bind_error_message(addr: &str, error: &std::io::Error) -> String {
+    let host = addr.rsplit_once(':').map_or("0.0.0.0", |(host, _)| host);
+    match error.kind() {
+        std::io::ErrorKind::PermissionDenied if is_privileged_port(addr) => format!(
+            "Cannot listen on {}: ports below 1024 need root or CAP_NET_BIND_SERVICE. \
+             Use a higher port such as {}:3000, or put a reverse proxy in front.",
+            addr, host
+        ),
+        std::io::ErrorKind::AddrInUse => format!(
+            "Cannot listen on {}: another program is using that port. Choose a different one.",
+            addr
+        ),
+        _ => format!("Cannot listen on {}: {}", addr, error),
+    }
+}
+
+/// Whether `addr` has a port below 1024, which only privileged processes may bind
+fn is_privileged_port(addr: &str) -> bool {
+    port_of(addr).is_some_and(|port| port != 0 && port < 1024)
+}
+
+/// The port of a `host:port` address
+fn port_of(addr: &str) -> Option<u16> {
+    addr.rsplit_once(':').and_then(|(_, port)| port.parse().ok())
+}
+
 // Middleware to check if setup is needed
 async fn setup_guard(
     State(shared): State<Arc<SharedState>>,
@@ -223,24 +491,76 @@ async fn setup_guard(
 ) -> Response<Body> {
     let path = request.uri().path();
     
-    // Always allow setup routes and static files
-    if path.starts_with("/setup") || path.starts_with("/static/") {
+    // Always allow setup routes, static files and the version probe
+    if path.starts_with("/setup") || path.starts_with("/static/") || path == "/api/version" {
         return next.run(request).await;
     }
     
-    // Check if configured
-    if !shared.is_configured().await {
+    // Check if configured, or can be now that rTorrent is back
+    if !shared.is_configured().await && !shared.recover().await {
+        if path == "/api/add" {
+            return api_not_configured();
+        }
         return Redirect::to("/setup").into_response();
     }
     
     next.run(request).await
 }
 
-fn create_router(shared: Arc<SharedState>, _force_setup: bool) -> Router {
+/// What scripts calling `/api/add` get before setup is done: a redirect to an
+/// HTML form would be no use to them
+fn api_not_configured() -> Response<Body> {
+    (StatusCode::SERVICE_UNAVAILABLE, Json(serde_json::json!({ "error": "not configured" }))).into_response()
+}
+
+// Middleware to reject mutating requests in read-only mode
+async fn read_only_guard(
+    State(shared): State<Arc<SharedState>>,
+    request: Request<Body>,
+    next: Next,
+) -> Response<Body> {
+    let method = request.method();
+    let is_mutation = !(method == Method::GET || method == Method::HEAD || method == Method::OPTIONS);
+    
+    if is_mutation && shared.is_read_only().await {
+        return (StatusCode::FORBIDDEN, "VibeTorrent is running in read-only mode").into_response();
+    }
+    
+    next.run(request).await
+}
+
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+// Middleware to tag every log line of a request with an id, so SCGI failures
+// can be matched to the HTTP request that caused them. An id set by a reverse
+// proxy is kept; the id is echoed back in `X-Request-Id`.
+async fn request_id(request: Request<Body>, next: Next) -> Response<Body> {
+    let id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= 64 && v.bytes().all(|b| b.is_ascii_graphic()))
+        .map(str::to_string)
+        .unwrap_or_else(|| NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed).to_string());
+
+    let span = tracing::info_span!("request", id = %id, method = %request.method(), path = %request.uri().path());
+    let mut response = next.run(request).instrument(span).await;
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert("x-request-id", value);
+    }
+    response
+}
+
+/// `no_setup` leaves the setup wizard out entirely, so `/setup` is a 404
+fn create_router(shared: Arc<SharedState>, no_setup: bool) -> Router {
     // Wrapper handlers that extract AppState from SharedState
-    async fn index_handler(State(shared): State<Arc<SharedState>>) -> Response<Body> {
+    async fn index_handler(
+        State(shared): State<Arc<SharedState>>,
+        query: axum::extract::Query<routes::FilterQuery>,
+    ) -> Response<Body> {
         if let Some(state) = shared.get_app_state().await {
-            routes::index(State(state)).await.into_response()
+            routes::index(State(state), query).await.into_response()
         } else {
             Redirect::to("/setup").into_response()
         }
@@ -269,202 +589,1178 @@ fn create_router(shared: Arc<SharedState>, _force_setup: bool) -> Router {
         }
     }
     
-    async fn torrent_pause_handler(
+    async fn torrent_details_handler(
         State(shared): State<Arc<SharedState>>,
         Path(hash): Path<String>,
     ) -> Response<Body> {
         if let Some(state) = shared.get_app_state().await {
-            routes::torrent_pause(State(state), Path(hash)).await.into_response()
+            routes::torrent_details(State(state), Path(hash)).await.into_response()
         } else {
             Redirect::to("/setup").into_response()
         }
     }
     
-    async fn torrent_resume_handler(
+    async fn torrent_scrape_handler(
         State(shared): State<Arc<SharedState>>,
         Path(hash): Path<String>,
     ) -> Response<Body> {
         if let Some(state) = shared.get_app_state().await {
-            routes::torrent_resume(State(state), Path(hash)).await.into_response()
+            routes::torrent_scrape(State(state), Path(hash)).await.into_response()
         } else {
             Redirect::to("/setup").into_response()
         }
     }
     
-    async fn torrent_remove_handler(
+    async fn torrent_info_handler(
         State(shared): State<Arc<SharedState>>,
         Path(hash): Path<String>,
     ) -> Response<Body> {
         if let Some(state) = shared.get_app_state().await {
-            routes::torrent_remove(State(state), Path(hash)).await.into_response()
+            routes::torrent_info(State(state), Path(hash)).await
         } else {
             Redirect::to("/setup").into_response()
         }
     }
     
-    async fn torrent_toggle_star_handler(
+    async fn torrent_pause_handler(
         State(shared): State<Arc<SharedState>>,
         Path(hash): Path<String>,
+        query: axum::extract::Query<routes::FilterQuery>,
     ) -> Response<Body> {
         if let Some(state) = shared.get_app_state().await {
-            routes::torrent_toggle_star(State(state), Path(hash)).await.into_response()
+            routes::torrent_pause(State(state), Path(hash), query).await.into_response()
         } else {
             Redirect::to("/setup").into_response()
         }
     }
     
-    async fn add_torrent_modal_handler() -> Response<Body> {
-        routes::add_torrent_modal().await.into_response()
+    async fn torrent_resume_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+        query: axum::extract::Query<routes::FilterQuery>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::torrent_resume(State(state), Path(hash), query).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
     }
     
-    async fn add_torrent_handler(
+    async fn torrent_retry_handler(
         State(shared): State<Arc<SharedState>>,
-        form: axum::extract::Multipart,
+        Path(hash): Path<String>,
+        query: axum::extract::Query<routes::FilterQuery>,
     ) -> Response<Body> {
         if let Some(state) = shared.get_app_state().await {
-            routes::add_torrent(State(state), form).await.into_response()
+            routes::torrent_retry(State(state), Path(hash), query).await.into_response()
         } else {
             Redirect::to("/setup").into_response()
         }
     }
     
-    async fn stats_handler(State(shared): State<Arc<SharedState>>) -> Response<Body> {
+    async fn torrent_force_start_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+        query: axum::extract::Query<routes::FilterQuery>,
+    ) -> Response<Body> {
         if let Some(state) = shared.get_app_state().await {
-            routes::stats_partial(State(state)).await.into_response()
+            routes::torrent_force_start(State(state), Path(hash), query).await.into_response()
         } else {
             Redirect::to("/setup").into_response()
         }
     }
     
-    // SSE handlers for real-time updates
-    async fn sse_torrents_handler(
+    async fn torrent_stop_seeding_handler(
         State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
         query: axum::extract::Query<routes::FilterQuery>,
     ) -> Response<Body> {
         if let Some(state) = shared.get_app_state().await {
-            sse::torrent_events(State(state), query).await.into_response()
+            routes::torrent_stop_seeding(State(state), Path(hash), query).await.into_response()
         } else {
             Redirect::to("/setup").into_response()
         }
     }
     
-    async fn sse_torrents_filtered_handler(
+    async fn torrent_auto_stop_handler(
         State(shared): State<Arc<SharedState>>,
-        Path(filter): Path<String>,
+        Path(hash): Path<String>,
         query: axum::extract::Query<routes::FilterQuery>,
+        form: Result<Form<routes::AutoStopForm>, axum::extract::rejection::FormRejection>,
     ) -> Response<Body> {
         if let Some(state) = shared.get_app_state().await {
-            sse::torrent_filtered_events(State(state), Path(filter), query).await.into_response()
+            routes::torrent_auto_stop(State(state), Path(hash), query, form).await.into_response()
         } else {
             Redirect::to("/setup").into_response()
         }
     }
     
-    async fn sse_stats_handler(State(shared): State<Arc<SharedState>>) -> Response<Body> {
+    async fn torrent_add_tracker_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+        Form(form): Form<routes::TrackerForm>,
+    ) -> Response<Body> {
         if let Some(state) = shared.get_app_state().await {
-            sse::stats_events(State(state)).await.into_response()
+            routes::torrent_add_tracker(State(state), Path(hash), Form(form)).await.into_response()
         } else {
             Redirect::to("/setup").into_response()
         }
     }
     
-    // Setup route for first-time or forced setup
-    async fn setup_get_handler(
-        State(_shared): State<Arc<SharedState>>,
+    async fn torrent_remove_tracker_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(params): Path<(String, usize)>,
     ) -> Response<Body> {
-        setup_get().await.into_response()
+        if let Some(state) = shared.get_app_state().await {
+            routes::torrent_remove_tracker(State(state), Path(params)).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
     }
     
-    let shared_clone = shared.clone();
-    
-    let router = Router::new()
-        // Setup routes
-        .route("/setup", get(setup_get_handler))
-        .route("/setup", post(setup_post))
-        // Main pages
-        .route("/", get(index_handler))
-        .route("/torrents", get(torrents_list_handler))
-        .route("/torrents/filter/{filter}", get(torrents_filtered_handler))
-        // Torrent actions
-        .route("/torrent/{hash}/pause", post(torrent_pause_handler))
-        .route("/torrent/{hash}/resume", post(torrent_resume_handler))
-        .route("/torrent/{hash}/remove", post(torrent_remove_handler))
-        .route("/torrent/{hash}/toggle-star", post(torrent_toggle_star_handler))
-        // Add torrent
-        .route("/add-torrent", get(add_torrent_modal_handler))
-        .route("/add-torrent", post(add_torrent_handler))
-        // Stats
-        .route("/stats", get(stats_handler))
-        // SSE endpoints for real-time updates
-        .route("/events/torrents", get(sse_torrents_handler))
-        .route("/events/torrents/filter/{filter}", get(sse_torrents_filtered_handler))
-        .route("/events/stats", get(sse_stats_handler))
-        // Static files (embedded in binary)
-        .route("/static/{*path}", get(serve_static))
-        // State
-        .with_state(shared)
-        // Middleware - redirect to setup if not configured
-        .layer(middleware::from_fn_with_state(shared_clone, setup_guard))
-        .layer(CompressionLayer::new());
+    async fn torrent_retie_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+        form: axum::extract::Multipart,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::torrent_retie(State(state), Path(hash), shared.max_upload_mb, form).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
     
-    router
-}
-
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    // Parse CLI arguments
-    let args = Args::parse();
+    async fn torrent_set_directory_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+        Form(form): Form<routes::DirectoryForm>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::torrent_set_directory(State(state), Path(hash), Form(form)).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
     
-    // Load config if exists (CLI args can override)
-    let mut config = if let Some(socket) = args.socket.as_ref() {
-        // CLI socket provided - use it
-        Some(Config {
-            scgi_socket: socket.clone(),
-            bind_address: args.bind.clone().unwrap_or_else(|| "0.0.0.0:3000".to_string()),
-        })
-    } else if Config::exists() && !args.setup {
-        // Config file exists and not forcing setup
-        Config::load()
-    } else {
-        // No config - will show setup
-        None
-    };
+    async fn torrent_confirm_remove_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::torrent_confirm_remove(State(state), Path(hash)).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
     
-    // Test rtorrent connection if config exists
-    if let Some(ref cfg) = config {
-        let client = crate::rtorrent::RtorrentClient::new(cfg.scgi_socket.clone());
-        if !client.test_connection().await {
-            eprintln!("⚠️  Cannot connect to rtorrent at {}", cfg.scgi_socket);
-            eprintln!("   Starting setup wizard...");
-            config = None; // Force setup mode
+    async fn torrent_remove_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+        form: Result<Form<routes::RemoveForm>, axum::extract::rejection::FormRejection>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::torrent_remove(State(state), Path(hash), form).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
         }
     }
     
-    // Determine bind address
-    let bind_addr = args.bind
-        .or_else(|| config.as_ref().map(|c| c.bind_address.clone()))
-        .unwrap_or_else(|| "0.0.0.0:3000".to_string());
+    async fn torrent_toggle_star_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+        query: axum::extract::Query<routes::FilterQuery>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::torrent_toggle_star(State(state), Path(hash), query).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
     
-    // Create shared state
-    let shared = Arc::new(SharedState::new(config.clone()));
+    async fn torrent_open_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::torrent_open(State(state), Path(hash)).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
     
-    // Print startup message
-    if config.is_some() && !args.setup {
-        let cfg = config.as_ref().unwrap();
-        println!("🚀 VibeTorrent");
-        println!("   SCGI Socket: {}", cfg.scgi_socket);
-        println!("   Listening:   http://{}", bind_addr);
-    } else {
-        println!("🔧 VibeTorrent Setup");
-        println!("   Open http://{} in your browser", bind_addr);
+    async fn torrent_start_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::torrent_start(State(state), Path(hash)).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
     }
     
-    // Create unified router
-    let app = create_router(shared, args.setup);
+    async fn torrent_stop_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::torrent_stop(State(state), Path(hash)).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
     
-    // Start server
-    let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
-    axum::serve(listener, app).await?;
+    async fn torrent_close_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::torrent_close(State(state), Path(hash)).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
     
-    Ok(())
+    async fn torrent_hide_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::torrent_hide(State(state), Path(hash)).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn torrent_unhide_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::torrent_unhide(State(state), Path(hash)).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn torrent_move_up_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::torrent_move_up(State(state), Path(hash)).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn torrent_move_down_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::torrent_move_down(State(state), Path(hash)).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn torrent_queue_top_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::torrent_queue_top(State(state), Path(hash)).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn torrent_queue_bottom_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::torrent_queue_bottom(State(state), Path(hash)).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn trackers_replace_handler(
+        State(shared): State<Arc<SharedState>>,
+        form: Form<routes::TrackerReplaceForm>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::trackers_replace(State(state), form).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn settings_modal_handler(State(shared): State<Arc<SharedState>>) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::settings_modal(State(state)).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn settings_limits_handler(
+        State(shared): State<Arc<SharedState>>,
+        form: Form<routes::LimitsForm>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::settings_limits(State(state), form).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn settings_dht_handler(
+        State(shared): State<Arc<SharedState>>,
+        form: Form<routes::ToggleForm>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::settings_dht(State(state), form).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn settings_pex_handler(
+        State(shared): State<Arc<SharedState>>,
+        form: Form<routes::ToggleForm>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::settings_pex(State(state), form).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn settings_session_handler(
+        State(shared): State<Arc<SharedState>>,
+        form: Form<routes::SessionForm>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::settings_session(State(state), form).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn maintenance_save_session_handler(State(shared): State<Arc<SharedState>>) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::maintenance_save_session(State(state)).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn add_torrent_modal_handler() -> Response<Body> {
+        routes::add_torrent_modal().await.into_response()
+    }
+    
+    async fn add_torrent_handler(
+        State(shared): State<Arc<SharedState>>,
+        form: axum::extract::Multipart,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::add_torrent(State(state), shared.max_upload_mb, form).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn api_torrent_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(hash): Path<String>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::api_torrent(State(state), Path(hash)).await
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn api_methods_handler(State(shared): State<Arc<SharedState>>) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::api_methods(State(state)).await
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn api_method_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(name): Path<String>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::api_method(State(state), Path(name)).await
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn api_add_handler(
+        State(shared): State<Arc<SharedState>>,
+        request: Result<Json<routes::ApiAddRequest>, JsonRejection>,
+    ) -> Response<Body> {
+        // Scripts get JSON errors, not axum's plain-text rejection
+        let request = match request {
+            Ok(request) => request,
+            Err(rejection) => {
+                let error = serde_json::json!({ "error": rejection.body_text() });
+                return (StatusCode::BAD_REQUEST, Json(error)).into_response();
+            }
+        };
+        if let Some(state) = shared.get_app_state().await {
+            routes::api_add(State(state), request).await
+        } else {
+            api_not_configured()
+        }
+    }
+    
+    async fn api_version_handler(State(shared): State<Arc<SharedState>>) -> Response<Body> {
+        routes::api_version(shared.get_app_state().await).await.into_response()
+    }
+    
+    async fn stats_handler(State(shared): State<Arc<SharedState>>) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::stats_partial(State(state)).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn widget_handler(State(shared): State<Arc<SharedState>>) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            routes::widget(State(state)).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    // SSE handlers for real-time updates
+    async fn sse_torrents_handler(
+        State(shared): State<Arc<SharedState>>,
+        query: axum::extract::Query<routes::FilterQuery>,
+        params: axum::extract::Query<sse::StreamParams>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            sse::torrent_events(State(state), query, params).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn sse_torrents_filtered_handler(
+        State(shared): State<Arc<SharedState>>,
+        Path(filter): Path<String>,
+        query: axum::extract::Query<routes::FilterQuery>,
+        params: axum::extract::Query<sse::StreamParams>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            sse::torrent_filtered_events(State(state), Path(filter), query, params).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn sse_torrent_updates_handler(
+        State(shared): State<Arc<SharedState>>,
+        query: axum::extract::Query<routes::FilterQuery>,
+        params: axum::extract::Query<sse::StreamParams>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            sse::torrent_update_events(State(state), query, params).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    async fn sse_stats_handler(
+        State(shared): State<Arc<SharedState>>,
+        params: axum::extract::Query<sse::StreamParams>,
+    ) -> Response<Body> {
+        if let Some(state) = shared.get_app_state().await {
+            sse::stats_events(State(state), params).await.into_response()
+        } else {
+            Redirect::to("/setup").into_response()
+        }
+    }
+    
+    // Setup route for first-time or forced setup
+    async fn setup_get_handler(
+        State(shared): State<Arc<SharedState>>,
+    ) -> Response<Body> {
+        setup_get(&shared).await.into_response()
+    }
+    
+    let shared_clone = shared.clone();
+    let max_upload_bytes = usize::try_from(shared.max_upload_mb.saturating_mul(1024 * 1024)).unwrap_or(usize::MAX);
+    
+    let mut router = Router::new();
+    if !no_setup {
+        router = router.route("/setup", get(setup_get_handler).post(setup_post));
+    }
+    router
+        // Main pages
+        .route("/", get(index_handler))
+        .route("/torrents", get(torrents_list_handler))
+        .route("/torrents/filter/{filter}", get(torrents_filtered_handler))
+        // Torrent actions
+        .route("/torrent/{hash}/details", get(torrent_details_handler))
+        .route("/torrent/{hash}/info", get(torrent_info_handler))
+        .route("/torrent/{hash}/scrape", post(torrent_scrape_handler))
+        .route("/torrent/{hash}/tracker", post(torrent_add_tracker_handler))
+        .route("/torrent/{hash}/tracker/{index}/remove", post(torrent_remove_tracker_handler))
+        .route("/torrent/{hash}/pause", post(torrent_pause_handler))
+        .route("/torrent/{hash}/resume", post(torrent_resume_handler))
+        .route("/torrent/{hash}/force-start", post(torrent_force_start_handler))
+        .route("/torrent/{hash}/retry", post(torrent_retry_handler))
+        .route("/torrent/{hash}/stop-seeding", post(torrent_stop_seeding_handler))
+        .route("/torrent/{hash}/open", post(torrent_open_handler))
+        .route("/torrent/{hash}/start", post(torrent_start_handler))
+        .route("/torrent/{hash}/stop", post(torrent_stop_handler))
+        .route("/torrent/{hash}/close", post(torrent_close_handler))
+        .route("/torrent/{hash}/auto-stop", post(torrent_auto_stop_handler))
+        .route("/torrent/{hash}/directory", post(torrent_set_directory_handler))
+        .route(
+            "/torrent/{hash}/retie",
+            post(torrent_retie_handler).layer(DefaultBodyLimit::max(max_upload_bytes)),
+        )
+        .route("/torrent/{hash}/confirm-remove", get(torrent_confirm_remove_handler))
+        .route("/torrent/{hash}/remove", post(torrent_remove_handler))
+        .route("/torrent/{hash}/toggle-star", post(torrent_toggle_star_handler))
+        .route("/torrent/{hash}/hide", post(torrent_hide_handler))
+        .route("/torrent/{hash}/unhide", post(torrent_unhide_handler))
+        .route("/torrent/{hash}/move-up", post(torrent_move_up_handler))
+        .route("/torrent/{hash}/move-down", post(torrent_move_down_handler))
+        .route("/torrent/{hash}/queue-top", post(torrent_queue_top_handler))
+        .route("/torrent/{hash}/queue-bottom", post(torrent_queue_bottom_handler))
+        // Bulk tracker maintenance
+        .route("/trackers/replace", post(trackers_replace_handler))
+        .route("/settings", get(settings_modal_handler))
+        .route("/settings/limits", post(settings_limits_handler))
+        .route("/settings/dht", post(settings_dht_handler))
+        .route("/settings/pex", post(settings_pex_handler))
+        .route("/settings/session", post(settings_session_handler))
+        .route("/maintenance/save-session", post(maintenance_save_session_handler))
+        // Add torrent
+        .route("/add-torrent", get(add_torrent_modal_handler))
+        // Batches of .torrent files can exceed axum's 2 MB default
+        .route(
+            "/add-torrent",
+            post(add_torrent_handler).layer(DefaultBodyLimit::max(max_upload_bytes)),
+        )
+        // Stats
+        .route("/stats", get(stats_handler))
+        .route("/widget", get(widget_handler))
+        // JSON API, the only part other origins may call
+        .merge(
+            Router::new()
+                .route("/api/version", get(api_version_handler))
+                .route("/api/torrent/{hash}", get(api_torrent_handler))
+                .route("/api/methods", get(api_methods_handler))
+                .route("/api/method/{name}", get(api_method_handler))
+                .route("/api/add", post(api_add_handler))
+                .layer(api_cors(shared_clone.clone())),
+        )
+        // SSE endpoints for real-time updates
+        .route("/events/torrents", get(sse_torrents_handler))
+        .route("/events/torrents/filter/{filter}", get(sse_torrents_filtered_handler))
+        .route("/events/torrent-updates", get(sse_torrent_updates_handler))
+        .route("/events/stats", get(sse_stats_handler))
+        // Static files (embedded in binary)
+        .route("/static/{*path}", get(serve_static))
+        // State
+        .with_state(shared)
+        // Middleware - redirect to setup if not configured
+        .layer(middleware::from_fn_with_state(shared_clone.clone(), setup_guard))
+        .layer(middleware::from_fn_with_state(shared_clone, read_only_guard))
+        .layer(middleware::from_fn(request_id))
+        .layer(CompressionLayer::new().compress_when(compression_predicate()))
+}
+
+/// CORS for `/api/*`: origins in `cors_allowed_origins` may call it, preflights
+/// included. The allowlist is read from the current config on each request, so
+/// saving a new config applies it without a restart.
+fn api_cors(shared: Arc<SharedState>) -> CorsLayer {
+    let allow_origin = AllowOrigin::async_predicate(move |origin: HeaderValue, _: &axum::http::request::Parts| async move {
+        let Ok(origin) = origin.to_str() else {
+            return false;
+        };
+        shared
+            .config
+            .read()
+            .await
+            .as_ref()
+            .is_some_and(|c| c.cors_allowed_origins.iter().any(|allowed| allowed.trim_end_matches('/') == origin))
+    });
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST])
+        .allow_headers([header::CONTENT_TYPE])
+        .expose_headers([header::HeaderName::from_static("x-request-id")])
+        .max_age(std::time::Duration::from_secs(3600))
+}
+
+/// Compress HTML/CSS/JSON, but never SSE streams (gzip buffers events until a
+/// block fills up) or payloads that are already compressed.
+fn compression_predicate() -> impl Predicate {
+    DefaultPredicate::new()
+        .and(NotForContentType::SSE)
+        .and(NotForContentType::IMAGES)
+        .and(NotForContentType::const_new("font/"))
+        .and(NotForContentType::const_new("application/gzip"))
+        .and(NotForContentType::const_new("application/zip"))
+        .and(NotForContentType::const_new("application/x-bittorrent"))
+}
+
+/// `--check`: one `<check> ok|fail <detail>` line per step, stopping at the
+/// first failure. Returns whether every check passed.
+async fn run_check(config: Option<&Config>, source: &str) -> bool {
+    let Some(config) = config else {
+        println!("config fail {} missing or unreadable", source);
+        return false;
+    };
+    if let Err(e) = config.validate() {
+        println!("config fail {} {}", source, e);
+        return false;
+    }
+    println!("config ok {}", source);
+    
+    if let (Some(cert), Some(key)) = (&config.tls_cert, &config.tls_key) {
+        match tls::load_config(cert, key) {
+            Ok(_) => println!("tls ok {}", cert.display()),
+            Err(e) => {
+                println!("tls fail {}", e);
+                return false;
+            }
+        }
+    } else if config.tls_cert.is_some() || config.tls_key.is_some() {
+        println!("tls fail tls_cert and tls_key must be set together");
+        return false;
+    }
+    
+    let client = crate::rtorrent::RtorrentClient::new(config.scgi_socket.clone())
+        .with_scgi_env(&config.scgi_env);
+    if !client.test_connection().await {
+        println!("scgi fail cannot connect to {}", config.scgi_socket);
+        return false;
+    }
+    println!("scgi ok {}", config.scgi_socket);
+    
+    match client.get_client_version().await {
+        Ok(version) => {
+            println!("version ok {}", version);
+            true
+        }
+        Err(e) => {
+            println!("version fail {}", e);
+            false
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    // Parse CLI arguments
+    let args = Args::parse();
+    
+    // RUST_LOG overrides the default: info for VibeTorrent, warnings from its dependencies
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("warn,vibetorrent=info"));
+    match args.log_format {
+        LogFormat::Pretty => tracing_subscriber::fmt().with_env_filter(filter).init(),
+        // Span fields (the request id) are kept with each event
+        LogFormat::Json => tracing_subscriber::fmt()
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .with_env_filter(filter)
+            .init(),
+    }
+    
+    if let Some(dir) = args.templates_dir.as_ref() {
+        match theme::load(dir) {
+            Ok(loaded) if loaded.is_empty() => {
+                tracing::warn!("No overridable templates in {}", dir.display());
+            }
+            Ok(loaded) => tracing::info!("Using template overrides: {}", loaded.join(", ")),
+            Err(e) => {
+                eprintln!("❌ {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    
+    // A template that can't render stops the start here rather than failing
+    // every request that needs it
+    match templates::self_test() {
+        Ok(count) if args.check => println!("templates ok {} rendered", count),
+        Ok(count) => tracing::debug!("Template self-test: {} rendered", count),
+        Err(e) if args.check => {
+            println!("templates fail {}", e);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("❌ Template self-test failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+    
+    let config_location = ConfigLocation::new(args.config.clone());
+    
+    // Load config if exists (CLI args can override)
+    let mut config = if let Some(socket) = args.socket.as_ref() {
+        // CLI socket provided - use it
+        Some(Config {
+            scgi_socket: socket.clone(),
+            bind_address: args.bind.clone().unwrap_or_else(|| "0.0.0.0:3000".to_string()),
+            ..Config::default()
+        })
+    } else if Config::exists(&config_location) && !args.setup {
+        // Config file exists and not forcing setup
+        Config::load(&config_location)
+    } else {
+        // No config - will show setup
+        None
+    };
+    
+    if args.read_only {
+        if let Some(cfg) = config.as_mut() {
+            cfg.read_only = true;
+        }
+    }
+    
+    // A value that parses but can't work fails the start, not every request
+    // that uses it; --check reports it below
+    if let (Some(Err(e)), false) = (config.as_ref().map(Config::validate), args.check) {
+        eprintln!("❌ Invalid config {}: {}", config_location.path().display(), e);
+        std::process::exit(1);
+    }
+    
+    if args.check {
+        let source = if args.socket.is_some() {
+            "--socket".to_string()
+        } else {
+            config_location.path().display().to_string()
+        };
+        let passed = run_check(config.as_ref(), &source).await;
+        std::process::exit(if passed { 0 } else { 1 });
+    }
+    
+    // Headless deployments fail fast instead of waiting for someone to run setup
+    if args.no_setup && config.is_none() {
+        eprintln!("❌ No config found at {} and --no-setup is set", config_location.path().display());
+        eprintln!("   Pass --socket or create the config file");
+        std::process::exit(1);
+    }
+    
+    // Test rtorrent connection if config exists
+    let mut unreachable_config = None;
+    if let Some(ref cfg) = config {
+        let client = crate::rtorrent::RtorrentClient::new(cfg.scgi_socket.clone())
+            .with_scgi_env(&cfg.scgi_env);
+        if !client.test_connection().await {
+            if args.no_setup {
+                eprintln!("❌ Cannot connect to rtorrent at {} and --no-setup is set", cfg.scgi_socket);
+                std::process::exit(1);
+            }
+            eprintln!("⚠️  Cannot connect to rtorrent at {}", cfg.scgi_socket);
+            eprintln!("   Starting setup wizard; the config is used as soon as rtorrent answers");
+            // Setup mode until the first request that finds rTorrent reachable
+            unreachable_config = config.take();
+        }
+    }
+    // The bind address and TLS settings apply even while waiting for rTorrent
+    let listen_config = config.as_ref().or(unreachable_config.as_ref());
+    
+    let max_upload_mb = listen_config.map_or(Config::default().max_upload_mb, |c| c.max_upload_mb);
+    
+    // Determine bind address
+    let bind_addr = args.bind
+        .or_else(|| listen_config.map(|c| c.bind_address.clone()))
+        .unwrap_or_else(|| "0.0.0.0:3000".to_string());
+    
+    // Both TLS files must load now, so a bad path or key fails the start
+    // instead of the first request
+    let tls = match listen_config.filter(|_| !args.setup) {
+        Some(Config { tls_cert: Some(cert), tls_key: Some(key), .. }) => {
+            match tls::load_config(cert, key) {
+                Ok(tls_config) => Some((tls_config, cert.clone(), key.clone())),
+                Err(e) => {
+                    eprintln!("❌ {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some(Config { tls_cert: None, tls_key: None, .. }) | None => None,
+        Some(_) => {
+            eprintln!("❌ tls_cert and tls_key must be set together");
+            std::process::exit(1);
+        }
+    };
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    
+    // Create shared state
+    let shared = Arc::new(
+        SharedState::new(config.clone(), unreachable_config, config_location).with_max_upload_mb(max_upload_mb),
+    );
+    
+    // Print startup message; JSON output stays one object per line
+    if args.log_format == LogFormat::Json {
+        match config.as_ref().filter(|_| !args.setup) {
+            Some(cfg) => tracing::info!(socket = %cfg.scgi_socket, listen = %bind_addr, scheme, "VibeTorrent started"),
+            None => tracing::info!(listen = %bind_addr, "VibeTorrent setup wizard started"),
+        }
+    } else if let Some(cfg) = config.as_ref().filter(|_| !args.setup) {
+        println!("🚀 VibeTorrent");
+        println!("   SCGI Socket: {}", cfg.scgi_socket);
+        println!("   Listening:   {}://{}", scheme, bind_addr);
+    } else {
+        println!("🔧 VibeTorrent Setup");
+        println!("   Open http://{} in your browser", bind_addr);
+    }
+    
+    // Create unified router
+    let app = create_router(shared.clone(), args.no_setup);
+    
+    // Start server
+    let listener = match tokio::net::TcpListener::bind(&bind_addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("❌ {}", bind_error_message(&bind_addr, &e));
+            eprintln!("   Change bind_address in {} or pass --bind", shared.config_location.path().display());
+            std::process::exit(1);
+        }
+    };
+    if let Ok(addr) = listener.local_addr() {
+        let _ = shared.listen_addr.set(addr);
+    }
+    match tls {
+        Some((tls_config, cert, key)) => {
+            tls::serve(listener, app, tls_config, cert, key, shutdown_signal(shared.clone())).await?;
+        }
+        None => {
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown_signal(shared.clone()))
+                .await?;
+        }
+    }
+
+    // Catches anything changed by requests that finished after the signal
+    if let Some(state) = shared.get_app_state().await {
+        state.flush().await;
+    }
+    
+    Ok(())
+}
+
+/// Resolves on Ctrl-C or SIGTERM, after saving stars and queue order and ending
+/// SSE streams so open pages don't hold up the exit
+async fn shutdown_signal(shared: Arc<SharedState>) {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::warn!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+
+    tracing::info!("Shutting down");
+    if let Some(state) = shared.get_app_state().await {
+        state.flush().await;
+        state.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRtorrent};
+
+    fn empty_rtorrent() -> MockRtorrent {
+        MockRtorrent::start(|xml| match crate::rtorrent::xml_method_name(xml) {
+            "d.multicall2" => testing::torrents_response(&[]),
+            _ => testing::response("<string></string>"),
+        })
+    }
+
+    fn polls(mock: &MockRtorrent) -> usize {
+        mock.methods().iter().filter(|m| *m == "d.multicall2").count()
+    }
+
+    #[tokio::test]
+    async fn config_change_stops_the_old_poller_while_its_state_is_still_held() {
+        let dir = tempfile::tempdir().unwrap();
+        let old_rtorrent = empty_rtorrent();
+        let new_rtorrent = empty_rtorrent();
+        let config = |mock: &MockRtorrent| Config { scgi_socket: mock.socket_path(), ..Config::default() };
+        let shared = SharedState::new(
+            Some(config(&old_rtorrent)),
+            None,
+            ConfigLocation::new(Some(dir.path().join("config.json"))),
+        );
+        // Like an SSE stream still holding the old state
+        let old_state = shared.get_app_state().await.unwrap();
+
+        shared.update_config(config(&new_rtorrent)).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(1), old_state.stopped()).await.unwrap();
+        let old_polls = polls(&old_rtorrent);
+
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        assert_eq!(polls(&old_rtorrent), old_polls);
+        assert!(polls(&new_rtorrent) >= 2);
+    }
+
+    #[tokio::test]
+    async fn unreachable_rtorrent_is_retried_at_most_once_per_interval() {
+        let dir = tempfile::tempdir().unwrap();
+        let rtorrent = empty_rtorrent();
+        let link = dir.path().join("rtorrent.sock");
+        let config = Config { scgi_socket: link.display().to_string(), ..Config::default() };
+        let shared = SharedState::new(None, Some(config), ConfigLocation::new(Some(dir.path().join("config.json"))));
+
+        assert!(!shared.recover().await);
+        // rTorrent is up now, but the last attempt was too recent to try again
+        std::os::unix::fs::symlink(rtorrent.path(), &link).unwrap();
+        assert!(!shared.recover().await);
+        assert!(!shared.is_configured().await);
+
+        *shared.last_recovery.lock().unwrap() = Some(Instant::now() - RECOVERY_INTERVAL);
+        assert!(shared.recover().await);
+        assert!(shared.is_configured().await);
+    }
+
+    #[tokio::test]
+    async fn upload_errors_report_the_limit_the_router_applies() {
+        let dir = tempfile::tempdir().unwrap();
+        let rtorrent = empty_rtorrent();
+        // Saved after startup, so not in effect
+        let config = Config { scgi_socket: rtorrent.socket_path(), max_upload_mb: 500, ..Config::default() };
+        let shared = SharedState::new(Some(config), None, ConfigLocation::new(Some(dir.path().join("config.json"))))
+            .with_max_upload_mb(1);
+        let app = create_router(Arc::new(shared), false);
+
+        let mut body = b"--XYZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"big.torrent\"\r\n\r\n".to_vec();
+        body.resize(body.len() + 2 * 1024 * 1024, b'x');
+        body.extend_from_slice(b"\r\n--XYZ--\r\n");
+        let request = Request::post("/add-torrent")
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=XYZ")
+            .body(Body::from(body))
+            .unwrap();
+        let response = tower::ServiceExt::oneshot(app, request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.contains("limited to 1 MB"), "{}", body);
+    }
+
+    #[tokio::test]
+    async fn setup_saves_only_a_config_that_was_applied() {
+        let dir = tempfile::tempdir().unwrap();
+        let location = ConfigLocation::new(Some(dir.path().join("config.json")));
+        let shared = Arc::new(SharedState::new(None, None, location.clone()));
+        let form = |scgi_socket: String| {
+            Form(SetupForm { scgi_socket, bind_address: Config::default().bind_address })
+        };
+
+        let missing = dir.path().join("missing.sock").display().to_string();
+        let response = setup_post(State(shared.clone()), form(missing)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(!location.path().exists());
+        assert!(!shared.is_configured().await);
+
+        let rtorrent = empty_rtorrent();
+        let response = setup_post(State(shared.clone()), form(rtorrent.socket_path())).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert!(shared.is_configured().await);
+        assert_eq!(Config::load(&location).unwrap().scgi_socket, rtorrent.socket_path());
+    }
+
+    #[tokio::test]
+    async fn no_setup_leaves_the_setup_routes_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let rtorrent = empty_rtorrent();
+        let config = Config { scgi_socket: rtorrent.socket_path(), ..Config::default() };
+        let location = ConfigLocation::new(Some(dir.path().join("config.json")));
+        let shared = Arc::new(SharedState::new(Some(config), None, location));
+
+        for no_setup in [false, true] {
+            let app = create_router(shared.clone(), no_setup);
+            let get = tower::ServiceExt::oneshot(app.clone(), Request::get("/setup").body(Body::empty()).unwrap());
+            let post = Request::post("/setup")
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from("scgi_socket=%2Ftmp%2Fother.sock&bind_address=0.0.0.0%3A3000"))
+                .unwrap();
+            let get = get.await.unwrap().status();
+            let post = tower::ServiceExt::oneshot(app, post).await.unwrap().status();
+            if no_setup {
+                assert_eq!((get, post), (StatusCode::NOT_FOUND, StatusCode::NOT_FOUND));
+            } else {
+                assert_eq!(get, StatusCode::OK);
+                assert_ne!(post, StatusCode::NOT_FOUND);
+            }
+        }
+    }
+
+    async fn post_api_add(shared: SharedState, body: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::post("/api/add")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = tower::ServiceExt::oneshot(create_router(Arc::new(shared), false), request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn api_add_answers_scripts_in_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let location = || ConfigLocation::new(Some(dir.path().join("config.json")));
+
+        let (status, body) = post_api_add(SharedState::new(None, None, location()), r#"{"url": "magnet:?"}"#).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["error"], "not configured");
+
+        let rtorrent = empty_rtorrent();
+        let config = Config { scgi_socket: rtorrent.socket_path(), ..Config::default() };
+        let (status, body) = post_api_add(SharedState::new(Some(config), None, location()), r#"{"url": "#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].as_str().is_some_and(|error| !error.is_empty()), "{}", body);
+    }
+
+    #[test]
+    fn privileged_ports_are_below_1024() {
+        assert!(is_privileged_port("0.0.0.0:80"));
+        assert!(is_privileged_port("[::]:443"));
+        assert!(is_privileged_port("127.0.0.1:1023"));
+        assert!(!is_privileged_port("127.0.0.1:1024"));
+        assert!(!is_privileged_port("0.0.0.0:3000"));
+        // Port 0 picks a free port, and garbage isn't a port at all
+        assert!(!is_privileged_port("0.0.0.0:0"));
+        assert!(!is_privileged_port("localhost"));
+        assert!(!is_privileged_port("0.0.0.0:http"));
+    }
+
+    #[test]
+    fn bind_errors_say_what_to_do() {
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        let message = bind_error_message("192.168.1.2:80", &denied);
+        assert!(message.contains("CAP_NET_BIND_SERVICE"), "{}", message);
+        assert!(message.contains("192.168.1.2:3000"), "{}", message);
+
+        let message = bind_error_message("0.0.0.0:8080", &denied);
+        assert!(!message.contains("CAP_NET_BIND_SERVICE"), "{}", message);
+
+        let in_use = std::io::Error::from(std::io::ErrorKind::AddrInUse);
+        let message = bind_error_message("0.0.0.0:8080", &in_use);
+        assert!(message.contains("another program is using that port"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn setup_accepts_an_address_on_the_port_it_is_listening_on() {
+        let dir = tempfile::tempdir().unwrap();
+        let rtorrent = empty_rtorrent();
+        let shared = Arc::new(SharedState::new(None, None, ConfigLocation::new(Some(dir.path().join("config.json")))));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let form = |bind_address: String| {
+            Form(SetupForm { scgi_socket: rtorrent.socket_path(), bind_address })
+        };
+
+        // Without knowing its own address, the port looks taken
+        let response = setup_post(State(shared.clone()), form(format!("127.0.0.1:{}", port))).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        shared.listen_addr.set(listener.local_addr().unwrap()).unwrap();
+        let response = setup_post(State(shared.clone()), form(format!("127.0.0.1:{}", port))).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    }
+
+    async fn get_static(path: &str, range: Option<&str>) -> Response<Body> {
+        let mut headers = HeaderMap::new();
+        if let Some(range) = range {
+            headers.insert(header::RANGE, HeaderValue::from_str(range).unwrap());
+        }
+        serve_static(Path(path.to_string()), headers).await
+    }
+
+    #[tokio::test]
+    async fn static_files_serve_byte_ranges() {
+        let full = StaticFiles::get("icon.svg").unwrap().data;
+
+        let response = get_static("icon.svg", Some("bytes=0-9")).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], format!("bytes 0-9/{}", full.len()).as_str());
+        assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], &full[..10]);
+
+        let response = get_static("icon.svg", Some("bytes=-5")).await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], &full[full.len() - 5..]);
+
+        let response = get_static("icon.svg", None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ACCEPT_RANGES], "bytes");
+    }
+
+    #[tokio::test]
+    async fn static_range_past_the_end_is_unsatisfiable() {
+        let len = StaticFiles::get("icon.svg").unwrap().data.len();
+
+        let response = get_static("icon.svg", Some(&format!("bytes={}-", len))).await;
+
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], format!("bytes */{}", len).as_str());
+    }
+
+    #[tokio::test]
+    async fn ranged_static_responses_are_not_compressed() {
+        use tower::ServiceExt;
+        let app = Router::new()
+            .route("/static/{*path}", get(serve_static))
+            .layer(CompressionLayer::new());
+        let request = |range: Option<&str>| {
+            let mut request = Request::get("/static/icon.svg").header(header::ACCEPT_ENCODING, "gzip");
+            if let Some(range) = range {
+                request = request.header(header::RANGE, range);
+            }
+            request.body(Body::empty()).unwrap()
+        };
+
+        let full = app.clone().oneshot(request(None)).await.unwrap();
+        let ranged = app.oneshot(request(Some("bytes=0-99"))).await.unwrap();
+
+        assert_eq!(full.headers()[header::CONTENT_ENCODING], "gzip");
+        assert_eq!(ranged.status(), StatusCode::PARTIAL_CONTENT);
+        assert!(ranged.headers().get(header::CONTENT_ENCODING).is_none());
+    }
 }
@@ -1,66 +1,119 @@
 use axum::{
-    extract::{Path, Query, State, Multipart},
+    extract::{rejection::FormRejection, Path, Query, State, Multipart},
+    Form, Json,
     http::StatusCode,
-    response::{Html, IntoResponse},
+    response::{Html, IntoResponse, Response},
 };
+use futures::stream::{self, StreamExt};
 use std::sync::Arc;
 use serde::Deserialize;
 use askama::Template;
 
 use crate::error::{AppError, Result};
-use crate::rtorrent::{TorrentState, GlobalStats};
-use crate::state::AppState;
+use crate::rtorrent::{self, GlobalStats, TrackerScrape, TransferLimits};
+use crate::state::{AppState, QueueMove};
+use crate::services::automation;
 use crate::services::torrents as torrents_service;
 use crate::templates::{
     IndexTemplate, TorrentRowTemplate, 
-    AddTorrentModalTemplate, StatsTemplate, TorrentView,
+    AddOutcome, AddTorrentModalTemplate, AddTorrentResultTemplate, ConfirmRemoveModalTemplate, PeerDiscoveryTemplate, ScrapeView, SettingsModalTemplate, StatsTemplate, TorrentDetailsTemplate,
+    TieView, TiedFileTemplate, TorrentView, TrackerListTemplate, TrackerListView, TrackerScrapeTemplate, WidgetTemplate,
 };
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct FilterQuery {
     pub search: Option<String>,
     pub sort: Option<String>,
     pub order: Option<String>,
+    /// Overrides the configured `default_limit`; 0 shows every torrent
+    pub limit: Option<usize>,
+    /// Only torrents with this label, summarized above the list
+    pub label: Option<String>,
+    /// Lower bound of a progress range in percent, see [`Self::progress_range`]
+    pub progress_min: Option<f64>,
+    /// Upper bound of a progress range in percent
+    pub progress_max: Option<f64>,
+    /// `1` renders rows as stacked, touch-friendly cards at every width
+    pub mobile: Option<String>,
+}
+
+impl FilterQuery {
+    /// Whether the client asked for the compact list, see `mobile`
+    pub fn is_mobile(&self) -> bool {
+        matches!(self.mobile.as_deref(), Some("1" | "true"))
+    }
+
+    /// Inclusive progress range to keep, in percent. A missing bound is open,
+    /// bounds given the wrong way round are swapped, and 0–0 (the controls'
+    /// resting state) means no range at all.
+    pub fn progress_range(&self) -> Option<(f64, f64)> {
+        let min = self.progress_min.unwrap_or(0.0).clamp(0.0, 100.0);
+        let max = self.progress_max.unwrap_or(100.0).clamp(0.0, 100.0);
+        match (self.progress_min, self.progress_max) {
+            (None, None) => None,
+            _ if min == 0.0 && max == 0.0 => None,
+            _ => Some((min.min(max), min.max(max))),
+        }
+    }
 }
 
 /// Main index page - full SSR
 pub async fn index(
     State(state): State<Arc<AppState>>,
+    Query(query): Query<FilterQuery>,
 ) -> Result<impl IntoResponse> {
-    // Use cached torrents instead of querying rTorrent directly
-    let torrents = state.latest_torrents().await
-        .map(|arc| (*arc).clone())
-        .unwrap_or_default();
-    let stats = state.latest_stats().await
-        .map(|arc| (*arc).clone())
-        .unwrap_or_else(|| GlobalStats {
-            down_rate: 0,
-            up_rate: 0,
-            free_disk_space: 2_000_000_000_000,
-            active_peers: 0,
-        });
-    let rtorrent_version = state.rtorrent.get_client_version().await.unwrap_or_else(|_| "Disconnected".to_string());
-    
-    let mut torrent_views = Vec::new();
-    for t in &torrents {
-        let is_starred = state.is_starred(&t.hash).await;
-        torrent_views.push(TorrentView::from_torrent(t, is_starred));
-    }
+    // Prefer the poller's caches; only a cold cache (first paint after startup)
+    // costs live SCGI calls, and those run concurrently
+    let (torrents, stats, rtorrent_version) = tokio::join!(
+        state.fresh_torrents(),
+        async {
+            match state.latest_stats().await {
+                Some(cached) => Some((*cached).clone()),
+                None => state.rtorrent.get_global_stats().await
+                    .inspect_err(|e| tracing::warn!("index: get_global_stats failed: {}", e))
+                    .ok(),
+            }
+        },
+        async {
+            match state.rtorrent_version().await {
+                Some(cached) => Some(cached),
+                None => state.rtorrent.get_client_version().await.ok(),
+            }
+        },
+    );
+    // Rates and peers come from the torrents, as in the poller; a live stats fetch has none
+    let mut stats = stats.unwrap_or_else(|| GlobalStats::empty(state.config.rate_unit));
+    stats.add_torrent_totals(&torrents);
+    let rtorrent_version = rtorrent_version.unwrap_or_else(|| "Disconnected".to_string());
     
-    let total_count = torrents.len();
-    let downloading_count = torrents.iter().filter(|t| t.state == TorrentState::Downloading).count();
-    let seeding_count = torrents.iter().filter(|t| t.state == TorrentState::Seeding).count();
-    let paused_count = torrents.iter().filter(|t| t.state == TorrentState::Paused).count();
+    // Same sidebar counts as the list partial and the SSE stream
+    let positions = state.queue_positions(&torrents).await;
+    let hidden = state.hidden_torrents.read().await.clone();
+    let counts = torrents_service::calculate_counts(&torrents, &hidden);
+    let visible: Vec<_> = torrents.iter().filter(|t| !hidden.contains(&t.hash)).cloned().collect();
+    let (shown, hidden_count) = torrents_service::apply_limit(&visible, state.config.default_limit);
+    let starred = state.starred_torrents.read().await.clone();
+    let torrent_views = torrents_service::torrent_views(shown, &starred, &positions);
     
     let template = IndexTemplate {
         stats,
         torrents: torrent_views,
-        total_count,
-        downloading_count,
-        seeding_count,
-        paused_count,
+        hidden_count,
+        group: None,
+        total_count: counts.total,
+        downloading_count: counts.downloading,
+        seeding_count: counts.seeding,
+        paused_count: counts.paused,
+        stopped_count: counts.stopped,
+        errored_count: counts.errored,
+        hidden_torrents_count: counts.hidden,
+        private_count: counts.private,
+        public_count: counts.total - counts.private,
         rtorrent_version,
         cache_version: crate::templates::CACHE_VERSION.clone(),
+        read_only: state.config.read_only,
+        compact: query.is_mobile(),
+        missing_methods: state.missing_methods().await,
     };
     
     Ok(Html(template.render().map_err(|e| AppError::TemplateError(e.to_string()))?))
@@ -71,10 +124,8 @@ pub async fn torrents_list(
     State(state): State<Arc<AppState>>,
     Query(query): Query<FilterQuery>,
 ) -> Result<impl IntoResponse> {
-    // Use cached torrents - no rTorrent query needed for filtering/sorting
-    let all_torrents = state.latest_torrents().await
-        .map(|arc| (*arc).clone())
-        .unwrap_or_default();
+    // Cached snapshot unless it's stale - filtering/sorting needs no rTorrent query
+    let all_torrents = state.fresh_torrents().await;
     let html = torrents_service::render_torrents_html(&state, &query, None, &all_torrents).await?;
     Ok(Html(html))
 }
@@ -85,162 +136,1891 @@ pub async fn torrents_filtered(
     Path(filter): Path<String>,
     Query(query): Query<FilterQuery>,
 ) -> Result<impl IntoResponse> {
-    // Use cached torrents - no rTorrent query needed for filtering
-    let all_torrents = state.latest_torrents().await
-        .map(|arc| (*arc).clone())
-        .unwrap_or_default();
+    // Cached snapshot unless it's stale - filtering needs no rTorrent query
+    let all_torrents = state.fresh_torrents().await;
     let html = torrents_service::render_torrents_html(&state, &query, Some(filter.as_str()), &all_torrents).await?;
     Ok(Html(html))
 }
 
+/// Torrent detail modal
+pub async fn torrent_details(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+) -> Result<impl IntoResponse> {
+    let torrents = state.latest_torrents().await.ok_or_else(|| AppError::NotFound("Cache not ready".to_string()))?;
+    let torrent = torrents
+        .iter()
+        .find(|t| t.hash == hash)
+        .ok_or_else(|| AppError::NotFound("Torrent not found".to_string()))?;
+    
+    // Independent of each other, so asked for all at once
+    let wants_directory = !state.config.read_only && !torrent.complete;
+    let (scrapes, trackers, tied_to_file, directory) = tokio::join!(
+        state.rtorrent.get_tracker_scrape(&hash),
+        state.rtorrent.get_trackers(&hash),
+        state.rtorrent.get_tied_file(&hash),
+        async {
+            if wants_directory {
+                Some(state.rtorrent.get_directory(&hash).await)
+            } else {
+                None
+            }
+        },
+    );
+    let scrapes = details_part(scrapes, "tracker scrape", &hash);
+    let trackers = details_part(trackers, "get_trackers", &hash);
+    let tied_to_file = details_part(tied_to_file, "get_tied_file", &hash).filter(|path| !path.is_empty());
+    // An unreadable location counts as missing, so re-tying is still offered
+    let tie_missing = match &tied_to_file {
+        Some(path) => !tokio::fs::try_exists(path).await.unwrap_or(false),
+        None => false,
+    };
+    let directory = directory.and_then(|directory| details_part(directory, "get_directory", &hash));
+    
+    let is_starred = state.is_starred(&hash).await;
+    let template = TorrentDetailsTemplate {
+        torrent: TorrentView::from_torrent(torrent, is_starred)
+            .with_queue_position(state.queue_position(&hash).await),
+        scrape: ScrapeView::new(&hash, scrapes.as_deref(), !state.config.read_only),
+        tracker_list: TrackerListView { hash: hash.clone(), trackers, editable: !state.config.read_only },
+        tie: TieView::new(&hash, tied_to_file.as_deref(), tie_missing, state.config.read_only),
+        directory,
+        is_hidden: state.is_hidden(&hash).await,
+        read_only: state.config.read_only,
+    };
+    Ok(Html(template.render().map_err(|e| AppError::TemplateError(e.to_string()))?))
+}
+
+/// A details section's data, or `None` with a warning if rTorrent didn't give it
+fn details_part<T>(result: Result<T>, call: &str, hash: &str) -> Option<T> {
+    result
+        .inspect_err(|e| tracing::warn!("torrent_details: {} failed for {}: {}", call, hash, e))
+        .ok()
+}
+
+/// Ask the trackers for fresh seed/leech counts and return the updated summary.
+///
+/// Scrapes are answered asynchronously, so this waits briefly for a newer
+/// scrape time before rendering whatever rTorrent has.
+pub async fn torrent_scrape(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+) -> Result<impl IntoResponse> {
+    let last_scrape = |scrapes: &[TrackerScrape]| scrapes.iter().map(|s| s.last_scrape).max().unwrap_or(0);
+    
+    let before = state.rtorrent.get_tracker_scrape(&hash).await?;
+    let mut scrapes = before.clone();
+    if state.rtorrent.send_scrape(&hash).await? {
+        for _ in 0..SCRAPE_WAIT_ATTEMPTS {
+            tokio::time::sleep(SCRAPE_WAIT_INTERVAL).await;
+            scrapes = state.rtorrent.get_tracker_scrape(&hash).await?;
+            if last_scrape(&scrapes) > last_scrape(&before) {
+                break;
+            }
+        }
+    } else {
+        tracing::debug!("d.tracker.send_scrape unavailable, showing last known scrape for {}", hash);
+    }
+    
+    let template = TrackerScrapeTemplate {
+        scrape: ScrapeView::new(&hash, Some(&scrapes), !state.config.read_only),
+    };
+    Ok(Html(template.render().map_err(|e| AppError::TemplateError(e.to_string()))?))
+}
+
+const SCRAPE_WAIT_ATTEMPTS: usize = 6;
+const SCRAPE_WAIT_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+#[derive(Debug, Deserialize)]
+pub struct TrackerForm {
+    pub url: String,
+}
+
+/// Add an announce URL to a torrent and return its updated tracker list.
+/// A tracker that was removed (only disabled by rTorrent) is enabled again.
+pub async fn torrent_add_tracker(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+    Form(form): Form<TrackerForm>,
+) -> Result<impl IntoResponse> {
+    let url = form.url.trim();
+    if !rtorrent::is_valid_tracker_url(url) {
+        return Err(AppError::BadRequest("Tracker URL must be http, https or udp".to_string()));
+    }
+    let trackers = state.rtorrent.get_trackers(&hash).await?;
+    match trackers.iter().find(|t| t.url == url) {
+        Some(tracker) if tracker.is_enabled => {
+            return Err(AppError::BadRequest("The torrent already has this tracker".to_string()));
+        }
+        Some(tracker) => {
+            state.rtorrent.enable_tracker(&hash, tracker.index).await?;
+            tracing::info!("Enabled tracker {} of {} again", url, hash);
+        }
+        None => {
+            state.rtorrent.add_tracker(&hash, url).await?;
+            tracing::info!("Added tracker {} to {}", url, hash);
+        }
+    }
+    tracker_list(&state, &hash).await
+}
+
+/// Remove a tracker from a torrent and return its updated tracker list.
+/// rTorrent only disables it, so it stays listed as removed.
+pub async fn torrent_remove_tracker(
+    State(state): State<Arc<AppState>>,
+    Path((hash, index)): Path<(String, usize)>,
+) -> Result<impl IntoResponse> {
+    let trackers = state.rtorrent.get_trackers(&hash).await?;
+    let Some(tracker) = trackers.iter().find(|t| t.index == index) else {
+        return Err(AppError::NotFound("Tracker not found".to_string()));
+    };
+    
+    state.rtorrent.remove_tracker(&hash, index).await?;
+    tracing::info!("Removed tracker {} from {}", tracker.url, hash);
+    tracker_list(&state, &hash).await
+}
+
+/// Tie a torrent to an uploaded copy of its `.torrent` file, e.g. after the
+/// original was deleted or for a torrent added from a magnet.
+///
+/// The upload must have the torrent's infohash. It's saved to
+/// [`AppState::torrents_dir`], which rTorrent needs to be able to read, and
+/// the updated tie is returned. `max_mb` is the body limit the router applies,
+/// named if the upload goes over it.
+pub async fn torrent_retie(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+    max_mb: u64,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse> {
+    let mut data = None;
+    while let Some(field) = multipart.next_field().await.map_err(|e| upload_error(e, max_mb))? {
+        if field.name() == Some("file") {
+            data = Some(field.bytes().await.map_err(|e| upload_error(e, max_mb))?);
+        }
+    }
+    let data = data
+        .filter(|data| !data.is_empty())
+        .ok_or_else(|| AppError::BadRequest("Choose a .torrent file".to_string()))?;
+    if !crate::bencode::is_torrent(&data) {
+        return Err(AppError::BadRequest("Not a valid .torrent file".to_string()));
+    }
+    if !crate::bencode::info_hash(&data).is_some_and(|info_hash| info_hash.eq_ignore_ascii_case(&hash)) {
+        return Err(AppError::BadRequest("This .torrent is for a different torrent".to_string()));
+    }
+    
+    let dir = state.torrents_dir();
+    let path = std::path::absolute(dir.join(format!("{}.torrent", hash.to_ascii_uppercase())))
+        .map_err(|e| AppError::BadRequest(format!("Cannot resolve {}: {}", dir.display(), e)))?;
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Cannot create {}: {}", dir.display(), e)))?;
+    tokio::fs::write(&path, &data)
+        .await
+        .map_err(|e| AppError::BadRequest(format!("Cannot save {}: {}", path.display(), e)))?;
+    
+    let path = path.to_string_lossy();
+    state.rtorrent.set_tied_file(&hash, &path).await?;
+    tracing::info!("Tied {} to {}", hash, path);
+    
+    // Just written, so not missing
+    let template = TiedFileTemplate { tie: TieView::new(&hash, Some(&path), false, state.config.read_only) };
+    Ok(Html(template.render().map_err(|e| AppError::TemplateError(e.to_string()))?))
+}
+
+async fn tracker_list(state: &AppState, hash: &str) -> Result<Html<String>> {
+    let template = TrackerListTemplate {
+        tracker_list: TrackerListView {
+            hash: hash.to_string(),
+            trackers: Some(state.rtorrent.get_trackers(hash).await?),
+            editable: !state.config.read_only,
+        },
+    };
+    Ok(Html(template.render().map_err(|e| AppError::TemplateError(e.to_string()))?))
+}
+
+/// Per-torrent metadata for clipboard actions, as JSON
+pub async fn torrent_info(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+) -> Response {
+    let torrent = state.latest_torrents().await
+        .and_then(|torrents| torrents.iter().find(|t| t.hash == hash).cloned());
+    let Some(torrent) = torrent else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Torrent not found", "hash": hash })),
+        ).into_response();
+    };
+    
+    let base_path = match state.rtorrent.get_base_path(&hash).await {
+        Ok(path) => path,
+        Err(e) => {
+            tracing::warn!("torrent_info: get_base_path failed for {}: {}", hash, e);
+            String::new()
+        }
+    };
+    
+    Json(serde_json::json!({
+        "hash": torrent.hash,
+        "name": torrent.name,
+        "magnet": torrent.magnet_link(),
+        "base_path": base_path,
+    })).into_response()
+}
+
 /// Pause a torrent
 pub async fn torrent_pause(
     State(state): State<Arc<AppState>>,
     Path(hash): Path<String>,
+    Query(query): Query<FilterQuery>,
 ) -> Result<impl IntoResponse> {
     state.rtorrent.pause_torrent(&hash).await?;
     
-    // Refresh cache and broadcast to SSE clients
-    state.refresh_cache().await;
+    // Refresh cache and push just this torrent to SSE clients
+    state.refresh_torrent(&hash).await;
     
-    // Return updated row from refreshed cache
+    render_action_row(&state, &hash, query.is_mobile(), "paused").await
+}
+
+/// The updated row of `hash` from the refreshed cache, announcing `action`.
+/// `compact` renders it as a card, for lists requested with `mobile=1`.
+async fn render_action_row(state: &AppState, hash: &str, compact: bool, action: &str) -> Result<impl IntoResponse> {
     let torrents = state.latest_torrents().await.ok_or_else(|| AppError::NotFound("Cache not ready".to_string()))?;
-    if let Some(torrent) = torrents.iter().find(|t| t.hash == hash) {
-        let is_starred = state.is_starred(&hash).await;
-        let view = TorrentView::from_torrent(torrent, is_starred);
-        let template = TorrentRowTemplate { torrent: view };
-        Ok(Html(template.render().map_err(|e| AppError::TemplateError(e.to_string()))?))
-    } else {
-        Err(AppError::NotFound("Torrent not found".to_string()))
-    }
+    let torrent = torrents.iter().find(|t| t.hash == hash)
+        .ok_or_else(|| AppError::NotFound("Torrent not found".to_string()))?;
+    
+    let view = TorrentView::from_torrent(torrent, state.is_starred(hash).await)
+        .with_queue_position(state.queue_position(hash).await);
+    let template = TorrentRowTemplate { torrent: view, read_only: state.config.read_only, compact };
+    let html = template.render().map_err(|e| AppError::TemplateError(e.to_string()))?;
+    Ok((action_trigger(action, &torrent.hash, torrent.display_name()), Html(html)))
+}
+
+/// Whether resuming `hash` has a stop tag to clear, going by the cache.
+/// A torrent the cache doesn't know yet is cleared to be safe.
+async fn has_stop_tag(state: &AppState, hash: &str) -> bool {
+    state
+        .latest_torrents()
+        .await
+        .and_then(|torrents| torrents.iter().find(|t| t.hash == hash).map(|t| t.has_stop_tag()))
+        .unwrap_or(true)
 }
 
 /// Resume a torrent
 pub async fn torrent_resume(
     State(state): State<Arc<AppState>>,
     Path(hash): Path<String>,
+    Query(query): Query<FilterQuery>,
 ) -> Result<impl IntoResponse> {
-    state.rtorrent.resume_torrent(&hash).await?;
+    let clear_stop_tag = has_stop_tag(&state, &hash).await;
+    state.rtorrent.resume_torrent(&hash, clear_stop_tag).await?;
     
-    // Refresh cache and broadcast to SSE clients
-    state.refresh_cache().await;
+    // Refresh cache and push just this torrent to SSE clients
+    state.refresh_torrent(&hash).await;
     
-    // Return updated row from refreshed cache
-    let torrents = state.latest_torrents().await.ok_or_else(|| AppError::NotFound("Cache not ready".to_string()))?;
-    if let Some(torrent) = torrents.iter().find(|t| t.hash == hash) {
-        let is_starred = state.is_starred(&hash).await;
-        let view = TorrentView::from_torrent(torrent, is_starred);
-        let template = TorrentRowTemplate { torrent: view };
-        Ok(Html(template.render().map_err(|e| AppError::TemplateError(e.to_string()))?))
-    } else {
-        Err(AppError::NotFound("Torrent not found".to_string()))
+    render_action_row(&state, &hash, query.is_mobile(), "resumed").await
+}
+
+/// Force-start a torrent, bypassing rTorrent's queue and global limits
+pub async fn torrent_force_start(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+    Query(query): Query<FilterQuery>,
+) -> Result<impl IntoResponse> {
+    let clear_stop_tag = has_stop_tag(&state, &hash).await;
+    state.rtorrent.force_start(&hash, clear_stop_tag).await?;
+    
+    // Refresh cache and push just this torrent to SSE clients
+    state.refresh_torrent(&hash).await;
+    
+    render_action_row(&state, &hash, query.is_mobile(), "force-started").await
+}
+
+/// Retry an errored torrent: reopen, restart and reannounce it
+pub async fn torrent_retry(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+    Query(query): Query<FilterQuery>,
+) -> Result<impl IntoResponse> {
+    state.rtorrent.retry_torrent(&hash).await?;
+    
+    // Refresh cache and push just this torrent to SSE clients
+    state.refresh_torrent(&hash).await;
+    
+    render_action_row(&state, &hash, query.is_mobile(), "retried").await
+}
+
+/// Stop seeding a finished torrent but keep it in the list
+pub async fn torrent_stop_seeding(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+    Query(query): Query<FilterQuery>,
+) -> Result<impl IntoResponse> {
+    state.rtorrent.stop_seeding(&hash).await?;
+    
+    // Refresh cache and push just this torrent to SSE clients
+    state.refresh_torrent(&hash).await;
+    
+    render_action_row(&state, &hash, query.is_mobile(), "stopped-seeding").await
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AutoStopForm {
+    /// Shown with the "Auto-stopped" status, e.g. "ratio 2.0 reached"
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// Stop a torrent on behalf of a script or rule, marked so it isn't mistaken
+/// for a manual pause. A request without a form body stops it with no reason.
+pub async fn torrent_auto_stop(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+    Query(query): Query<FilterQuery>,
+    form: std::result::Result<Form<AutoStopForm>, FormRejection>,
+) -> Result<impl IntoResponse> {
+    let reason = form.map(|Form(form)| form.reason).unwrap_or_default();
+    // The reason shares a custom field with the marker; keep it to one short line
+    let reason: String = reason.trim().replace(['\n', '\r'], " ").chars().take(200).collect();
+    if !automation::may_act_on(&hash, &*state.starred_torrents.read().await) {
+        return Err(AppError::BadRequest("Torrent is starred, automation leaves it alone".to_string()));
+    }
+    state.rtorrent.auto_stop(&hash, &reason).await?;
+    
+    // Refresh cache and push just this torrent to SSE clients
+    state.refresh_torrent(&hash).await;
+    
+    render_action_row(&state, &hash, query.is_mobile(), "auto-stopped").await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DirectoryForm {
+    pub directory: String,
+}
+
+/// Send a torrent's future data to another directory. Unlike a move, data
+/// already downloaded stays behind, so finished torrents are refused.
+pub async fn torrent_set_directory(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+    Form(form): Form<DirectoryForm>,
+) -> Result<impl IntoResponse> {
+    let directory = form.directory.trim();
+    if !std::path::Path::new(directory).is_absolute() {
+        return Err(AppError::BadRequest("Download directory must be an absolute path".to_string()));
+    }
+
+    let torrents = state.fresh_torrents().await;
+    let torrent = torrents
+        .iter()
+        .find(|t| t.hash == hash)
+        .ok_or_else(|| AppError::NotFound("Torrent not found".to_string()))?;
+    if torrent.complete {
+        return Err(AppError::BadRequest(
+            "Torrent is complete; changing its directory would lose track of its data".to_string(),
+        ));
     }
+
+    state.rtorrent.set_directory(&hash, directory).await?;
+    state.refresh_torrent(&hash).await;
+
+    let message = format!("New data for {} goes to {}", torrent.display_name(), directory);
+    Ok((toast_trigger(&message, "info"), StatusCode::OK))
+}
+
+/// Confirmation modal for removing a torrent, with its current name and size
+pub async fn torrent_confirm_remove(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+) -> Result<impl IntoResponse> {
+    // Refetched if stale, so the name and size shown are current
+    let torrents = state.fresh_torrents().await;
+    let torrent = torrents
+        .iter()
+        .find(|t| t.hash == hash)
+        .ok_or_else(|| AppError::NotFound("Torrent not found".to_string()))?;
+    
+    let template = ConfirmRemoveModalTemplate {
+        torrent: TorrentView::from_torrent(torrent, state.is_starred(&hash).await),
+    };
+    Ok(Html(template.render().map_err(|e| AppError::TemplateError(e.to_string()))?))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RemoveForm {
+    /// Also delete the downloaded files
+    #[serde(default)]
+    pub delete_data: bool,
 }
 
-/// Remove a torrent
+/// Remove a torrent, and its data if the form asks for it.
+///
+/// A request without a form body only removes the torrent.
 pub async fn torrent_remove(
     State(state): State<Arc<AppState>>,
     Path(hash): Path<String>,
+    form: std::result::Result<Form<RemoveForm>, FormRejection>,
 ) -> Result<impl IntoResponse> {
+    let delete_data = form.map(|Form(form)| form.delete_data).unwrap_or(false);
+    
+    // Look the torrent up before it disappears so the response can name it
+    let torrent = state.latest_torrents().await
+        .and_then(|torrents| torrents.iter().find(|t| t.hash == hash).cloned());
+    
+    // Located first: once erased, rTorrent can no longer say where the data is
+    let location = if delete_data {
+        Some(state.rtorrent.get_data_location(&hash).await?)
+    } else {
+        None
+    };
+    
+    // A magnet still fetching metadata is removed the same way, but log it distinctly
+    if torrent.as_ref().is_some_and(|t| t.is_awaiting_metadata()) {
+        tracing::info!("Cancelling metadata fetch for magnet {}", hash);
+    }
     state.rtorrent.remove_torrent(&hash).await?;
     // Refresh cache and broadcast to SSE clients
     state.refresh_cache().await;
-    Ok(StatusCode::OK)
+    
+    let name = torrent.map(|t| t.display_name().to_string()).unwrap_or_default();
+    let Some(location) = location else {
+        return Ok((action_trigger("removed", &hash, &name), StatusCode::OK));
+    };
+    
+    // The torrent is gone either way, so a failed or refused delete is reported, not an error
+    match torrents_service::delete_torrent_data(&location).await {
+        Ok(()) => {
+            tracing::info!("Deleted data of {} at {}", hash, location.base_path.display());
+            Ok((action_trigger("removed-with-data", &hash, &name), StatusCode::OK))
+        }
+        Err(e) => {
+            tracing::error!("Removed {} but deleting {} failed: {}", hash, location.base_path.display(), e);
+            Ok((
+                hx_trigger(serde_json::json!({
+                    "torrentAction": { "action": "removed", "hash": hash, "name": name },
+                    "showToast": { "message": format!("Data not deleted: {}", e), "level": "error" },
+                })),
+                StatusCode::OK,
+            ))
+        }
+    }
+}
+
+/// One of rTorrent's own state changes, offered one by one in the detail view
+#[derive(Debug, Clone, Copy)]
+enum StateStep {
+    Open,
+    Start,
+    Stop,
+    Close,
+}
+
+/// Open a torrent's files without starting it
+pub async fn torrent_open(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+) -> Result<impl IntoResponse> {
+    state_step(&state, &hash, StateStep::Open).await
+}
+
+/// Start an open torrent
+pub async fn torrent_start(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+) -> Result<impl IntoResponse> {
+    state_step(&state, &hash, StateStep::Start).await
+}
+
+/// Stop a torrent, leaving its files open
+pub async fn torrent_stop(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+) -> Result<impl IntoResponse> {
+    state_step(&state, &hash, StateStep::Stop).await
+}
+
+/// Close a stopped torrent's files
+pub async fn torrent_close(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+) -> Result<impl IntoResponse> {
+    state_step(&state, &hash, StateStep::Close).await
+}
+
+/// Unlike pause/resume these send the single rTorrent command and nothing
+/// else: no scheduler or "done seeding" bookkeeping.
+async fn state_step(state: &Arc<AppState>, hash: &str, step: StateStep) -> Result<impl IntoResponse> {
+    let action = match step {
+        StateStep::Open => {
+            state.rtorrent.open_torrent(hash).await?;
+            "opened"
+        }
+        StateStep::Start => {
+            state.rtorrent.start_torrent(hash).await?;
+            "started"
+        }
+        StateStep::Stop => {
+            state.rtorrent.stop_torrent(hash).await?;
+            "stopped"
+        }
+        StateStep::Close => {
+            state.rtorrent.close_torrent(hash).await?;
+            "closed"
+        }
+    };
+    
+    // Refresh cache and push just this torrent to SSE clients
+    state.refresh_torrent(hash).await;
+    
+    let torrents = state.latest_torrents().await.ok_or_else(|| AppError::NotFound("Cache not ready".to_string()))?;
+    let torrent = torrents.iter().find(|t| t.hash == hash)
+        .ok_or_else(|| AppError::NotFound("Torrent not found".to_string()))?;
+    Ok((action_trigger(action, &torrent.hash, torrent.display_name()), StatusCode::OK))
 }
 
 /// Toggle star on torrent
 pub async fn torrent_toggle_star(
     State(state): State<Arc<AppState>>,
     Path(hash): Path<String>,
+    Query(query): Query<FilterQuery>,
 ) -> Result<impl IntoResponse> {
-    let is_starred = state.toggle_star(&hash).await;
-    
     // Use cached torrents - star toggle doesn't require rTorrent query
     let torrents = state.latest_torrents().await.ok_or_else(|| AppError::NotFound("Cache not ready".to_string()))?;
-    if let Some(torrent) = torrents.iter().find(|t| t.hash == hash) {
-        let view = TorrentView::from_torrent(torrent, is_starred);
-        let template = TorrentRowTemplate { torrent: view };
-        Ok(Html(template.render().map_err(|e| AppError::TemplateError(e.to_string()))?))
-    } else {
-        Err(AppError::NotFound("Torrent not found".to_string()))
-    }
+    let torrent = torrents.iter().find(|t| t.hash == hash)
+        .ok_or_else(|| AppError::NotFound("Torrent not found".to_string()))?;
+    
+    let position = state.queue_position(&hash).await;
+    
+    // Render under the star lock so the row always matches the stored state
+    let (is_starred, html) = state.toggle_star_with(&hash, |is_starred| {
+        let view = TorrentView::from_torrent(torrent, is_starred).with_queue_position(position);
+        let template = TorrentRowTemplate { torrent: view, read_only: state.config.read_only, compact: query.is_mobile() };
+        (is_starred, template.render())
+    }).await;
+    let html = html.map_err(|e| AppError::TemplateError(e.to_string()))?;
+    state.publish_torrent(torrent.clone());
+    
+    let action = if is_starred { "starred" } else { "unstarred" };
+    Ok((action_trigger(action, &torrent.hash, torrent.display_name()), Html(html)))
 }
 
-/// Show add torrent modal
-pub async fn add_torrent_modal() -> Result<impl IntoResponse> {
-    let template = AddTorrentModalTemplate;
-    Ok(Html(template.render().map_err(|e| AppError::TemplateError(e.to_string()))?))
+/// Hide a torrent from every list but the `hidden` filter
+pub async fn torrent_hide(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+) -> Result<impl IntoResponse> {
+    set_hidden(&state, &hash, true).await
 }
 
-/// Add torrent (URL or file upload)
-pub async fn add_torrent(
+/// Show a hidden torrent in the lists again
+pub async fn torrent_unhide(
     State(state): State<Arc<AppState>>,
-    mut multipart: Multipart,
+    Path(hash): Path<String>,
 ) -> Result<impl IntoResponse> {
-    tracing::info!("add_torrent called");
+    set_hidden(&state, &hash, false).await
+}
+
+async fn set_hidden(state: &Arc<AppState>, hash: &str, hidden: bool) -> Result<impl IntoResponse> {
+    // Hiding is VibeTorrent's own state, so the cached list is enough to check
+    let torrents = state.latest_torrents().await.ok_or_else(|| AppError::NotFound("Cache not ready".to_string()))?;
+    let torrent = torrents.iter().find(|t| t.hash == hash)
+        .ok_or_else(|| AppError::NotFound("Torrent not found".to_string()))?;
     
-    while let Some(field) = multipart.next_field().await.map_err(|e| AppError::BadRequest(e.to_string()))? {
-        let name = field.name().unwrap_or_default().to_string();
-        tracing::debug!("Processing field: {}", name);
-        
-        match name.as_str() {
-            "url" => {
-                let url = field.text().await.map_err(|e| AppError::BadRequest(e.to_string()))?;
-                tracing::info!("URL field value: '{}'", url);
-                if !url.trim().is_empty() {
-                    if let Err(e) = state.rtorrent.add_torrent_url(&url).await {
-                        tracing::error!("Failed to add torrent URL: {:?}", e);
-                        return Err(e);
-                    }
-                }
-            }
-            "file" => {
-                let data = field.bytes().await.map_err(|e| AppError::BadRequest(e.to_string()))?;
-                tracing::info!("File field size: {} bytes", data.len());
-                if !data.is_empty() {
-                    if let Err(e) = state.rtorrent.add_torrent_file(&data).await {
-                        tracing::error!("Failed to add torrent file: {:?}", e);
-                        return Err(e);
-                    }
-                }
-            }
-            _ => {
-                tracing::debug!("Unknown field: {}", name);
-            }
-        }
+    if state.set_hidden(hash, hidden, &torrents).await {
+        // Re-render every list stream with the torrent in or out
+        state.refresh_cache().await;
     }
     
-    // Refresh cache and broadcast to SSE clients after adding torrent
-    state.refresh_cache().await;
-    
-    // Return updated torrent list + sidebar counts with HX-Trigger to close modal
-    let torrents = state.latest_torrents().await
-        .map(|arc| (*arc).clone())
-        .unwrap_or_default();
-    let query = FilterQuery {
-        search: None,
-        sort: None,
-        order: None,
-    };
-    let html = torrents_service::render_torrents_html(&state, &query, None, &torrents).await?;
+    let action = if hidden { "hidden" } else { "unhidden" };
+    Ok((action_trigger(action, &torrent.hash, torrent.display_name()), StatusCode::OK))
+}
 
-    Ok(([("HX-Trigger", "closeModal")], Html(html)))
+/// Move a torrent one place up in the queue
+pub async fn torrent_move_up(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+) -> Result<impl IntoResponse> {
+    move_in_queue(&state, &hash, QueueMove::Up).await
 }
 
-/// Get stats partial (for HTMX polling)
-pub async fn stats_partial(
+/// Move a torrent one place down in the queue
+pub async fn torrent_move_down(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+) -> Result<impl IntoResponse> {
+    move_in_queue(&state, &hash, QueueMove::Down).await
+}
+
+/// Move a torrent to the front of the queue
+pub async fn torrent_queue_top(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+) -> Result<impl IntoResponse> {
+    move_in_queue(&state, &hash, QueueMove::Top).await
+}
+
+/// Move a torrent to the end of the queue
+pub async fn torrent_queue_bottom(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+) -> Result<impl IntoResponse> {
+    move_in_queue(&state, &hash, QueueMove::Bottom).await
+}
+
+/// Reorder, then rebroadcast the list so every client re-renders positions,
+/// and answer with the list sorted by queue position. Moving past either end
+/// is a no-op rather than an error.
+async fn move_in_queue(state: &Arc<AppState>, hash: &str, to: QueueMove) -> Result<Response> {
+    let torrents = state.latest_torrents().await.ok_or_else(|| AppError::NotFound("Cache not ready".to_string()))?;
+    let torrent = torrents.iter().find(|t| t.hash == hash)
+        .ok_or_else(|| AppError::NotFound("Torrent not found".to_string()))?;
+    
+    let moved = state.move_in_queue(hash, to, &torrents).await;
+    if moved {
+        state.rebroadcast().await;
+    }
+    let query = FilterQuery { sort: Some("queue".to_string()), ..FilterQuery::default() };
+    let html = Html(torrents_service::render_torrents_html(state, &query, None, &torrents).await?);
+    if !moved {
+        return Ok(html.into_response());
+    }
+    
+    let action = match to {
+        QueueMove::Up => "moved-up",
+        QueueMove::Down => "moved-down",
+        QueueMove::Top => "moved-top",
+        QueueMove::Bottom => "moved-bottom",
+    };
+    Ok((action_trigger(action, &torrent.hash, torrent.display_name()), html).into_response())
+}
+
+/// Build an `HX-Trigger` header announcing a finished torrent action.
+///
+/// The `torrentAction` event carries the torrent name so the frontend can say
+/// "Paused Ubuntu 24.04" instead of showing a bare hash.
+fn action_trigger(action: &str, hash: &str, name: &str) -> [(&'static str, String); 1] {
+    hx_trigger(serde_json::json!({
+        "torrentAction": { "action": action, "hash": hash, "name": name }
+    }))
+}
+
+/// Build an `HX-Trigger` header that pops a toast on the client
+fn toast_trigger(message: &str, level: &str) -> [(&'static str, String); 1] {
+    hx_trigger(serde_json::json!({
+        "showToast": { "message": message, "level": level }
+    }))
+}
+
+/// Serialize an `HX-Trigger` event map.
+///
+/// Non-ASCII characters are `\u`-escaped because browsers decode header values
+/// as Latin-1, and so are control characters such as DEL, which JSON allows
+/// in strings but a header value doesn't.
+fn hx_trigger(events: serde_json::Value) -> [(&'static str, String); 1] {
+    let payload = events.to_string();
+    
+    let mut escaped = String::with_capacity(payload.len());
+    for c in payload.chars() {
+        if c.is_ascii() && !c.is_ascii_control() {
+            escaped.push(c);
+        } else {
+            let mut units = [0u16; 2];
+            for unit in c.encode_utf16(&mut units) {
+                escaped.push_str(&format!("\\u{:04x}", unit));
+            }
+        }
+    }
+    
+    [("HX-Trigger", escaped)]
+}
+
+/// Show add torrent modal
+pub async fn add_torrent_modal() -> Result<impl IntoResponse> {
+    let template = AddTorrentModalTemplate;
+    Ok(Html(template.render().map_err(|e| AppError::TemplateError(e.to_string()))?))
+}
+
+/// Add torrent (URL or file upload)
+///
+/// Responds 200 with the refreshed list when at least one input was accepted,
+/// 400 when nothing valid was provided. Either way the modal gets a per-input
+/// report unless everything went through.
+///
+/// An optional `label` is applied to every added torrent whose infohash is
+/// known up front: magnets and uploaded files, not `.torrent` URLs.
+///
+/// With `existing_data` set, uploaded files are loaded stopped, hash-checked
+/// against the data already in their download directory, then started.
+/// URLs are added as usual: their metadata isn't known until rTorrent fetches it.
+///
+/// `max_mb` is the body limit the router applies, named if an upload goes over it.
+pub async fn add_torrent(
+    State(state): State<Arc<AppState>>,
+    max_mb: u64,
+    mut multipart: Multipart,
+) -> Result<Response> {
+    tracing::info!("add_torrent called");
+    
+    // Inputs are collected first so options apply whatever order the form sends them in
+    let mut urls: Vec<String> = Vec::new();
+    let mut files: Vec<(String, axum::body::Bytes)> = Vec::new();
+    let mut label = String::new();
+    let mut existing_data = false;
+    
+    let read_form = async {
+        while let Some(field) = multipart.next_field().await.map_err(|e| upload_error(e, max_mb))? {
+            let name = field.name().unwrap_or_default().to_string();
+            tracing::debug!("Processing field: {}", name);
+        
+            match name.as_str() {
+                "label" => {
+                    let text = field.text().await.map_err(|e| upload_error(e, max_mb))?;
+                    label = text.trim().to_string();
+                }
+                "existing_data" => {
+                    let text = field.text().await.map_err(|e| upload_error(e, max_mb))?;
+                    existing_data = matches!(text.trim(), "true" | "on" | "1");
+                }
+                "url" => {
+                    let url = field.text().await.map_err(|e| upload_error(e, max_mb))?;
+                    tracing::info!("URL field value: '{}'", url.trim());
+                    urls.push(url.trim().to_string());
+                }
+                // A pasted list, one URL per line
+                "urls" => {
+                    let text = field.text().await.map_err(|e| upload_error(e, max_mb))?;
+                    let before = urls.len();
+                    urls.extend(text.lines().map(str::trim).filter(|line| !line.is_empty()).map(str::to_string));
+                    tracing::info!("URLs field: {} URL(s)", urls.len() - before);
+                }
+                "file" => {
+                    let file_name = field.file_name().unwrap_or("upload").to_string();
+                    let data = field.bytes().await.map_err(|e| upload_error(e, max_mb))?;
+                    tracing::info!("File field {} size: {} bytes", file_name, data.len());
+                    files.push((file_name, data));
+                }
+                _ => {
+                    tracing::debug!("Unknown field: {}", name);
+                }
+            }
+        }
+        Ok::<_, AppError>(())
+    };
+    // A stalled client would otherwise hold the handler open indefinitely
+    let timeout = std::time::Duration::from_secs(state.config.upload_timeout_secs);
+    tokio::time::timeout(timeout, read_form).await.map_err(|_| {
+        AppError::BadRequest(format!("Upload timed out after {} seconds", timeout.as_secs()))
+    })??;
+    
+    // Every URL and file part is attempted; failures are recorded, not fatal.
+    // Blank URLs and empty file parts mean "not provided" and are skipped.
+    let mut outcomes: Vec<AddOutcome> = Vec::new();
+    let mut added_hashes: Vec<String> = Vec::new();
+    let mut to_verify: Vec<String> = Vec::new();
+    if existing_data && files.iter().any(|(_, data)| !data.is_empty()) {
+        require_method(&state, "load.raw").await?;
+    }
+    
+    for url in urls.iter().filter(|url| !url.is_empty()) {
+        if existing_data {
+            tracing::warn!("add_torrent: existing_data ignored for URL {}", shorten_url(url));
+        }
+        let result = state.rtorrent.add_torrent_url(url).await;
+        match &result {
+            Ok(()) => added_hashes.extend(rtorrent::magnet_info_hash(url)),
+            Err(e) => tracing::error!("Failed to add torrent URL: {:?}", e),
+        }
+        outcomes.push(outcome(shorten_url(url), result.map_err(|e| e.to_string())));
+    }
+    
+    for (file_name, data) in files.into_iter().filter(|(_, data)| !data.is_empty()) {
+        // Catch stray uploads here rather than via an opaque rTorrent fault
+        if !crate::bencode::is_torrent(&data) {
+            outcomes.push(outcome(file_name, Err("Not a valid .torrent file".to_string())));
+            continue;
+        }
+        let result = state.rtorrent.add_torrent_file(&data, !existing_data).await;
+        match &result {
+            Ok(()) => {
+                let hash = crate::bencode::info_hash(&data);
+                if existing_data {
+                    to_verify.extend(hash.clone());
+                }
+                added_hashes.extend(hash);
+            }
+            Err(e) => tracing::error!("Failed to add torrent file {}: {:?}", file_name, e),
+        }
+        outcomes.push(outcome(file_name, result.map_err(|e| e.to_string())));
+    }
+    
+    let added = outcomes.iter().filter(|o| o.accepted).count();
+    let failed = outcomes.len() - added;
+    
+    // Nothing made it: keep the modal open and show what was rejected
+    if added == 0 {
+        let message = match outcomes.as_slice() {
+            [] => "Nothing to add: enter a URL or choose a .torrent file".to_string(),
+            [only] => only.reason.clone(),
+            _ => format!("None of the {} torrents could be added", outcomes.len()),
+        };
+        tracing::warn!("add_torrent: nothing added: {}", message);
+        let html = if outcomes.is_empty() {
+            message
+        } else {
+            AddTorrentResultTemplate { outcomes, oob: false }
+                .render()
+                .map_err(|e| AppError::TemplateError(e.to_string()))?
+        };
+        return Ok((StatusCode::BAD_REQUEST, Html(html)).into_response());
+    }
+    
+    if !label.is_empty() {
+        if added_hashes.len() < added {
+            tracing::warn!(
+                "add_torrent: label {:?} not applied to {} torrent(s) added by URL",
+                label,
+                added - added_hashes.len()
+            );
+        }
+        apply_label(&state, &added_hashes, &label).await;
+    }
+    
+    check_and_start(&state, &to_verify).await;
+    
+    // Refresh cache and broadcast to SSE clients after adding torrent
+    state.refresh_cache().await;
+    
+    // Return updated torrent list + sidebar counts
+    let torrents = state.latest_torrents().await
+        .map(|arc| (*arc).clone())
+        .unwrap_or_default();
+    let query = FilterQuery {
+        search: None,
+        sort: None,
+        order: None,
+        limit: None,
+        label: None,
+        progress_min: None,
+        progress_max: None,
+        mobile: None,
+    };
+    let mut html = torrents_service::render_torrents_html(&state, &query, None, &torrents).await?;
+    
+    let trigger = if failed == 0 {
+        hx_trigger(serde_json::json!({
+            "closeModal": null,
+            "showToast": { "message": format!("Added {} torrent(s)", added), "level": "info" }
+        }))
+    } else {
+        // Partial success: leave the modal open with the per-input report
+        tracing::warn!("add_torrent: {} added, {} failed", added, failed);
+        let report = AddTorrentResultTemplate { outcomes, oob: true }
+            .render()
+            .map_err(|e| AppError::TemplateError(e.to_string()))?;
+        html.push_str(&report);
+        hx_trigger(serde_json::json!({
+            "showToast": {
+                "message": format!("Added {} torrent(s), {} failed", added, failed),
+                "level": "error"
+            }
+        }))
+    };
+
+    Ok((trigger, Html(html)).into_response())
+}
+
+/// Label every added torrent, waiting for each to load at the same time
+async fn apply_label(state: &AppState, hashes: &[String], label: &str) {
+    futures::future::join_all(hashes.iter().map(|hash| async move {
+        if let Err(e) = once_loaded(|| state.rtorrent.set_label(hash, label)).await {
+            tracing::warn!("Failed to label {} as {:?}: {}", hash, label, e);
+        }
+    }))
+    .await;
+}
+
+/// Hash-check and start every torrent added over existing data, waiting for
+/// each to load at the same time
+async fn check_and_start(state: &AppState, hashes: &[String]) {
+    futures::future::join_all(hashes.iter().map(|hash| async move {
+        if let Err(e) = once_loaded(|| state.rtorrent.check_and_start(hash)).await {
+            tracing::warn!("Failed to check and start {}: {}", hash, e);
+        }
+    }))
+    .await;
+}
+
+/// rTorrent loads torrents asynchronously, so a hash can be unknown for a
+/// moment right after `load.*` returns. Retry `call` briefly before giving up.
+async fn once_loaded<F, Fut>(mut call: F) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<()>>,
+{
+    const ATTEMPTS: u32 = 10;
+    const RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(200);
+    
+    let mut attempt = 1;
+    loop {
+        match call().await {
+            Err(_) if attempt < ATTEMPTS => {
+                attempt += 1;
+                tokio::time::sleep(RETRY_DELAY).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn outcome(input: String, result: std::result::Result<(), String>) -> AddOutcome {
+    match result {
+        Ok(()) => AddOutcome { input, accepted: true, reason: String::new() },
+        Err(reason) => AddOutcome { input, accepted: false, reason },
+    }
+}
+
+/// An upload that couldn't be read, naming the size limit when it was hit
+fn upload_error(e: axum::extract::multipart::MultipartError, max_mb: u64) -> AppError {
+    if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        AppError::BadRequest(format!("File too large: uploads are limited to {} MB (max_upload_mb)", max_mb))
+    } else {
+        AppError::BadRequest(e.to_string())
+    }
+}
+
+/// Magnet links are mostly tracker lists; keep the report readable
+fn shorten_url(url: &str) -> String {
+    const MAX_CHARS: usize = 60;
+    if url.chars().count() <= MAX_CHARS {
+        return url.to_string();
+    }
+    let head: String = url.chars().take(MAX_CHARS - 1).collect();
+    format!("{}…", head)
+}
+
+/// Tiny status fragment for embedding in other dashboards (iframe or HTMX include).
+///
+/// Reads only the poller's caches, so it's safe to poll frequently.
+pub async fn widget(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse> {
+    let template = WidgetTemplate {
+        total_count: state.latest_torrents().await.map(|t| t.len()).unwrap_or(0),
+        stats: state.latest_stats().await.map(|arc| (*arc).clone()),
+        connected: state.is_connected(),
+    };
+    Ok(Html(template.render().map_err(|e| AppError::TemplateError(e.to_string()))?))
+}
+
+/// Build and connection info for deployments and monitoring.
+///
+/// Cheap by design: the rTorrent version comes from the poller's cache. Also
+/// answers before setup is complete, with no rTorrent version.
+pub async fn api_version(state: Option<Arc<AppState>>) -> impl IntoResponse {
+    let rtorrent_version = match state {
+        Some(state) => state.rtorrent_version().await,
+        None => None,
+    };
+    Json(serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_sha": option_env!("VIBETORRENT_GIT_SHA"),
+        "rtorrent_version": rtorrent_version,
+    }))
+}
+
+/// Everything about one torrent in a single response: its fields plus
+/// `files`, `trackers` and `peers`, fetched concurrently. `files` and `peers`
+/// are `null` when rTorrent lacks the method that lists them.
+pub async fn api_torrent(
+    State(state): State<Arc<AppState>>,
+    Path(hash): Path<String>,
+) -> Response {
+    let torrents = state.fresh_torrents().await;
+    let Some(torrent) = torrents.iter().find(|t| t.hash == hash) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "Torrent not found", "hash": hash })),
+        ).into_response();
+    };
+    
+    let (has_files, has_peers) = (state.supports("f.multicall").await, state.supports("p.multicall").await);
+    let (files, trackers, peers) = tokio::join!(
+        async { if has_files { state.rtorrent.get_files(&hash).await.map(Some) } else { Ok(None) } },
+        state.rtorrent.get_trackers(&hash),
+        async { if has_peers { state.rtorrent.get_peers(&hash).await.map(Some) } else { Ok(None) } },
+    );
+    let (files, trackers, peers) = match (files, trackers, peers) {
+        (Ok(files), Ok(trackers), Ok(peers)) => (files, trackers, peers),
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+            tracing::warn!("api_torrent: fetching details failed for {}: {}", hash, e);
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(serde_json::json!({ "error": e.to_string(), "hash": hash })),
+            ).into_response();
+        }
+    };
+    
+    let mut body = serde_json::json!(torrent);
+    body["is_starred"] = serde_json::json!(state.is_starred(&hash).await);
+    body["is_hidden"] = serde_json::json!(state.is_hidden(&hash).await);
+    body["files"] = serde_json::json!(files);
+    body["trackers"] = serde_json::json!(trackers);
+    body["peers"] = serde_json::json!(peers);
+    Json(body).into_response()
+}
+
+/// Every method rTorrent exposes (`system.listMethods`), for writing automation
+/// against it. Fetched once per config, then served from memory.
+pub async fn api_methods(State(state): State<Arc<AppState>>) -> Response {
+    match state.method_list().await {
+        Ok(methods) => Json(serde_json::json!(*methods)).into_response(),
+        Err(e) => {
+            tracing::warn!("api_methods: system.listMethods failed: {}", e);
+            (StatusCode::BAD_GATEWAY, Json(serde_json::json!({ "error": e.to_string() }))).into_response()
+        }
+    }
+}
+
+/// rTorrent's help text and signatures for one method
+pub async fn api_method(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Response {
+    // Unknown names fault in rTorrent too, but this tells them from a broken connection
+    if let Ok(methods) = state.method_list().await {
+        if !methods.contains(&name) {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({ "error": "Method not found", "name": name })),
+            ).into_response();
+        }
+    }
+    match state.rtorrent.method_help(&name).await {
+        Ok(help) => Json(help).into_response(),
+        Err(e) => {
+            tracing::warn!("api_method: describing {} failed: {}", name, e);
+            (
+                StatusCode::BAD_GATEWAY,
+                Json(serde_json::json!({ "error": e.to_string(), "name": name })),
+            ).into_response()
+        }
+    }
+}
+
+/// Body of `POST /api/add`
+#[derive(Debug, Deserialize)]
+pub struct ApiAddRequest {
+    pub url: Option<String>,
+    pub label: Option<String>,
+    pub directory: Option<String>,
+    #[serde(default)]
+    pub start_paused: bool,
+}
+
+/// Add one torrent by URL from a script, with its label, directory and
+/// paused start set as rTorrent loads it.
+///
+/// Responds with the infohash (`null` for `.torrent` URLs, whose hash rTorrent
+/// only learns once it has fetched them) and whether it was started or left
+/// paused. Bad input is a 400, a refusal or unreachable rTorrent a 502, both
+/// with an `error` field.
+pub async fn api_add(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ApiAddRequest>,
+) -> Response {
+    let bad_request = |error: &str| {
+        (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": error }))).into_response()
+    };
+    let non_empty = |value: Option<String>| value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    
+    let Some(url) = non_empty(request.url) else {
+        return bad_request("Nothing to add: url is required");
+    };
+    let options = rtorrent::AddOptions {
+        label: non_empty(request.label),
+        directory: non_empty(request.directory),
+        start_paused: request.start_paused,
+    };
+    if options.label.as_deref().is_some_and(rtorrent::AddOptions::is_unquotable) {
+        return bad_request("label must not contain quotes, backslashes or control characters");
+    }
+    if let Some(directory) = options.directory.as_deref() {
+        if !std::path::Path::new(directory).is_absolute() {
+            return bad_request("directory must be an absolute path");
+        }
+        if rtorrent::AddOptions::is_unquotable(directory) {
+            return bad_request("directory must not contain quotes, backslashes or control characters");
+        }
+    }
+    
+    if let Err(e) = state.rtorrent.add_torrent_url_with(&url, &options).await {
+        tracing::error!("api_add: adding {} failed: {}", shorten_url(&url), e);
+        return (
+            StatusCode::BAD_GATEWAY,
+            Json(serde_json::json!({ "error": e.to_string(), "url": url })),
+        ).into_response();
+    }
+    state.refresh_cache().await;
+    
+    Json(serde_json::json!({
+        "hash": rtorrent::magnet_info_hash(&url),
+        "status": if options.start_paused { "paused" } else { "started" },
+    })).into_response()
+}
+
+/// Get stats partial (for HTMX polling)
+pub async fn stats_partial(
     State(state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse> {
     // Use cached stats instead of querying rTorrent directly
     let stats = state.latest_stats().await
         .map(|arc| (*arc).clone())
-        .unwrap_or_else(|| GlobalStats {
-            down_rate: 0,
-            up_rate: 0,
-            free_disk_space: 2_000_000_000_000,
-            active_peers: 0,
-        });
+        .unwrap_or_else(|| GlobalStats::empty(state.config.rate_unit));
     
     let template = StatsTemplate { stats };
     Ok(Html(template.render().map_err(|e| AppError::TemplateError(e.to_string()))?))
 }
+
+/// Settings modal
+pub async fn settings_modal(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse> {
+    let limits = state.rtorrent.get_transfer_limits().await?;
+    let discovery = if state.supports("dht.statistics").await {
+        state.rtorrent.get_peer_discovery().await
+            .inspect_err(|e| tracing::warn!("settings: reading DHT/PEX state failed: {}", e))
+            .ok()
+    } else {
+        None
+    };
+    let network = state.rtorrent.get_network_info().await
+        .inspect_err(|e| tracing::warn!("settings: reading listening port failed: {}", e))
+        .ok();
+    let session = state.rtorrent.get_session_info().await
+        .inspect_err(|e| tracing::warn!("settings: reading session settings failed: {}", e))
+        .ok();
+    let rtorrent_version = state.rtorrent_version().await.unwrap_or_else(|| "Disconnected".to_string());
+    
+    let template = SettingsModalTemplate {
+        limits,
+        discovery,
+        network,
+        session,
+        rtorrent_version,
+        read_only: state.config.read_only,
+    };
+    Ok(Html(template.render().map_err(|e| AppError::TemplateError(e.to_string()))?))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LimitsForm {
+    pub max_downloads: i64,
+    pub max_uploads: i64,
+}
+
+/// Set rTorrent's global download/upload slot limits
+pub async fn settings_limits(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<LimitsForm>,
+) -> Result<impl IntoResponse> {
+    if form.max_downloads < 0 || form.max_uploads < 0 {
+        return Err(AppError::BadRequest("Limits can't be negative".to_string()));
+    }
+    
+    state.rtorrent.set_transfer_limits(TransferLimits {
+        max_downloads: form.max_downloads,
+        max_uploads: form.max_uploads,
+    }).await?;
+    
+    Ok((toast_trigger("Transfer limits updated", "info"), StatusCode::OK))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ToggleForm {
+    pub enabled: bool,
+}
+
+/// Turn DHT on or off and return the re-rendered DHT/PEX section
+pub async fn settings_dht(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<ToggleForm>,
+) -> Result<impl IntoResponse> {
+    require_method(&state, "dht.mode.set").await?;
+    state.rtorrent.set_dht(form.enabled).await?;
+    let message = if form.enabled { "DHT turned on" } else { "DHT disabled" };
+    peer_discovery_response(&state, message).await
+}
+
+/// Turn peer exchange on or off and return the re-rendered DHT/PEX section
+pub async fn settings_pex(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<ToggleForm>,
+) -> Result<impl IntoResponse> {
+    require_method(&state, "protocol.pex.set").await?;
+    state.rtorrent.set_pex(form.enabled).await?;
+    let message = if form.enabled { "Peer exchange turned on" } else { "Peer exchange turned off" };
+    peer_discovery_response(&state, message).await
+}
+
+/// Turn the request away when rTorrent lacks `method`, one of
+/// [`rtorrent::OPTIONAL_METHODS`]
+async fn require_method(state: &AppState, method: &str) -> Result<()> {
+    if state.supports(method).await {
+        return Ok(());
+    }
+    Err(AppError::BadRequest(format!(
+        "This rTorrent has no {}: {} is missing",
+        rtorrent::optional_feature(method),
+        method
+    )))
+}
+
+/// Reads the state back rather than assuming the write took effect
+async fn peer_discovery_response(state: &AppState, message: &str) -> Result<impl IntoResponse> {
+    let template = PeerDiscoveryTemplate {
+        discovery: Some(state.rtorrent.get_peer_discovery().await?),
+        read_only: state.config.read_only,
+    };
+    let html = template.render().map_err(|e| AppError::TemplateError(e.to_string()))?;
+    Ok((toast_trigger(message, "info"), Html(html)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SessionForm {
+    /// Minutes between saves; blank leaves rTorrent's schedule alone
+    #[serde(default)]
+    pub save_interval_minutes: String,
+    /// Checkbox, absent when unticked
+    #[serde(default)]
+    pub save_on_completion: bool,
+}
+
+/// Set how often and when rTorrent saves its session
+pub async fn settings_session(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<SessionForm>,
+) -> Result<impl IntoResponse> {
+    let interval = form.save_interval_minutes.trim();
+    let minutes = if interval.is_empty() {
+        None
+    } else {
+        match interval.parse::<u64>() {
+            Ok(minutes) if minutes > 0 => Some(minutes),
+            _ => return Err(AppError::BadRequest("Save interval must be a whole number of minutes".to_string())),
+        }
+    };
+    
+    if let Some(minutes) = minutes {
+        state.rtorrent.set_session_save_interval(minutes * 60).await?;
+    }
+    state.rtorrent.set_session_on_completion(form.save_on_completion).await?;
+    
+    Ok((toast_trigger("Session settings updated", "info"), StatusCode::OK))
+}
+
+/// Have rTorrent write its session now, e.g. before restarting it
+pub async fn maintenance_save_session(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse> {
+    let session = state.rtorrent.get_session_info().await?;
+    if !session.is_enabled() {
+        return Err(AppError::BadRequest(
+            "rTorrent has no session directory (session.path), so there is nothing to save".to_string(),
+        ));
+    }
+    
+    state.rtorrent.save_session().await?;
+    let message = format!("Session saved to {}", session.path);
+    Ok((toast_trigger(&message, "info"), StatusCode::OK))
+}
+
+/// Torrents whose trackers are replaced at the same time
+const TRACKER_REPLACE_CONCURRENCY: usize = 8;
+
+#[derive(Debug, Deserialize)]
+pub struct TrackerReplaceForm {
+    pub from_host: String,
+    pub to_url: String,
+}
+
+/// Replace the tracker on every torrent announcing to `from_host`
+pub async fn trackers_replace(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<TrackerReplaceForm>,
+) -> Result<impl IntoResponse> {
+    let from_host = form.from_host.trim().to_lowercase();
+    let to_url = form.to_url.trim();
+    if from_host.is_empty() {
+        return Err(AppError::BadRequest("Tracker host is required".to_string()));
+    }
+    if !rtorrent::is_valid_tracker_url(to_url) {
+        return Err(AppError::BadRequest("Tracker URL must be http, https or udp".to_string()));
+    }
+    
+    let torrents = state.latest_torrents().await.unwrap_or_default();
+    
+    // One tracker fetch per torrent, a few torrents at a time so a big library
+    // doesn't flood the SCGI socket
+    let hashes: Vec<String> = torrents.iter().map(|t| t.hash.clone()).collect();
+    let results: Vec<Result<bool>> = stream::iter(hashes)
+        .map(|hash| {
+            let state = state.clone();
+            let from_host = from_host.clone();
+            let to_url = to_url.to_string();
+            async move {
+                let trackers = state.rtorrent.get_trackers(&hash).await.map_err(|e| {
+                    tracing::warn!("trackers_replace: get_trackers failed for {}: {}", hash, e);
+                    e
+                })?;
+                state
+                    .rtorrent
+                    .replace_trackers(&hash, &trackers, |t| t.host().as_deref() == Some(from_host.as_str()), &to_url)
+                    .await
+                    .map_err(|e| {
+                        tracing::warn!("trackers_replace: replace failed for {}: {}", hash, e);
+                        e
+                    })
+            }
+        })
+        .buffer_unordered(TRACKER_REPLACE_CONCURRENCY)
+        .collect()
+        .await;
+    
+    let updated = results.iter().filter(|r| matches!(r, Ok(true))).count();
+    let failed = results.iter().filter(|r| r.is_err()).count();
+    
+    let message = if failed > 0 {
+        format!("Updated trackers on {} torrent(s), {} failed", updated, failed)
+    } else {
+        format!("Updated trackers on {} torrent(s)", updated)
+    };
+    let level = if failed > 0 { "error" } else { "info" };
+    
+    Ok((toast_trigger(&message, level), Html(message)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRtorrent};
+    use axum::body::Body;
+    use axum::http::Request;
+    use axum::routing::post;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    /// A multipart body with one part per `(name, file name, content)`
+    fn multipart_body(boundary: &str, parts: &[(&str, Option<&str>, &[u8])]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (name, file_name, content) in parts {
+            body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+            match file_name {
+                Some(file_name) => body.extend_from_slice(
+                    format!(
+                        "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
+                         Content-Type: application/x-bittorrent\r\n\r\n",
+                        name, file_name
+                    )
+                    .as_bytes(),
+                ),
+                None => body.extend_from_slice(
+                    format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name).as_bytes(),
+                ),
+            }
+            body.extend_from_slice(content);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+        body
+    }
+
+    #[tokio::test]
+    async fn add_torrent_adds_every_file_part() {
+        let mock = MockRtorrent::start(|xml| match rtorrent::xml_method_name(xml) {
+            "d.multicall2" => testing::torrents_response(&[]),
+            _ => testing::response("<i4>0</i4>"),
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let app = Router::new()
+            .route("/add-torrent", post(|state, multipart| add_torrent(state, 10, multipart)))
+            .with_state(testing::app_state(&mock, dir.path()));
+
+        let body = multipart_body(
+            "XYZ",
+            &[
+                ("file", Some("one.torrent"), b"d4:infod4:name3:oneee"),
+                ("file", Some("two.torrent"), b"d4:infod4:name3:twoee"),
+            ],
+        );
+        let response = app
+            .oneshot(
+                Request::post("/add-torrent")
+                    .header("content-type", "multipart/form-data; boundary=XYZ")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let trigger = response.headers()["HX-Trigger"].to_str().unwrap();
+        assert!(trigger.contains("Added 2 torrent(s)"), "{}", trigger);
+        let loads: Vec<String> = mock
+            .requests()
+            .into_iter()
+            .filter(|xml| rtorrent::xml_method_name(xml) == "load.raw_start")
+            .collect();
+        assert_eq!(loads.len(), 2);
+        assert_ne!(loads[0], loads[1]);
+    }
+
+    /// Wait out the poller's first tick, which runs straight away
+    async fn wait_for_poll(state: &AppState) {
+        tokio::time::timeout(std::time::Duration::from_secs(1), async {
+            while state.latest_stats().await.is_none() {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    async fn body_text(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn widget_shows_the_poll_without_any_sse_clients() {
+        let mock = MockRtorrent::start(|xml| match rtorrent::xml_method_name(xml) {
+            "d.multicall2" => testing::torrents_response(&[
+                testing::torrent_row("AAAA", "one"),
+                testing::torrent_row("BBBB", "two"),
+            ]),
+            _ => testing::response("<i8>0</i8>"),
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let state = testing::app_state(&mock, dir.path());
+        wait_for_poll(&state).await;
+
+        let html = body_text(widget(State(state)).await.into_response()).await;
+
+        assert!(html.contains("<b>2</b> torrents"), "{}", html);
+        assert!(!html.contains("No stats yet"), "{}", html);
+    }
+
+    #[tokio::test]
+    async fn resume_clears_the_stop_tag_only_when_there_is_one() {
+        let mut stopped = testing::torrent_row("DONE", "done");
+        stopped[16] = "<string>done-seeding</string>".to_string();
+        let rows = vec![testing::torrent_row("PLAIN", "plain"), stopped];
+        let mock = MockRtorrent::start(move |xml| match rtorrent::xml_method_name(xml) {
+            "d.multicall2" => testing::torrents_response(&rows),
+            _ => testing::response("<i8>0</i8>"),
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let state = testing::app_state(&mock, dir.path());
+        wait_for_poll(&state).await;
+        let tag_clears = |hash: &str| {
+            mock.requests()
+                .iter()
+                .filter(|xml| rtorrent::xml_method_name(xml) == "d.custom.set" && xml.contains(hash))
+                .count()
+        };
+
+        torrent_resume(State(state.clone()), Path("PLAIN".to_string()), Query(FilterQuery::default())).await.unwrap();
+        torrent_resume(State(state), Path("DONE".to_string()), Query(FilterQuery::default())).await.unwrap();
+
+        assert_eq!(tag_clears("PLAIN"), 0);
+        assert_eq!(tag_clears("DONE"), 1);
+    }
+
+    #[tokio::test]
+    async fn row_actions_from_compact_cards_answer_with_compact_rows() {
+        let rows = vec![testing::torrent_row("AAAA", "one")];
+        let mock = MockRtorrent::start(move |xml| match rtorrent::xml_method_name(xml) {
+            "d.multicall2" => testing::torrents_response(&rows),
+            _ => testing::response("<i8>0</i8>"),
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let state = testing::app_state(&mock, dir.path());
+        wait_for_poll(&state).await;
+        let mobile = FilterQuery { mobile: Some("1".to_string()), ..FilterQuery::default() };
+        let desktop_grid = "hidden lg:grid";
+
+        let compact = torrent_pause(State(state.clone()), Path("AAAA".to_string()), Query(mobile.clone()))
+            .await
+            .unwrap();
+        let compact = body_text(compact.into_response()).await;
+        assert!(!compact.contains(desktop_grid));
+        // Its buttons keep asking for compact rows
+        assert!(compact.contains("/torrent/AAAA/pause?mobile=1"), "{}", compact);
+
+        let starred = torrent_toggle_star(State(state.clone()), Path("AAAA".to_string()), Query(mobile))
+            .await
+            .unwrap();
+        assert!(!body_text(starred.into_response()).await.contains(desktop_grid));
+
+        let responsive = torrent_pause(State(state), Path("AAAA".to_string()), Query(FilterQuery::default()))
+            .await
+            .unwrap();
+        let responsive = body_text(responsive.into_response()).await;
+        assert!(responsive.contains(desktop_grid));
+        assert!(!responsive.contains("?mobile=1"));
+    }
+
+    #[tokio::test]
+    async fn auto_stop_leaves_starred_torrents_alone() {
+        let mut seeding = testing::torrent_row("AAAA", "one");
+        seeding[9] = "<i8>1</i8>".to_string();
+        seeding[11] = "<i8>3000</i8>".to_string();
+        let rows = vec![seeding];
+        let mock = MockRtorrent::start(move |xml| match rtorrent::xml_method_name(xml) {
+            "d.multicall2" => testing::torrents_response(&rows),
+            _ => testing::response("<i8>0</i8>"),
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let state = testing::app_state(&mock, dir.path());
+        wait_for_poll(&state).await;
+        state.toggle_star_with("AAAA", |_| ()).await;
+        let calls = mock.requests().len();
+
+        // A ratio rule asking to stop a starred torrent well past its target
+        let form = Form(AutoStopForm { reason: "ratio 2.0 reached".to_string() });
+        let result = torrent_auto_stop(State(state), Path("AAAA".to_string()), Query(FilterQuery::default()), Ok(form)).await;
+
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+        assert!(mock.methods()[calls..].iter().all(|method| method == "d.multicall2"));
+    }
+
+    /// The number in each sidebar count badge, by element id
+    fn sidebar_counts(html: &str) -> Vec<(String, String)> {
+        html.match_indices("id=\"count-")
+            .map(|(start, _)| {
+                let rest = &html[start + 4..];
+                let id = rest[..rest.find('"').unwrap()].to_string();
+                let text = &rest[rest.find('>').unwrap() + 1..];
+                (id, text[..text.find("</span>").unwrap()].trim().to_string())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn index_and_sse_render_the_same_sidebar_counts() {
+        let flag = |on: bool| if on { "<i8>1</i8>" } else { "<i8>0</i8>" }.to_string();
+        let mut seeding = testing::torrent_row("SEED", "seed");
+        seeding[9] = flag(true);
+        seeding[20] = flag(true);
+        let mut paused = testing::torrent_row("PAUSE", "pause");
+        paused[6] = flag(false);
+        let mut stopped = testing::torrent_row("STOP", "stop");
+        stopped[7] = flag(false);
+        let mut errored = testing::torrent_row("ERR", "err");
+        errored[10] = "<string>Storage error</string>".to_string();
+        let rows = vec![testing::torrent_row("DOWN", "down"), seeding, paused, stopped, errored];
+        let mock = MockRtorrent::start(move |xml| match rtorrent::xml_method_name(xml) {
+            "d.multicall2" => testing::torrents_response(&rows),
+            _ => testing::response("<i8>0</i8>"),
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let state = testing::app_state(&mock, dir.path());
+        wait_for_poll(&state).await;
+        let torrents = state.latest_torrents().await.unwrap();
+        state.set_hidden("STOP", true, &torrents).await;
+
+        let index_html = body_text(index(State(state.clone()), Query(FilterQuery::default())).await.into_response()).await;
+        let torrents = state.fresh_torrents().await;
+        let sse_html = torrents_service::render_torrents_html(&state, &FilterQuery::default(), None, &torrents)
+            .await
+            .unwrap();
+
+        let counts = sidebar_counts(&sse_html);
+        assert_eq!(counts.len(), 9);
+        assert_eq!(sidebar_counts(&index_html), counts);
+        assert!(counts.contains(&("count-all".to_string(), "4".to_string())));
+        assert!(counts.contains(&("count-hidden".to_string(), "1".to_string())));
+    }
+
+    /// Remove AAAA with its data, rTorrent placing it at `base_path` in
+    /// `directory`; answers with the response's HX-Trigger
+    async fn remove_with_data(downloads: &std::path::Path, base_path: &str, directory: &str, name: &str, multi: bool) -> String {
+        let location = [
+            format!("<string>{}</string>", base_path),
+            format!("<string>{}</string>", directory),
+            format!("<string>{}</string>", name),
+            format!("<i8>{}</i8>", multi as i64),
+            format!("<string>{}</string>", downloads.display()),
+        ];
+        let rows = vec![testing::torrent_row("AAAA", name)];
+        let mock = MockRtorrent::start(move |xml| match rtorrent::xml_method_name(xml) {
+            "d.multicall2" => testing::torrents_response(&rows),
+            "system.multicall" => {
+                let results: Vec<String> = location.iter().map(|value| testing::array(std::slice::from_ref(value))).collect();
+                testing::response(&testing::array(&results))
+            }
+            _ => testing::response("<i8>0</i8>"),
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let state = testing::app_state(&mock, dir.path());
+        wait_for_poll(&state).await;
+
+        let form = Form(RemoveForm { delete_data: true });
+        let response = torrent_remove(State(state), Path("AAAA".to_string()), Ok(form)).await.unwrap().into_response();
+
+        assert!(mock.methods().contains(&"d.erase".to_string()));
+        response.headers()["HX-Trigger"].to_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn removing_a_nameless_torrent_keeps_the_download_directory() {
+        let downloads = tempfile::tempdir().unwrap();
+        std::fs::write(downloads.path().join("other.iso"), "keep").unwrap();
+        let path = downloads.path().display().to_string();
+
+        // A magnet still fetching metadata has no name, so its path is the directory
+        let trigger = remove_with_data(downloads.path(), &path, &path, "", false).await;
+
+        assert!(trigger.contains("Data not deleted"), "{}", trigger);
+        assert!(downloads.path().join("other.iso").exists());
+    }
+
+    #[tokio::test]
+    async fn removing_a_torrent_in_a_shared_directory_keeps_the_directory() {
+        let downloads = tempfile::tempdir().unwrap();
+        let shared = downloads.path().join("shared");
+        std::fs::create_dir(&shared).unwrap();
+        std::fs::write(shared.join("other.iso"), "keep").unwrap();
+        let path = shared.display().to_string();
+
+        // `d.directory_base.set` pointed a multi-file torrent at the shared folder
+        let trigger = remove_with_data(downloads.path(), &path, &path, "album", true).await;
+
+        assert!(trigger.contains("Data not deleted"), "{}", trigger);
+        assert!(shared.join("other.iso").exists());
+    }
+
+    #[tokio::test]
+    async fn removing_with_data_deletes_the_torrents_own_folder() {
+        let downloads = tempfile::tempdir().unwrap();
+        let album = downloads.path().join("album");
+        std::fs::create_dir(&album).unwrap();
+        std::fs::write(album.join("track.flac"), "data").unwrap();
+        std::fs::write(downloads.path().join("other.iso"), "keep").unwrap();
+        let path = album.display().to_string();
+
+        let trigger = remove_with_data(downloads.path(), &path, &path, "album", true).await;
+
+        assert!(trigger.contains("removed-with-data"), "{}", trigger);
+        assert!(!album.exists());
+        assert!(downloads.path().join("other.iso").exists());
+    }
+
+    /// A mock whose AAAA has an enabled and a removed (disabled) tracker
+    fn tracker_mock() -> MockRtorrent {
+        MockRtorrent::start(|xml| match rtorrent::xml_method_name(xml) {
+            "d.multicall2" => testing::torrents_response(&[]),
+            "t.multicall" => {
+                let tracker = |url: &str, enabled: bool| {
+                    testing::array(&[format!("<string>{}</string>", url), format!("<i8>{}</i8>", enabled as i64)])
+                };
+                testing::response(&testing::array(&[
+                    tracker("http://live.example/announce", true),
+                    tracker("udp://removed.example:80", false),
+                ]))
+            }
+            _ => testing::response("<i8>0</i8>"),
+        })
+    }
+
+    fn tracker_form(url: &str) -> Form<TrackerForm> {
+        Form(TrackerForm { url: url.to_string() })
+    }
+
+    #[tokio::test]
+    async fn adding_a_tracker_inserts_it_and_lists_the_trackers() {
+        let mock = tracker_mock();
+        let dir = tempfile::tempdir().unwrap();
+        let state = testing::app_state(&mock, dir.path());
+
+        let html = torrent_add_tracker(State(state), Path("AAAA".to_string()), tracker_form(" http://new.example/announce "))
+            .await
+            .unwrap();
+
+        let html = body_text(html.into_response()).await;
+        assert!(html.contains("live.example"), "{}", html);
+        let insert = mock.requests().into_iter().find(|xml| rtorrent::xml_method_name(xml) == "d.tracker.insert").unwrap();
+        assert!(insert.contains("<string>http://new.example/announce</string>"), "{}", insert);
+    }
+
+    #[tokio::test]
+    async fn adding_a_tracker_rejects_duplicates_and_bad_urls() {
+        let mock = tracker_mock();
+        let dir = tempfile::tempdir().unwrap();
+        let state = testing::app_state(&mock, dir.path());
+        let add = |url: &str| torrent_add_tracker(State(state.clone()), Path("AAAA".to_string()), tracker_form(url));
+
+        assert!(matches!(add("http://live.example/announce").await, Err(AppError::BadRequest(_))));
+        assert!(matches!(add("ftp://live.example/announce").await, Err(AppError::BadRequest(_))));
+        assert!(matches!(add("not a url").await, Err(AppError::BadRequest(_))));
+        assert!(!mock.methods().iter().any(|method| method == "d.tracker.insert" || method == "t.enable"));
+    }
+
+    #[tokio::test]
+    async fn adding_a_removed_tracker_enables_it_again() {
+        let mock = tracker_mock();
+        let dir = tempfile::tempdir().unwrap();
+        let state = testing::app_state(&mock, dir.path());
+
+        torrent_add_tracker(State(state), Path("AAAA".to_string()), tracker_form("udp://removed.example:80"))
+            .await
+            .unwrap();
+
+        let enable = mock.requests().into_iter().find(|xml| rtorrent::xml_method_name(xml) == "t.enable").unwrap();
+        assert!(enable.contains("<string>AAAA:t1</string>"), "{}", enable);
+        assert!(!mock.methods().iter().any(|method| method == "d.tracker.insert"));
+    }
+
+    #[tokio::test]
+    async fn removing_a_tracker_disables_it_by_index() {
+        let mock = tracker_mock();
+        let dir = tempfile::tempdir().unwrap();
+        let state = testing::app_state(&mock, dir.path());
+
+        torrent_remove_tracker(State(state.clone()), Path(("AAAA".to_string(), 0))).await.unwrap();
+        let missing = torrent_remove_tracker(State(state), Path(("AAAA".to_string(), 5))).await;
+
+        let disable = mock.requests().into_iter().find(|xml| rtorrent::xml_method_name(xml) == "t.disable").unwrap();
+        assert!(disable.contains("<string>AAAA:t0</string>"), "{}", disable);
+        assert!(matches!(missing, Err(AppError::NotFound(_))));
+        assert_eq!(mock.methods().iter().filter(|method| *method == "t.disable").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn moving_in_the_queue_answers_with_the_list_in_queue_order() {
+        let rows = vec![
+            testing::torrent_row("AAAA", "one"),
+            testing::torrent_row("BBBB", "two"),
+            testing::torrent_row("CCCC", "three"),
+        ];
+        let mock = MockRtorrent::start(move |xml| match rtorrent::xml_method_name(xml) {
+            "d.multicall2" => testing::torrents_response(&rows),
+            _ => testing::response("<i8>0</i8>"),
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let state = testing::app_state(&mock, dir.path());
+        wait_for_poll(&state).await;
+        let order = |html: &str| {
+            let mut rows: Vec<_> = ["AAAA", "BBBB", "CCCC"]
+                .into_iter()
+                .map(|hash| (html.find(&format!("id=\"torrent-{}\"", hash)).unwrap(), hash))
+                .collect();
+            rows.sort();
+            rows.into_iter().map(|(_, hash)| hash).collect::<Vec<_>>()
+        };
+
+        let top = torrent_queue_top(State(state.clone()), Path("CCCC".to_string())).await.unwrap().into_response();
+        assert_eq!(top.status(), StatusCode::OK);
+        assert!(top.headers().contains_key("HX-Trigger"));
+        assert_eq!(order(&body_text(top).await), ["CCCC", "AAAA", "BBBB"]);
+
+        let down = torrent_move_down(State(state.clone()), Path("AAAA".to_string())).await.unwrap().into_response();
+        assert_eq!(order(&body_text(down).await), ["CCCC", "BBBB", "AAAA"]);
+
+        // Already last: nothing moves, but the list still comes back
+        let bottom = torrent_queue_bottom(State(state), Path("AAAA".to_string())).await.unwrap().into_response();
+        assert!(!bottom.headers().contains_key("HX-Trigger"));
+        assert_eq!(order(&body_text(bottom).await), ["CCCC", "BBBB", "AAAA"]);
+    }
+
+    #[test]
+    fn trigger_names_with_control_characters_make_valid_headers() {
+        let name = "Bad\u{7f}name\u{1}\t\r\n\u{9b} ✓";
+
+        let [(_, value)] = action_trigger("paused", "AAAA", name);
+
+        let header = axum::http::HeaderValue::from_str(&value).unwrap();
+        let events: serde_json::Value = serde_json::from_slice(header.as_bytes()).unwrap();
+        assert_eq!(events["torrentAction"]["name"], name);
+    }
+
+    #[tokio::test]
+    async fn dht_toggle_is_refused_by_an_rtorrent_without_it() {
+        let mock = MockRtorrent::start(|xml| match crate::rtorrent::xml_method_name(xml) {
+            "system.listMethods" => testing::response(&testing::array(&["<string>dht.statistics</string>".to_string()])),
+            "d.multicall2" => testing::torrents_response(&[]),
+            _ => testing::response("<i8>0</i8>"),
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let state = testing::app_state(&mock, dir.path());
+
+        let result = settings_dht(State(state.clone()), Form(ToggleForm { enabled: true })).await;
+
+        let Err(AppError::BadRequest(message)) = result.map(|_| ()) else { panic!("expected a bad request") };
+        assert!(message.contains("dht.mode.set"), "{}", message);
+        assert!(!mock.methods().iter().any(|method| method == "dht.mode.set"));
+    }
+
+    const TORRENT: &[u8] = b"d8:announce20:http://t.example/ann4:infod6:lengthi5e4:name5:a.txtee";
+    const TORRENT_HASH: &str = "FA3671E2915FE9E91A61556FB0E70EE4A44A87BB";
+
+    /// Upload `file` to re-tie `hash`; answers with the status and the state
+    async fn retie(mock: &MockRtorrent, dir: &std::path::Path, hash: &str, file: &[u8]) -> (StatusCode, Arc<AppState>) {
+        let state = testing::app_state(mock, dir);
+        let app = Router::new()
+            .route("/torrent/{hash}/retie", post(|state, path, multipart| torrent_retie(state, path, 10, multipart)))
+            .with_state(state.clone());
+        let request = Request::post(format!("/torrent/{}/retie", hash))
+            .header("content-type", "multipart/form-data; boundary=XYZ")
+            .body(Body::from(multipart_body("XYZ", &[("file", Some("a.torrent"), file)])))
+            .unwrap();
+        (app.oneshot(request).await.unwrap().status(), state)
+    }
+
+    fn tie_mock() -> MockRtorrent {
+        MockRtorrent::start(|xml| match rtorrent::xml_method_name(xml) {
+            "d.multicall2" => testing::torrents_response(&[]),
+            _ => testing::response("<i8>0</i8>"),
+        })
+    }
+
+    #[tokio::test]
+    async fn retie_refuses_a_torrent_file_for_another_hash() {
+        let mock = tie_mock();
+        let dir = tempfile::tempdir().unwrap();
+
+        let (status, state) = retie(&mock, dir.path(), &"A".repeat(40), TORRENT).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!state.torrents_dir().exists());
+        assert!(!mock.methods().iter().any(|method| method == "d.tied_to_file.set"));
+    }
+
+    #[tokio::test]
+    async fn retie_refuses_files_that_are_not_torrents() {
+        let mock = tie_mock();
+        let dir = tempfile::tempdir().unwrap();
+
+        let (status, state) = retie(&mock, dir.path(), TORRENT_HASH, b"<html>not a torrent</html>").await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(!state.torrents_dir().exists());
+        assert!(!mock.methods().iter().any(|method| method == "d.tied_to_file.set"));
+    }
+
+    #[tokio::test]
+    async fn retie_saves_the_file_under_the_torrents_dir_and_ties_to_it() {
+        let mock = tie_mock();
+        let dir = tempfile::tempdir().unwrap();
+
+        let (status, state) = retie(&mock, dir.path(), &TORRENT_HASH.to_ascii_lowercase(), TORRENT).await;
+
+        assert_eq!(status, StatusCode::OK);
+        let saved = state.torrents_dir().join(format!("{}.torrent", TORRENT_HASH));
+        assert_eq!(std::fs::read(&saved).unwrap(), TORRENT);
+        let tie = mock
+            .requests()
+            .into_iter()
+            .find(|xml| rtorrent::xml_method_name(xml) == "d.tied_to_file.set")
+            .unwrap();
+        assert!(tie.contains(&*saved.to_string_lossy()), "{}", tie);
+    }
+}
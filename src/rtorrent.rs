@@ -4,16 +4,83 @@
 //! XML-RPC interface over a Unix socket.
 
 use bytes::{BufMut, BytesMut};
-use quick_xml::{Reader, Writer, events::{Event, BytesStart, BytesText, BytesEnd}};
+use quick_xml::{Writer, events::{Event, BytesStart, BytesText, BytesEnd}};
+use std::collections::HashSet;
 use std::io::Cursor;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::UnixStream;
 
 use crate::error::{AppError, Result};
+use crate::xmlrpc::{self, Value};
+
+/// XML-RPC methods VibeTorrent relies on; checked against `system.listMethods`
+///
+/// The torrent list multicall isn't listed: either `d.multicall2` or the legacy
+/// `d.multicall` will do, see [`RtorrentClient::missing_from`].
+pub const REQUIRED_METHODS: &[&str] = &[
+    "d.open",
+    "d.close",
+    "d.start",
+    "d.stop",
+    "d.erase",
+    "d.ignore_commands.set",
+    "d.custom.set",
+    "d.custom1.set",
+    "t.multicall",
+    "load.start",
+    "load.raw_start",
+    "d.check_hash",
+    "directory.default",
+    "get_safe_free_diskspace",
+];
+
+/// Methods only one feature needs, with that feature's name. Builds without
+/// them aren't warned about; the feature alone is turned away, see
+/// [`RtorrentClient::unsupported_features`].
+pub const OPTIONAL_METHODS: &[(&str, &str)] = &[
+    ("f.multicall", "file lists"),
+    ("p.multicall", "peer lists"),
+    ("dht.statistics", "DHT and PEX settings"),
+    ("dht.mode.set", "DHT settings"),
+    ("protocol.pex.set", "PEX settings"),
+    ("load.raw", "adding torrents for existing data"),
+];
+
+/// The feature an [`OPTIONAL_METHODS`] entry gates, or the method itself
+pub fn optional_feature(method: &str) -> &str {
+    OPTIONAL_METHODS
+        .iter()
+        .find(|(optional, _)| *optional == method)
+        .map_or(method, |(_, feature)| feature)
+}
 
 #[derive(Debug, Clone)]
 pub struct RtorrentClient {
+    /// Socket path as configured, for error messages
     socket_path: String,
+    /// Absolute path with symlinks followed, used to connect. Re-resolved when
+    /// a connect finds nothing there, e.g. after the symlink was repointed.
+    /// Shared between clones like `legacy_multicall`.
+    resolved_path: Arc<std::sync::RwLock<PathBuf>>,
+    /// Whether `d.multicall2` faulted and the legacy `d.multicall` is used instead.
+    /// Shared between clones so the poller and request handlers learn it once.
+    legacy_multicall: Arc<AtomicBool>,
+    /// Extra SCGI header variables, appended after the standard ones
+    scgi_env: Arc<Vec<(String, String)>>,
+    /// Tries for [`Self::add_torrent_url`] when rTorrent can't be reached
+    add_url_attempts: u32,
+    /// Progress measure given to every parsed [`Torrent`]
+    progress_mode: ProgressMode,
+    /// Rate unit given to every parsed [`Torrent`] and [`GlobalStats`]
+    rate_unit: RateUnit,
+    /// Hashes already warned about as listed twice, so the poller doesn't
+    /// repeat the warning every tick. Shared between clones.
+    warned_duplicates: Arc<std::sync::Mutex<HashSet<String>>>,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -23,6 +90,8 @@ pub struct Torrent {
     pub size_bytes: i64,
     pub completed_bytes: i64,
     pub down_rate: i64,
+    /// Moving average of `down_rate`; the same value until smoothed by `AppState`
+    pub avg_down_rate: i64,
     pub up_rate: i64,
     pub state: TorrentState,
     pub ratio: f64,
@@ -31,19 +100,80 @@ pub struct Torrent {
     pub is_hashing: bool,
     pub complete: bool,
     pub message: String,
+    /// Unix timestamp of when the torrent was loaded into rTorrent
+    pub load_date: i64,
+    /// Force-started: rTorrent's scheduler and ratio groups leave it alone
+    pub is_forced: bool,
+    /// Total bytes uploaded over the torrent's lifetime
+    pub bytes_uploaded: i64,
+    /// Total bytes downloaded over the torrent's lifetime
+    pub bytes_downloaded: i64,
+    /// Stopped via "stop seeding" (tagged in `d.custom`), not an ordinary pause
+    pub done_seeding: bool,
+    /// Why automation stopped it (tagged in `d.custom`), `Some("")` when no
+    /// reason was given; `None` for torrents it didn't stop
+    pub auto_stopped: Option<String>,
+    /// Label kept in `d.custom1`, the slot ruTorrent uses too; empty when unset
+    pub label: String,
+    /// Private flag in the metadata: no DHT or peer exchange, trackers only
+    pub is_private: bool,
+    pub completed_chunks: i64,
+    pub size_chunks: i64,
+    /// Chunks of files selected for download that aren't complete yet
+    pub wanted_chunks: i64,
+    /// Chunks checked so far while `is_hashing`
+    pub chunks_hashed: i64,
+    /// Peers connected right now
+    pub peers_connected: i64,
+    /// Of those, peers that have the whole torrent (seeds)
+    pub peers_complete: i64,
+    /// Which of the progress measures [`Self::progress_percent`] reports
+    pub progress_mode: ProgressMode,
+    /// Unit the `*_rate_formatted` methods display
+    pub rate_unit: RateUnit,
+    /// Seconds since the download finished, while complete; `None` when
+    /// rTorrent doesn't know when that was
+    pub seeding_time: Option<i64>,
+    /// Seconds since data last moved either way; `None` until filled in by
+    /// [`ActivityTracker`](crate::services::activity::ActivityTracker)
+    pub idle_time: Option<i64>,
+    /// Chunks completed per second, averaged; `None` until filled in by
+    /// [`PieceRateTracker`](crate::services::pieces::PieceRateTracker), and
+    /// while not downloading
+    pub chunk_rate: Option<f64>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
 pub enum TorrentState {
     Downloading,
     Seeding,
+    /// Open but not transferring
     Paused,
+    /// Closed: not transferring and its files aren't open
+    Stopped,
     Hashing,
     Error,
 }
 
+impl TorrentState {
+    /// Paused or stopped, i.e. something "Resume" applies to
+    pub fn is_inactive(self) -> bool {
+        matches!(self, TorrentState::Paused | TorrentState::Stopped)
+    }
+}
+
 impl Torrent {
+    /// Progress as configured, see [`ProgressMode`]
     pub fn progress_percent(&self) -> f64 {
+        match self.progress_mode {
+            ProgressMode::Total => self.total_progress_percent(),
+            ProgressMode::Wanted => self.wanted_progress_percent(),
+        }
+    }
+    
+    /// Completed bytes over the whole torrent; never reaches 100% when files
+    /// were deselected
+    pub fn total_progress_percent(&self) -> f64 {
         if self.size_bytes == 0 {
             0.0
         } else {
@@ -51,43 +181,163 @@ impl Torrent {
         }
     }
     
+    /// Completed chunks over the chunks of selected files, so a torrent with
+    /// files deselected reaches 100% once the rest are done. Chunks shared with
+    /// a deselected file count as wanted.
+    pub fn wanted_progress_percent(&self) -> f64 {
+        let wanted = self.completed_chunks + self.wanted_chunks;
+        if self.size_chunks == 0 || wanted == 0 {
+            return self.total_progress_percent();
+        }
+        (self.completed_chunks as f64 / wanted as f64 * 100.0).min(100.0)
+    }
+    
+    /// How far a running hash check has got, in percent; `None` when not
+    /// hashing. The download progress stands still during a check, so this is
+    /// what shows it moving.
+    pub fn hashing_percent(&self) -> Option<f64> {
+        if !self.is_hashing || self.size_chunks == 0 {
+            return None;
+        }
+        Some((self.chunks_hashed as f64 / self.size_chunks as f64 * 100.0).clamp(0.0, 100.0))
+    }
+    
+    /// `name`, or the start of the infohash while rTorrent reports no name (a
+    /// magnet still resolving metadata), so the torrent can still be told apart
+    pub fn display_name(&self) -> &str {
+        if self.name.trim().is_empty() {
+            self.hash.get(..8).unwrap_or(&self.hash)
+        } else {
+            &self.name
+        }
+    }
+    
     pub fn size_formatted(&self) -> String {
         format_bytes(self.size_bytes)
     }
     
     pub fn down_rate_formatted(&self) -> String {
-        format!("{}/s", format_bytes(self.down_rate))
+        format_rate(self.down_rate, self.rate_unit)
     }
     
     pub fn up_rate_formatted(&self) -> String {
-        format!("{}/s", format_bytes(self.up_rate))
+        format_rate(self.up_rate, self.rate_unit)
+    }
+    
+    pub fn uploaded_formatted(&self) -> String {
+        format_bytes(self.bytes_uploaded)
+    }
+    
+    pub fn downloaded_formatted(&self) -> String {
+        format_bytes(self.bytes_downloaded)
     }
     
+    /// Time left at the smoothed download rate
     pub fn eta(&self) -> Option<String> {
-        if self.complete || self.down_rate == 0 {
+        self.eta_at(self.avg_down_rate)
+    }
+    
+    /// Time left at the instantaneous download rate
+    pub fn eta_instant(&self) -> Option<String> {
+        self.eta_at(self.down_rate)
+    }
+    
+    fn eta_at(&self, rate: i64) -> Option<String> {
+        if self.complete || rate <= 0 {
             return None;
         }
-        let remaining = self.size_bytes - self.completed_bytes;
-        let seconds = remaining / self.down_rate;
+        // rTorrent can briefly report more completed bytes than the total
+        let remaining = self.size_bytes.saturating_sub(self.completed_bytes).max(0);
+        let seconds = remaining / rate;
         Some(format_duration(seconds))
     }
     
+    /// Tagged in `d.custom` as done seeding or auto-stopped, which a resume clears
+    pub fn has_stop_tag(&self) -> bool {
+        self.done_seeding || self.auto_stopped.is_some()
+    }
+    
+    /// Finished and deliberately stopped; kept in the list but no longer seeding
+    pub fn is_done_seeding(&self) -> bool {
+        self.done_seeding && self.complete && self.state.is_inactive()
+    }
+    
+    /// Stopped by automation rather than by hand, and not restarted since.
+    /// Rules that start torrents should leave these alone.
+    pub fn is_auto_stopped(&self) -> bool {
+        self.auto_stopped.is_some() && self.state.is_inactive()
+    }
+    
     pub fn status_text(&self) -> &'static str {
+        if self.is_done_seeding() {
+            return "Done";
+        }
+        if self.is_auto_stopped() {
+            return "Auto-stopped";
+        }
         match self.state {
             TorrentState::Downloading => "Downloading",
             TorrentState::Seeding => "Seeding",
             TorrentState::Paused => "Paused",
+            TorrentState::Stopped => "Stopped",
             TorrentState::Hashing => "Hashing",
             TorrentState::Error => "Error",
         }
     }
     
     pub fn progress_bar_class(&self) -> &'static str {
-        "bg-emerald-500"
+        if self.is_hashing {
+            "bg-yellow-500"
+        } else {
+            "bg-emerald-500"
+        }
+    }
+    
+    /// Magnet link for sharing: infohash plus display name
+    pub fn magnet_link(&self) -> String {
+        format!(
+            "magnet:?xt=urn:btih:{}&dn={}",
+            self.hash.to_lowercase(),
+            percent_encode(&self.name)
+        )
+    }
+    
+    /// A magnet that hasn't fetched its metadata yet reports a zero total size
+    pub fn is_awaiting_metadata(&self) -> bool {
+        self.size_bytes == 0 && !self.is_hashing
+    }
+    
+    /// How long a magnet has been waiting for metadata, based on its load date
+    pub fn metadata_pending_for(&self) -> Option<String> {
+        if !self.is_awaiting_metadata() || self.load_date <= 0 {
+            return None;
+        }
+        Some(format_duration((unix_now() - self.load_date).max(0)))
+    }
+    
+    pub fn seeding_time_formatted(&self) -> Option<String> {
+        self.seeding_time.map(format_duration)
+    }
+    
+    pub fn idle_time_formatted(&self) -> Option<String> {
+        self.idle_time.map(format_duration)
+    }
+    
+    pub fn chunk_rate_formatted(&self) -> Option<String> {
+        self.chunk_rate.map(|rate| format!("{:.1} pieces/s", rate))
     }
 }
 
-fn format_bytes(bytes: i64) -> String {
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Human-readable size; negative values (transient rTorrent glitches) read as 0 B
+pub fn format_bytes(bytes: i64) -> String {
+    let bytes = bytes.max(0);
     const KB: i64 = 1024;
     const MB: i64 = KB * 1024;
     const GB: i64 = MB * 1024;
@@ -106,12 +356,68 @@ fn format_bytes(bytes: i64) -> String {
     }
 }
 
+/// Transfer rate from bytes per second: binary units for `bytes`, decimal
+/// ones (as ISPs quote them) for `bits`
+pub fn format_rate(bytes_per_sec: i64, unit: RateUnit) -> String {
+    match unit {
+        RateUnit::Bytes => format!("{}/s", format_bytes(bytes_per_sec)),
+        RateUnit::Bits => {
+            let bits = bytes_per_sec.max(0) as f64 * 8.0;
+            if bits >= 1e9 {
+                format!("{:.1} Gbps", bits / 1e9)
+            } else if bits >= 1e6 {
+                format!("{:.1} Mbps", bits / 1e6)
+            } else if bits >= 1e3 {
+                format!("{:.1} Kbps", bits / 1e3)
+            } else {
+                format!("{} bps", bits)
+            }
+        }
+    }
+}
+
+/// Percent-encode everything outside RFC 3986's unreserved set
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Undo `%XX` escapes; `None` for a malformed escape or invalid UTF-8
+fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// `3d 4h` / `1h 5m` / `4m 10s` / `7s`; negative durations read as 0s
 fn format_duration(seconds: i64) -> String {
-    let hours = seconds / 3600;
+    let seconds = seconds.max(0);
+    let days = seconds / 86400;
+    let hours = (seconds % 86400) / 3600;
     let minutes = (seconds % 3600) / 60;
     let secs = seconds % 60;
     
-    if hours > 0 {
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
         format!("{}h {}m", hours, minutes)
     } else if minutes > 0 {
         format!("{}m {}s", minutes, secs)
@@ -126,15 +432,43 @@ pub struct GlobalStats {
     pub up_rate: i64,
     pub free_disk_space: i64,
     pub active_peers: i64,
+    /// Connected peers that are seeds, across all torrents
+    pub connected_seeds: i64,
+    /// Connected peers still downloading, across all torrents
+    pub connected_leechers: i64,
+    /// Unit the `*_rate_formatted` methods display
+    pub rate_unit: RateUnit,
 }
 
 impl GlobalStats {
+    /// Stats with nothing known yet, e.g. before the first poll
+    pub fn empty(rate_unit: RateUnit) -> Self {
+        Self {
+            down_rate: 0,
+            up_rate: 0,
+            free_disk_space: 2_000_000_000_000,
+            active_peers: 0,
+            connected_seeds: 0,
+            connected_leechers: 0,
+            rate_unit,
+        }
+    }
+    
+    /// Fill in the rates and peer counts, which rTorrent only reports per torrent
+    pub fn add_torrent_totals(&mut self, torrents: &[Torrent]) {
+        self.down_rate = torrents.iter().map(|t| t.down_rate).sum();
+        self.up_rate = torrents.iter().map(|t| t.up_rate).sum();
+        self.active_peers = torrents.iter().map(|t| t.peers_connected).sum();
+        self.connected_seeds = torrents.iter().map(|t| t.peers_complete).sum();
+        self.connected_leechers = self.active_peers - self.connected_seeds;
+    }
+    
     pub fn down_rate_formatted(&self) -> String {
-        format!("{}/s", format_bytes(self.down_rate))
+        format_rate(self.down_rate, self.rate_unit)
     }
     
     pub fn up_rate_formatted(&self) -> String {
-        format!("{}/s", format_bytes(self.up_rate))
+        format_rate(self.up_rate, self.rate_unit)
     }
     
     pub fn free_disk_formatted(&self) -> String {
@@ -142,9 +476,368 @@ impl GlobalStats {
     }
 }
 
+/// rTorrent's global caps on simultaneous downloads and uploads; 0 means unlimited
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct TransferLimits {
+    pub max_downloads: i64,
+    pub max_uploads: i64,
+}
+
+/// How rTorrent accepts incoming peer connections
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct NetworkInfo {
+    /// Port rTorrent listens on for peers
+    pub listen_port: i64,
+    /// Pending-connection queue length of the listening socket
+    pub listen_backlog: i64,
+    /// `network.port_open`: whether rTorrent opens a listening port at all;
+    /// `None` on builds without it
+    pub port_open: Option<bool>,
+}
+
+/// What rTorrent says about one of its methods
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MethodHelp {
+    pub name: String,
+    /// `system.methodHelp`; rTorrent leaves most of them empty
+    pub help: String,
+    /// `system.methodSignature`: the return type, then the parameter types, for
+    /// each form the method accepts; empty when rTorrent doesn't say
+    pub signatures: Vec<Vec<String>>,
+}
+
+/// Where and when rTorrent saves torrents' state, which it restores on restart
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SessionInfo {
+    /// `session.path`; empty when rTorrent runs without a session directory
+    pub path: String,
+    /// `session.on_completion`: also save a torrent's state when it finishes
+    pub on_completion: bool,
+}
+
+impl SessionInfo {
+    pub fn is_enabled(&self) -> bool {
+        !self.path.is_empty()
+    }
+}
+
+/// Name of the schedule rTorrent's default config saves the session with, so
+/// setting an interval replaces it rather than adding a second one
+const SESSION_SAVE_SCHEDULE: &str = "session_save";
+
+/// rTorrent's trackerless peer discovery, which private trackers require off
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PeerDiscovery {
+    /// `dht.mode`: `disable`, `off`, `auto` or `on`
+    pub dht_mode: String,
+    /// Peer exchange (`protocol.pex`)
+    pub pex: bool,
+}
+
+impl PeerDiscovery {
+    /// DHT runs now or will start by itself (`auto` starts it for public torrents)
+    pub fn dht_enabled(&self) -> bool {
+        matches!(self.dht_mode.as_str(), "auto" | "on")
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Tracker {
+    /// Position in the torrent's tracker list, used to address it as `{hash}:t{index}`
+    pub index: usize,
+    pub url: String,
+    pub is_enabled: bool,
+}
+
+impl Tracker {
+    /// Lowercased host of the announce URL, without scheme, credentials or port
+    pub fn host(&self) -> Option<String> {
+        tracker_host(&self.url)
+    }
+}
+
+/// One file of a torrent
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TorrentFile {
+    /// Position in the torrent's file list, used to address it as `{hash}:f{index}`
+    pub index: usize,
+    /// Relative to the torrent's base path
+    pub path: String,
+    pub size_bytes: i64,
+    pub completed_chunks: i64,
+    pub size_chunks: i64,
+    /// 0 = don't download, 1 = normal, 2 = high
+    pub priority: i64,
+}
+
+/// A connected peer
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Peer {
+    pub address: String,
+    pub port: i64,
+    pub client: String,
+    /// How much of the torrent the peer has, 0-100
+    pub completed_percent: i64,
+    pub down_rate: i64,
+    pub up_rate: i64,
+    pub is_encrypted: bool,
+    pub is_incoming: bool,
+}
+
+/// Swarm size as reported by one tracker's scrape
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TrackerScrape {
+    pub url: String,
+    pub is_enabled: bool,
+    /// Seeders (`t.scrape_complete`)
+    pub seeders: i64,
+    /// Leechers (`t.scrape_incomplete`)
+    pub leechers: i64,
+    /// Unix time of the last successful scrape, 0 if the tracker never answered one
+    pub last_scrape: i64,
+}
+
+impl TrackerScrape {
+    /// Trackers that don't support scrape leave the counts at 0 and never set a time
+    pub fn has_data(&self) -> bool {
+        self.is_enabled && self.last_scrape > 0
+    }
+}
+
+/// Where a torrent's data is on disk, see [`RtorrentClient::get_data_location`]
+#[derive(Debug, Clone, Default)]
+pub struct DataLocation {
+    /// `d.base_path`: the file of a single-file torrent, the folder of a
+    /// multi-file one. Empty while the torrent is closed.
+    pub base_path: PathBuf,
+    /// `d.directory`: the folder holding a single-file torrent's file, a
+    /// multi-file torrent's own folder (unless set with `d.directory_base.set`)
+    pub directory: PathBuf,
+    pub name: String,
+    pub is_multi_file: bool,
+    /// `directory.default`
+    pub default_directory: PathBuf,
+}
+
+impl DataLocation {
+    /// The path to delete along with the torrent, or why that isn't safe.
+    ///
+    /// Only a path named after the torrent qualifies, and never a download
+    /// directory or a folder above one: a torrent without a name yet (a magnet
+    /// still fetching metadata) or one pointed straight at a shared folder
+    /// would otherwise take everything else in there with it.
+    pub fn deletable_path(&self) -> std::result::Result<&Path, String> {
+        let path = self.base_path.as_path();
+        let refuse = |why: &str| Err(format!("refusing to delete {:?}: {}", path.display().to_string(), why));
+        
+        if self.name.is_empty() {
+            return refuse("the torrent has no name yet");
+        }
+        if path.as_os_str().is_empty() {
+            return refuse("rTorrent reports no data path, the torrent may be closed");
+        }
+        if !path.is_absolute() || path.parent().is_none() {
+            return refuse("not an absolute path below the root");
+        }
+        if path.file_name() != Some(std::ffi::OsStr::new(&self.name)) {
+            return refuse("it isn't named after the torrent");
+        }
+        // A multi-file torrent's own folder is its `d.directory`
+        let is_own_folder = self.is_multi_file && path == self.directory;
+        if (self.directory.starts_with(path) && !is_own_folder) || self.default_directory.starts_with(path) {
+            return refuse("it is a download directory or holds one");
+        }
+        Ok(path)
+    }
+}
+
+pub fn tracker_host(url: &str) -> Option<String> {
+    let rest = url.split_once("://").map(|(_, rest)| rest)?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host_port = authority.rsplit('@').next()?;
+    let host = if let Some(bracketed) = host_port.strip_prefix('[') {
+        bracketed.split(']').next()?
+    } else {
+        host_port.split(':').next()?
+    };
+    if host.is_empty() {
+        None
+    } else {
+        Some(host.to_lowercase())
+    }
+}
+
+/// Trackers are announced to over HTTP(S) or UDP; anything else is rejected
+pub fn is_valid_tracker_url(url: &str) -> bool {
+    let scheme = url.split_once("://").map(|(scheme, _)| scheme.to_ascii_lowercase());
+    matches!(scheme.as_deref(), Some("http" | "https" | "udp")) && tracker_host(url).is_some()
+}
+
+/// Infohash named by a magnet link's `xt=urn:btih:` parameter, in uppercase hex.
+/// Both the hex and the base32 spelling are accepted.
+pub fn magnet_info_hash(url: &str) -> Option<String> {
+    let query = url.strip_prefix("magnet:?")?;
+    query.split('&').find_map(|pair| {
+        // Some clients percent-encode the parameter, colons included
+        let value = pair.strip_prefix("xt=").and_then(percent_decode)?;
+        let hash = value.strip_prefix("urn:btih:")?;
+        match hash.len() {
+            40 if hash.bytes().all(|b| b.is_ascii_hexdigit()) => Some(hash.to_ascii_uppercase()),
+            32 => base32_decode(hash).map(|bytes| bytes.iter().map(|b| format!("{:02X}", b)).collect()),
+            _ => None,
+        }
+    })
+}
+
+/// RFC 4648 base32 without padding
+fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 5 / 8);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in text.bytes().map(|b| b.to_ascii_uppercase()) {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'2'..=b'7' => c - b'2' + 26,
+            _ => return None,
+        };
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+/// Failures worth retrying: rTorrent busy, restarting or dropping the
+/// connection, as opposed to answering with a fault
+fn is_transient(e: &AppError) -> bool {
+    matches!(e, AppError::RtorrentConnection(_) | AppError::ScgiError(_) | AppError::IoError(_))
+}
+
+/// XML for debug logs, cut short so a torrent upload or a big multicall
+/// doesn't flood them
+fn log_excerpt(xml: &str) -> std::borrow::Cow<'_, str> {
+    const MAX_CHARS: usize = 4096;
+    match xml.char_indices().nth(MAX_CHARS) {
+        Some((end, _)) => format!("{}... ({} bytes)", &xml[..end], xml.len()).into(),
+        None => xml.into(),
+    }
+}
+
+/// What torrent progress is measured against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgressMode {
+    /// Completed bytes over the torrent's total size
+    #[default]
+    Total,
+    /// Completed chunks over those of the files selected for download
+    Wanted,
+}
+
+/// How transfer rates are displayed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateUnit {
+    /// KB/s, MB/s, in powers of 1024
+    #[default]
+    Bytes,
+    /// Kbps, Mbps, in powers of 1000
+    Bits,
+}
+
+/// Settings for a torrent added with [`RtorrentClient::add_torrent_url_with`]
+#[derive(Debug, Clone, Default)]
+pub struct AddOptions {
+    pub label: Option<String>,
+    /// Download directory instead of rTorrent's default
+    pub directory: Option<String>,
+    /// Load it stopped (`load.normal`) rather than started
+    pub start_paused: bool,
+}
+
+impl AddOptions {
+    /// A value rTorrent's command parser can't take inside double quotes
+    pub fn is_unquotable(value: &str) -> bool {
+        value.chars().any(|c| c == '"' || c == '\\' || c.is_control())
+    }
+    
+    /// Commands for `load.*` to run on the new download, values quoted so
+    /// commas and spaces in them survive
+    fn load_commands(&self) -> Vec<String> {
+        let mut commands = Vec::new();
+        if let Some(label) = &self.label {
+            commands.push(format!("d.custom1.set=\"{}\"", label));
+        }
+        if let Some(directory) = &self.directory {
+            commands.push(format!("d.directory.set=\"{}\"", directory));
+        }
+        commands
+    }
+}
+
+/// A typed XML-RPC call parameter
+#[derive(Debug, Clone, Copy)]
+pub enum XmlRpcParam<'a> {
+    Str(&'a str),
+    Int(i64),
+}
+
 impl RtorrentClient {
     pub fn new(socket_path: String) -> Self {
-        Self { socket_path }
+        Self {
+            resolved_path: Arc::new(std::sync::RwLock::new(resolve_socket_path(&socket_path))),
+            socket_path,
+            legacy_multicall: Arc::new(AtomicBool::new(false)),
+            scgi_env: Arc::new(Vec::new()),
+            add_url_attempts: 1,
+            progress_mode: ProgressMode::default(),
+            rate_unit: RateUnit::default(),
+            warned_duplicates: Arc::new(std::sync::Mutex::new(HashSet::new())),
+        }
+    }
+    
+    pub fn with_progress_mode(mut self, mode: ProgressMode) -> Self {
+        self.progress_mode = mode;
+        self
+    }
+    
+    pub fn with_rate_unit(mut self, unit: RateUnit) -> Self {
+        self.rate_unit = unit;
+        self
+    }
+    
+    /// Retry adds by URL up to `attempts` times in all (at least once) while
+    /// rTorrent is unreachable or drops the connection
+    pub fn with_add_url_attempts(mut self, attempts: u32) -> Self {
+        self.add_url_attempts = attempts.max(1);
+        self
+    }
+    
+    /// Send `env` with every request. Entries that can't be encoded (NUL bytes,
+    /// empty names) or that would override the standard variables are skipped.
+    pub fn with_scgi_env<'a>(mut self, env: impl IntoIterator<Item = (&'a String, &'a String)>) -> Self {
+        const RESERVED: &[&str] = &["CONTENT_LENGTH", "SCGI", "REQUEST_METHOD", "REQUEST_URI"];
+        let env = env
+            .into_iter()
+            .filter(|(key, value)| {
+                if key.is_empty() || key.contains('\0') || value.contains('\0') {
+                    tracing::warn!("Ignoring SCGI variable {:?}: empty name or NUL byte", key);
+                    false
+                } else if RESERVED.contains(&key.as_str()) {
+                    tracing::warn!("Ignoring SCGI variable {}: it is set by VibeTorrent", key);
+                    false
+                } else {
+                    true
+                }
+            })
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        self.scgi_env = Arc::new(env);
+        self
     }
     
     /// Test connection to rtorrent by attempting to connect to the socket
@@ -153,29 +846,82 @@ impl RtorrentClient {
     }
     
     async fn connect(&self) -> Result<UnixStream> {
-        UnixStream::connect(&self.socket_path)
-            .await
-            .map_err(|e| AppError::RtorrentConnection(format!(
-                "Failed to connect to {}: {}", self.socket_path, e
-            )))
+        let mut path = self.resolved_path.read().unwrap_or_else(|e| e.into_inner()).clone();
+        let mut result = UnixStream::connect(&path).await;
+        
+        // Nothing listening where the path used to lead: rTorrent may have come
+        // back on a new socket behind the same symlink, or the socket may only
+        // exist now that rTorrent has started
+        if let Err(e) = &result {
+            if matches!(e.kind(), std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused) {
+                let resolved = resolve_socket_path(&self.socket_path);
+                if resolved != path {
+                    tracing::debug!("SCGI socket {} now resolves to {}", self.socket_path, resolved.display());
+                    *self.resolved_path.write().unwrap_or_else(|e| e.into_inner()) = resolved.clone();
+                    path = resolved;
+                    result = UnixStream::connect(&path).await;
+                }
+            }
+        }
+        
+        result.map_err(|e| {
+            let location = if Path::new(&self.socket_path) == path {
+                self.socket_path.clone()
+            } else {
+                format!("{} (resolved to {})", self.socket_path, path.display())
+            };
+            AppError::RtorrentConnection(format!(
+                "Failed to connect to {}: {}",
+                location,
+                diagnose_socket(&path, &e)
+            ))
+        })
     }
     
     async fn send_request(&self, xml_body: &str) -> Result<String> {
-        let mut stream = self.connect().await?;
-        
-        // Build SCGI request
-        let content_length = xml_body.len();
-        let headers = format!(
+        let started = std::time::Instant::now();
+        let method = xml_method_name(xml_body);
+        tracing::debug!("SCGI {} request: {}", method, log_excerpt(xml_body));
+        let result = self.exchange(xml_body).await;
+        match &result {
+            Ok(body) => {
+                tracing::debug!("SCGI {} ok: {} bytes in {:?}", method, body.len(), started.elapsed());
+                tracing::debug!("SCGI {} response: {}", method, log_excerpt(body));
+            }
+            Err(e) => tracing::debug!("SCGI {} failed after {:?}: {}", method, started.elapsed(), e),
+        }
+        result
+    }
+
+    /// Frame `body` as an SCGI request.
+    ///
+    /// Takes bytes so `CONTENT_LENGTH` is the byte count: names and paths in the
+    /// XML are often multibyte UTF-8, and a character count would cut the body
+    /// short and desync the connection.
+    fn scgi_request(&self, body: &[u8]) -> BytesMut {
+        let mut headers = format!(
             "CONTENT_LENGTH\0{}\0SCGI\01\0REQUEST_METHOD\0POST\0REQUEST_URI\0/RPC2\0",
-            content_length
+            body.len()
         );
+        for (key, value) in self.scgi_env.iter() {
+            headers.push_str(key);
+            headers.push('\0');
+            headers.push_str(value);
+            headers.push('\0');
+        }
         
         // Netstring format: length:content,
         let mut request = BytesMut::new();
         request.put_slice(format!("{}:", headers.len()).as_bytes());
         request.put_slice(headers.as_bytes());
         request.put_u8(b',');
-        request.put_slice(xml_body.as_bytes());
+        request.put_slice(body);
+        request
+    }
+    
+    async fn exchange(&self, xml_body: &str) -> Result<String> {
+        let mut stream = self.connect().await?;
+        let request = self.scgi_request(xml_body.as_bytes());
         
         // Send request
         stream.write_all(&request).await
@@ -301,6 +1047,92 @@ impl RtorrentClient {
         Ok(format!("<?xml version=\"1.0\"?>\n{}", xml_body))
     }
     
+    /// Build a method call with arbitrary typed params, escaping string values
+    fn build_call_xml(method: &str, params: &[XmlRpcParam]) -> Result<String> {
+        fn write<W: std::io::Write>(writer: &mut Writer<W>, event: Event) -> Result<()> {
+            writer
+                .write_event(event)
+                .map(|_| ())
+                .map_err(|e| AppError::XmlBuildError(e.to_string()))
+        }
+        
+        let mut writer = Writer::new(Cursor::new(Vec::new()));
+        write(&mut writer, Event::Start(BytesStart::new("methodCall")))?;
+        write(&mut writer, Event::Start(BytesStart::new("methodName")))?;
+        write(&mut writer, Event::Text(BytesText::new(method)))?;
+        write(&mut writer, Event::End(BytesEnd::new("methodName")))?;
+        write(&mut writer, Event::Start(BytesStart::new("params")))?;
+        
+        for param in params {
+            let (tag, text) = match param {
+                XmlRpcParam::Str(value) => ("string", value.to_string()),
+                XmlRpcParam::Int(value) => ("i8", value.to_string()),
+            };
+            write(&mut writer, Event::Start(BytesStart::new("param")))?;
+            write(&mut writer, Event::Start(BytesStart::new("value")))?;
+            write(&mut writer, Event::Start(BytesStart::new(tag)))?;
+            write(&mut writer, Event::Text(BytesText::new(&text)))?;
+            write(&mut writer, Event::End(BytesEnd::new(tag)))?;
+            write(&mut writer, Event::End(BytesEnd::new("value")))?;
+            write(&mut writer, Event::End(BytesEnd::new("param")))?;
+        }
+        
+        write(&mut writer, Event::End(BytesEnd::new("params")))?;
+        write(&mut writer, Event::End(BytesEnd::new("methodCall")))?;
+        
+        let result = writer.into_inner().into_inner();
+        let xml_body =
+            String::from_utf8(result).map_err(|e| AppError::XmlBuildError(e.to_string()))?;
+        Ok(format!("<?xml version=\"1.0\"?>\n{}", xml_body))
+    }
+    
+    /// Build a `system.multicall` running every `(method, params)` in one request
+    fn build_system_multicall_xml(calls: &[(&str, &[XmlRpcParam])]) -> String {
+        let calls: String = calls
+            .iter()
+            .map(|(method, params)| {
+                let params: String = params
+                    .iter()
+                    .map(|param| match param {
+                        XmlRpcParam::Str(value) => {
+                            format!("<value><string>{}</string></value>", quick_xml::escape::escape(*value))
+                        }
+                        XmlRpcParam::Int(value) => format!("<value><i8>{}</i8></value>", value),
+                    })
+                    .collect();
+                format!(
+                    "<value><struct>\
+                     <member><name>methodName</name><value><string>{}</string></value></member>\
+                     <member><name>params</name><value><array><data>{}</data></array></value></member>\
+                     </struct></value>",
+                    method, params
+                )
+            })
+            .collect();
+        format!(
+            "<?xml version=\"1.0\"?>\n<methodCall><methodName>system.multicall</methodName>\
+             <params><param><value><array><data>{}</data></array></value></param></params></methodCall>",
+            calls
+        )
+    }
+    
+    /// The result of each call in a `system.multicall` response, which wraps
+    /// every result in a one-element array; any fault fails the whole call
+    fn parse_system_multicall(xml: &str) -> Result<Vec<Value>> {
+        let results = xmlrpc::parse_response(xml)?
+            .into_array()
+            .ok_or_else(|| AppError::XmlRpcError("system.multicall response is not an array".to_string()))?;
+        results
+            .into_iter()
+            .map(|result| match result {
+                Value::Array(mut items) if !items.is_empty() => Ok(items.swap_remove(0)),
+                fault => Err(AppError::XmlRpcError(
+                    fault.get("faultString").and_then(Value::as_str).unwrap_or("Unknown XML-RPC fault").to_string(),
+                )),
+            })
+            .collect()
+    }
+    
     fn build_simple_xml(method: &str) -> String {
         format!(
             r#"<?xml version="1.0"?>
@@ -325,154 +1157,205 @@ impl RtorrentClient {
         )
     }
     
+    /// Per-torrent fields requested by `get_torrents`, in parse order
+    const TORRENT_FIELDS: &'static [&'static str] = &[
+        "d.hash=",
+        "d.name=",
+        "d.size_bytes=",
+        "d.completed_bytes=",
+        "d.down.rate=",
+        "d.up.rate=",
+        "d.is_active=",
+        "d.is_open=",
+        "d.is_hash_checking=",
+        "d.complete=",
+        "d.message=",
+        "d.ratio=",
+        "d.load_date=",
+        "d.ignore_commands=",
+        "d.up.total=",
+        "d.down.total=",
+        "d.custom=vt_state",
+        "d.custom1=",
+        "d.timestamp.finished=",
+        // Finish time as recorded by ruTorrent, for sessions it managed
+        "d.custom=seedingtime",
+        "d.is_private=",
+        "d.completed_chunks=",
+        "d.size_chunks=",
+        // Chunks of selected files still missing
+        "d.wanted_chunks=",
+        // Chunks a running hash check has got through
+        "d.chunks_hashed=",
+        "d.peers_connected=",
+        "d.peers_complete=",
+    ];
+    
     pub async fn get_torrents(&self) -> Result<Vec<Torrent>> {
-        let xml = Self::build_multicall_xml(
-            "d.multicall2",
-            &[
-                "d.hash=",
-                "d.name=",
-                "d.size_bytes=",
-                "d.completed_bytes=",
-                "d.down.rate=",
-                "d.up.rate=",
-                "d.is_active=",
-                "d.is_open=",
-                "d.is_hash_checking=",
-                "d.complete=",
-                "d.message=",
-                "d.ratio=",
-            ],
-        )?;
+        let legacy = self.legacy_multicall.load(Ordering::Relaxed);
+        let response = match self.torrents_multicall(legacy).await {
+            Err(AppError::XmlRpcError(fault)) if is_method_missing_fault(&fault) => {
+                // Only one of d.multicall2 / d.multicall exists on a given
+                // rTorrent; remember the one that works
+                tracing::info!(
+                    "{} unavailable, falling back to {}",
+                    Self::multicall_method(legacy),
+                    Self::multicall_method(!legacy)
+                );
+                let response = self.torrents_multicall(!legacy).await?;
+                self.legacy_multicall.store(!legacy, Ordering::Relaxed);
+                response
+            }
+            result => result?,
+        };
+        tracing::trace!("get_torrents response length: {} bytes", response.len());
+        self.parse_torrents_response(&response)
+    }
+    
+    fn multicall_method(legacy: bool) -> &'static str {
+        if legacy { "d.multicall" } else { "d.multicall2" }
+    }
+    
+    /// Run the torrent list multicall with either request shape.
+    ///
+    /// `d.multicall2` takes an empty target before the view name; the legacy
+    /// `d.multicall` starts with the view.
+    async fn torrents_multicall(&self, legacy: bool) -> Result<String> {
+        let xml = if legacy {
+            let params: Vec<XmlRpcParam> = std::iter::once(XmlRpcParam::Str("main"))
+                .chain(Self::TORRENT_FIELDS.iter().map(|f| XmlRpcParam::Str(f)))
+                .collect();
+            Self::build_call_xml("d.multicall", &params)?
+        } else {
+            Self::build_multicall_xml("d.multicall2", Self::TORRENT_FIELDS)?
+        };
         
         tracing::trace!("get_torrents request XML length: {} bytes", xml.len());
         let response = self.send_request(&xml).await?;
-        tracing::trace!("get_torrents response length: {} bytes", response.len());
-        self.parse_torrents_response(&response)
+        match parse_fault(&response) {
+            Some(fault) => Err(AppError::XmlRpcError(fault)),
+            None => Ok(response),
+        }
     }
     
     fn parse_torrents_response(&self, xml: &str) -> Result<Vec<Torrent>> {
         let mut torrents = Vec::new();
-        let mut reader = Reader::from_str(xml);
-        reader.config_mut().trim_text(true);
+        let now = unix_now();
+        // A corrupt session can list a hash twice; rows and actions are keyed
+        // by hash, so only the first one is kept
+        let mut seen = HashSet::new();
+        let mut duplicates = Vec::new();
         
-        let mut current_values: Vec<String> = Vec::new();
-        let mut in_value_tag = false;
-        let mut value_collected = false;
-        let mut in_array = false;
-        let mut array_depth = 0;
-        let mut buf = Vec::new();
-        
-        loop {
-            match reader.read_event_into(&mut buf) {
-                Ok(Event::Start(e)) => {
-                    match e.name().as_ref() {
-                        b"array" => {
-                            array_depth += 1;
-                            if array_depth == 2 {
-                                in_array = true;
-                                current_values.clear();
-                            }
-                        }
-                        b"i4" | b"i8" | b"int" | b"string" | b"double" => {
-                            if in_array {
-                                in_value_tag = true;
-                                value_collected = false;
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-                Ok(Event::End(e)) => {
-                    match e.name().as_ref() {
-                        b"array" => {
-                            if array_depth == 2 && current_values.len() >= 12 {
-                                // Parse torrent from values
-                                let is_active = current_values[6].parse::<i64>().unwrap_or(0) == 1;
-                                let is_open = current_values[7].parse::<i64>().unwrap_or(0) == 1;
-                                let is_hashing = current_values[8].parse::<i64>().unwrap_or(0) == 1;
-                                let complete = current_values[9].parse::<i64>().unwrap_or(0) == 1;
-                                
-                                let state = if is_hashing {
-                                    TorrentState::Hashing
-                                } else if !current_values[10].is_empty() && current_values[10] != "0" {
-                                    TorrentState::Error
-                                } else if !is_active && !is_open {
-                                    TorrentState::Paused
-                                } else if !is_active {
-                                    TorrentState::Paused
-                                } else if complete {
-                                    TorrentState::Seeding
-                                } else {
-                                    TorrentState::Downloading
-                                };
-                                
-                                torrents.push(Torrent {
-                                    hash: current_values[0].clone(),
-                                    name: current_values[1].clone(),
-                                    size_bytes: current_values[2].parse().unwrap_or(0),
-                                    completed_bytes: current_values[3].parse().unwrap_or(0),
-                                    down_rate: current_values[4].parse().unwrap_or(0),
-                                    up_rate: current_values[5].parse().unwrap_or(0),
-                                    is_active,
-                                    is_open,
-                                    is_hashing,
-                                    complete,
-                                    message: current_values[10].clone(),
-                                    ratio: current_values[11].parse::<f64>().unwrap_or(0.0) / 1000.0,
-                                    state,
-                                });
-                            }
-                            array_depth -= 1;
-                            if array_depth < 2 {
-                                in_array = false;
-                            }
-                        }
-                        b"i4" | b"i8" | b"int" | b"string" | b"double" => {
-                            // If we're closing a value tag and no value was collected, add empty string
-                            if in_value_tag && !value_collected && in_array {
-                                current_values.push(String::new());
-                            }
-                            in_value_tag = false;
-                            value_collected = false;
-                        }
-                        _ => {}
-                    }
-                }
-                Ok(Event::Text(e)) => {
-                    if in_value_tag && in_array {
-                        current_values.push(e.unescape().unwrap_or_default().to_string());
-                        value_collected = true;
-                    }
-                }
-                Ok(Event::Empty(e)) => {
-                    // Handle empty tags like <string/>
-                    if in_array {
-                        match e.name().as_ref() {
-                            b"string" | b"i4" | b"i8" | b"int" | b"double" => {
-                                current_values.push(String::new());
-                            }
-                            _ => {}
-                        }
-                    }
-                }
-                Ok(Event::Eof) => break,
-                Err(e) => {
-                    return Err(AppError::XmlRpcError(format!("XML parse error: {}", e)));
-                }
-                _ => {}
+        for row in Self::multicall_rows(xml)? {
+            if row.len() < Self::TORRENT_FIELDS.len() {
+                continue;
             }
-            buf.clear();
+            let hash = row[0].as_str().unwrap_or_default();
+            if !seen.insert(hash.to_string()) {
+                duplicates.push(hash.to_string());
+                continue;
+            }
+            
+            let int = |i: usize| row[i].as_i64().unwrap_or(0);
+            let flag = |i: usize| row[i].as_bool().unwrap_or(false);
+            let text = |i: usize| row[i].as_str().unwrap_or_default().to_string();
+            
+            let is_active = flag(6);
+            let is_open = flag(7);
+            let is_hashing = flag(8);
+            let complete = flag(9);
+            let message = text(10);
+            let finished_at = match int(18) {
+                0 => text(19).trim().parse().unwrap_or(0),
+                finished_at => finished_at,
+            };
+            
+            let state = if is_hashing {
+                TorrentState::Hashing
+            } else if is_error_message(&message) {
+                TorrentState::Error
+            } else if !is_open {
+                TorrentState::Stopped
+            } else if !is_active {
+                TorrentState::Paused
+            } else if complete {
+                TorrentState::Seeding
+            } else {
+                TorrentState::Downloading
+            };
+            
+            torrents.push(Torrent {
+                hash: text(0),
+                name: text(1),
+                size_bytes: int(2),
+                completed_bytes: int(3),
+                down_rate: int(4),
+                avg_down_rate: int(4),
+                up_rate: int(5),
+                is_active,
+                is_open,
+                is_hashing,
+                complete,
+                message,
+                ratio: int(11) as f64 / 1000.0,
+                load_date: int(12),
+                is_forced: flag(13),
+                bytes_uploaded: int(14),
+                bytes_downloaded: int(15),
+                done_seeding: text(16) == DONE_SEEDING,
+                auto_stopped: parse_auto_stopped(&text(16)),
+                label: text(17).trim().to_string(),
+                is_private: flag(20),
+                completed_chunks: int(21),
+                size_chunks: int(22),
+                wanted_chunks: int(23),
+                chunks_hashed: int(24),
+                peers_connected: int(25),
+                peers_complete: int(26),
+                progress_mode: self.progress_mode,
+                rate_unit: self.rate_unit,
+                seeding_time: (complete && finished_at > 0).then(|| (now - finished_at).max(0)),
+                idle_time: None,
+                chunk_rate: None,
+                state,
+            });
         }
         
+        self.report_duplicates(duplicates);
         tracing::trace!("Parsed {} torrents", torrents.len());
         
         Ok(torrents)
     }
     
+    /// Warn about each duplicated hash the first time it shows up; later
+    /// repeats only go to debug. Returns the hashes warned about now.
+    fn report_duplicates(&self, duplicates: Vec<String>) -> Vec<String> {
+        let mut warned = self.warned_duplicates.lock().unwrap();
+        let (new, repeated): (Vec<_>, Vec<_>) = duplicates
+            .into_iter()
+            .partition(|hash| warned.insert(hash.clone()));
+        
+        if !new.is_empty() {
+            tracing::warn!("rTorrent listed these hashes more than once, keeping the first: {}", new.join(", "));
+        }
+        if !repeated.is_empty() {
+            tracing::debug!("rTorrent still lists these hashes more than once: {}", repeated.join(", "));
+        }
+        new
+    }
+    
+    /// Rows of a `*.multicall` response: an array holding one array of field
+    /// values per item, in the order the fields were requested
+    fn multicall_rows(xml: &str) -> Result<Vec<Vec<Value>>> {
+        let rows = xmlrpc::parse_response(xml)?
+            .into_array()
+            .ok_or_else(|| AppError::XmlRpcError("multicall response is not an array".to_string()))?;
+        Ok(rows.into_iter().filter_map(Value::into_array).collect())
+    }
+    
     pub async fn get_global_stats(&self) -> Result<GlobalStats> {
-        // Speed rates are calculated from torrent data in the caller (state.rs poller)
-        let down_rate = 0i64;
-        let up_rate = 0i64;
+        // Rates and peer counts are summed from torrent data by the caller
+        // (GlobalStats::add_torrent_totals)
         
         // Get default directory to check free space
         let dir_xml = Self::build_simple_xml("directory.default");
@@ -484,41 +1367,166 @@ impl RtorrentClient {
         let disk_response = self.send_request(&disk_xml).await?;
         let free_disk_space = self.parse_int_response(&disk_response).unwrap_or(0);
         
-        // Count active peers (simplified)
-        let active_peers = 0i64;
-        
         Ok(GlobalStats {
-            down_rate,
-            up_rate,
             free_disk_space,
-            active_peers,
+            ..GlobalStats::empty(self.rate_unit)
         })
     }
     
     fn parse_int_response(&self, xml: &str) -> Option<i64> {
-        let mut reader = Reader::from_str(xml);
-        reader.config_mut().trim_text(true);
-        let mut buf = Vec::new();
-        let mut in_value = false;
+        xmlrpc::parse_response(xml).ok()?.as_i64()
+    }
+    
+    pub async fn get_transfer_limits(&self) -> Result<TransferLimits> {
+        let xml = Self::build_simple_xml("throttle.max_downloads.global");
+        let response = self.send_request(&xml).await?;
+        let max_downloads = self.parse_int_response(&response).unwrap_or(0);
         
-        loop {
-            match reader.read_event_into(&mut buf) {
-                Ok(Event::Start(e)) => {
-                    match e.name().as_ref() {
-                        b"i4" | b"i8" | b"int" => in_value = true,
-                        _ => {}
-                    }
-                }
-                Ok(Event::Text(e)) if in_value => {
-                    return e.unescape().ok()?.parse().ok();
-                }
-                Ok(Event::Eof) => break,
-                Err(_) => break,
-                _ => {}
+        let xml = Self::build_simple_xml("throttle.max_uploads.global");
+        let response = self.send_request(&xml).await?;
+        let max_uploads = self.parse_int_response(&response).unwrap_or(0);
+        
+        Ok(TransferLimits { max_downloads, max_uploads })
+    }
+    
+    pub async fn set_transfer_limits(&self, limits: TransferLimits) -> Result<()> {
+        for (method, value) in [
+            ("throttle.max_downloads.global.set", limits.max_downloads),
+            ("throttle.max_uploads.global.set", limits.max_uploads),
+        ] {
+            // Global setters still take an (empty) target as their first argument
+            let xml = Self::build_call_xml(method, &[XmlRpcParam::Str(""), XmlRpcParam::Int(value)])?;
+            let response = self.send_request(&xml).await?;
+            if let Some(fault) = parse_fault(&response) {
+                return Err(AppError::XmlRpcError(fault));
             }
-            buf.clear();
         }
-        None
+        tracing::info!(
+            "Set global limits: {} downloads, {} uploads",
+            limits.max_downloads, limits.max_uploads
+        );
+        Ok(())
+    }
+    
+    pub async fn get_peer_discovery(&self) -> Result<PeerDiscovery> {
+        // There is no `dht.mode` getter; the statistics struct carries the mode
+        let xml = Self::build_simple_xml("dht.statistics");
+        let response = self.send_request(&xml).await?;
+        let dht_mode = xmlrpc::parse_response(&response)?
+            .get("dht")
+            .and_then(Value::as_str)
+            .ok_or_else(|| AppError::XmlRpcError("dht.statistics has no mode".to_string()))?
+            .to_string();
+        
+        let xml = Self::build_simple_xml("protocol.pex");
+        let response = self.send_request(&xml).await?;
+        let pex = xmlrpc::parse_response(&response)?
+            .as_bool()
+            .ok_or_else(|| AppError::XmlRpcError("Failed to parse protocol.pex".to_string()))?;
+        
+        Ok(PeerDiscovery { dht_mode, pex })
+    }
+    
+    /// Turn DHT on (`auto`, so it only runs while public torrents are active) or
+    /// disable it outright
+    pub async fn set_dht(&self, enabled: bool) -> Result<()> {
+        let mode = if enabled { "auto" } else { "disable" };
+        let xml = Self::build_call_xml("dht.mode.set", &[XmlRpcParam::Str(""), XmlRpcParam::Str(mode)])?;
+        let response = self.send_request(&xml).await?;
+        if let Some(fault) = parse_fault(&response) {
+            return Err(AppError::XmlRpcError(fault));
+        }
+        tracing::info!("Set DHT mode to {}", mode);
+        Ok(())
+    }
+    
+    pub async fn set_pex(&self, enabled: bool) -> Result<()> {
+        let xml = Self::build_call_xml(
+            "protocol.pex.set",
+            &[XmlRpcParam::Str(""), XmlRpcParam::Int(enabled as i64)],
+        )?;
+        let response = self.send_request(&xml).await?;
+        if let Some(fault) = parse_fault(&response) {
+            return Err(AppError::XmlRpcError(fault));
+        }
+        tracing::info!("Set peer exchange {}", if enabled { "on" } else { "off" });
+        Ok(())
+    }
+    
+    /// Listening port settings. Whether the port is reachable from outside
+    /// (forwarded) is something only a peer or a port checker can tell.
+    pub async fn get_network_info(&self) -> Result<NetworkInfo> {
+        let xml = Self::build_simple_xml("network.listen.port");
+        let response = self.send_request(&xml).await?;
+        let listen_port = self.parse_int_response(&response)
+            .ok_or_else(|| AppError::XmlRpcError("Failed to parse network.listen.port".to_string()))?;
+        
+        let xml = Self::build_simple_xml("network.listen.backlog");
+        let response = self.send_request(&xml).await?;
+        let listen_backlog = self.parse_int_response(&response).unwrap_or(0);
+        
+        let xml = Self::build_simple_xml("network.port_open");
+        let response = self.send_request(&xml).await?;
+        let port_open = self.parse_int_response(&response).map(|open| open != 0);
+        
+        Ok(NetworkInfo { listen_port, listen_backlog, port_open })
+    }
+    
+    pub async fn get_session_info(&self) -> Result<SessionInfo> {
+        let xml = Self::build_simple_xml("session.path");
+        let response = self.send_request(&xml).await?;
+        let path = xmlrpc::parse_response(&response)?
+            .into_string()
+            .ok_or_else(|| AppError::XmlRpcError("Failed to parse session.path".to_string()))?;
+        
+        let xml = Self::build_simple_xml("session.on_completion");
+        let response = self.send_request(&xml).await?;
+        let on_completion = self.parse_int_response(&response).is_some_and(|v| v != 0);
+        
+        Ok(SessionInfo { path, on_completion })
+    }
+    
+    pub async fn set_session_on_completion(&self, enabled: bool) -> Result<()> {
+        let xml = Self::build_call_xml(
+            "session.on_completion.set",
+            &[XmlRpcParam::Str(""), XmlRpcParam::Int(enabled as i64)],
+        )?;
+        let response = self.send_request(&xml).await?;
+        if let Some(fault) = parse_fault(&response) {
+            return Err(AppError::XmlRpcError(fault));
+        }
+        tracing::info!("Set session save on completion {}", if enabled { "on" } else { "off" });
+        Ok(())
+    }
+    
+    /// Save the session every `secs` seconds from now on. rTorrent has no
+    /// getter for schedules, so the interval can be set but not read back.
+    pub async fn set_session_save_interval(&self, secs: u64) -> Result<()> {
+        let secs = secs.to_string();
+        let xml = Self::build_call_xml("schedule2", &[
+            XmlRpcParam::Str(""),
+            XmlRpcParam::Str(SESSION_SAVE_SCHEDULE),
+            XmlRpcParam::Str(&secs),
+            XmlRpcParam::Str(&secs),
+            XmlRpcParam::Str("session.save="),
+        ])?;
+        let response = self.send_request(&xml).await?;
+        if let Some(fault) = parse_fault(&response) {
+            return Err(AppError::XmlRpcError(fault));
+        }
+        tracing::info!("Set session save interval to {}s", secs);
+        Ok(())
+    }
+    
+    /// Write every torrent's state to the session directory now
+    pub async fn save_session(&self) -> Result<()> {
+        let xml = Self::build_simple_xml("session.save");
+        let response = self.send_request(&xml).await?;
+        if let Some(fault) = parse_fault(&response) {
+            return Err(AppError::XmlRpcError(fault));
+        }
+        tracing::info!("Saved rTorrent session");
+        Ok(())
     }
     
     pub async fn get_client_version(&self) -> Result<String> {
@@ -528,93 +1536,632 @@ impl RtorrentClient {
             .ok_or_else(|| AppError::XmlRpcError("Failed to parse version".to_string()))
     }
 
+    /// Every method the connected rTorrent exposes, as `system.listMethods`
+    /// lists them
+    pub async fn list_methods(&self) -> Result<Vec<String>> {
+        let xml = Self::build_simple_xml("system.listMethods");
+        let response = self.send_request(&xml).await?;
+        if let Some(fault) = parse_fault(&response) {
+            return Err(AppError::XmlRpcError(fault));
+        }
+        Self::parse_string_values(&response)
+    }
+    
+    /// `system.methodHelp` and `system.methodSignature` for `name`
+    pub async fn method_help(&self, name: &str) -> Result<MethodHelp> {
+        let xml = Self::build_call_xml("system.methodHelp", &[XmlRpcParam::Str(name)])?;
+        let response = self.send_request(&xml).await?;
+        if let Some(fault) = parse_fault(&response) {
+            return Err(AppError::XmlRpcError(fault));
+        }
+        let help = self.parse_string_response(&response).unwrap_or_default();
+        
+        let xml = Self::build_call_xml("system.methodSignature", &[XmlRpcParam::Str(name)])?;
+        let response = self.send_request(&xml).await?;
+        if let Some(fault) = parse_fault(&response) {
+            return Err(AppError::XmlRpcError(fault));
+        }
+        // An array of type lists, or the string "undef"
+        let signatures = xmlrpc::parse_response(&response)?
+            .into_array()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(Value::into_array)
+            .map(|types| types.into_iter().filter_map(Value::into_string).collect())
+            .collect();
+        
+        Ok(MethodHelp { name: name.to_string(), help, signatures })
+    }
+    
+    /// Required methods missing from `available`, a [`Self::list_methods`] result.
+    ///
+    /// Older releases lack some of the commands VibeTorrent calls, which
+    /// otherwise shows up as a silently empty torrent list.
+    pub fn missing_from(available: &[String]) -> Vec<&'static str> {
+        let available: HashSet<&str> = available.iter().map(String::as_str).collect();
+        
+        let mut missing: Vec<&'static str> = REQUIRED_METHODS
+            .iter()
+            .copied()
+            .filter(|method| !available.contains(method))
+            .collect();
+        if !available.contains("d.multicall2") && !available.contains("d.multicall") {
+            missing.insert(0, "d.multicall2");
+        }
+        missing
+    }
+    
+    /// Features turned off because `available` lacks their
+    /// [`OPTIONAL_METHODS`] entry
+    pub fn unsupported_features(available: &[String]) -> Vec<&'static str> {
+        OPTIONAL_METHODS
+            .iter()
+            .filter(|(method, _)| !available.iter().any(|name| name == method))
+            .map(|(_, feature)| *feature)
+            .collect()
+    }
+    
+    /// The strings of an array response, such as `system.listMethods`
+    fn parse_string_values(xml: &str) -> Result<Vec<String>> {
+        let values = xmlrpc::parse_response(xml)?
+            .into_array()
+            .ok_or_else(|| AppError::XmlRpcError("response is not an array".to_string()))?;
+        Ok(values.into_iter().filter_map(Value::into_string).collect())
+    }
+
     fn parse_string_response(&self, xml: &str) -> Option<String> {
-        let mut reader = Reader::from_str(xml);
-        reader.config_mut().trim_text(true);
-        let mut buf = Vec::new();
-        let mut in_string = false;
+        xmlrpc::parse_response(xml).ok()?.into_string()
+    }
 
-        loop {
-            match reader.read_event_into(&mut buf) {
-                Ok(Event::Start(e)) => {
-                    if e.name().as_ref() == b"string" {
-                        in_string = true;
-                    }
-                }
-                Ok(Event::Text(e)) if in_string => {
-                    return e.unescape().ok().map(|s| s.to_string());
-                }
-                Ok(Event::Eof) => break,
-                Err(_) => break,
-                _ => {}
-            }
-            buf.clear();
+    /// `d.open`: open the torrent's files without starting it.
+    ///
+    /// rTorrent keeps two flags per torrent: open (files allocated, the torrent
+    /// is known to the session) and started (actively transferring). An open but
+    /// not started torrent reads as "Paused", a closed one as "Stopped".
+    pub async fn open_torrent(&self, hash: &str) -> Result<()> {
+        self.send_hash_command("d.open", hash).await
+    }
+    
+    /// `d.start`: mark the torrent started, so it transfers while it is open.
+    /// The scheduler may still hold it back unless it was force-started.
+    pub async fn start_torrent(&self, hash: &str) -> Result<()> {
+        self.send_hash_command("d.start", hash).await
+    }
+    
+    /// `d.stop`: stop transferring but leave the files open, so a later start
+    /// needs no re-check
+    pub async fn stop_torrent(&self, hash: &str) -> Result<()> {
+        self.send_hash_command("d.stop", hash).await
+    }
+    
+    /// `d.close`: close the torrent's files, releasing them for other programs.
+    /// Stop it first; a closed torrent has to be opened again before it starts.
+    pub async fn close_torrent(&self, hash: &str) -> Result<()> {
+        self.send_hash_command("d.close", hash).await
+    }
+    
+    async fn send_hash_command(&self, method: &str, hash: &str) -> Result<()> {
+        let xml = Self::build_single_param_xml(method, hash);
+        let response = self.send_request(&xml).await?;
+        match parse_fault(&response) {
+            Some(fault) => Err(AppError::XmlRpcError(fault)),
+            None => Ok(()),
         }
-        None
     }
-
+    
+    /// Stop transferring but keep the torrent open, so it resumes without a
+    /// re-check and reads as "Paused" rather than "Stopped"
     pub async fn pause_torrent(&self, hash: &str) -> Result<()> {
-        let xml = Self::build_single_param_xml("d.stop", hash);
-        self.send_request(&xml).await?;
-        let xml = Self::build_single_param_xml("d.close", hash);
-        self.send_request(&xml).await?;
+        // A paused torrent goes back under the scheduler when resumed
+        self.set_ignore_commands(hash, false).await?;
+        self.stop_torrent(hash).await
+    }
+    
+    /// Pause and close the torrent's files
+    pub async fn stop_and_close(&self, hash: &str) -> Result<()> {
+        self.pause_torrent(hash).await?;
+        self.close_torrent(hash).await
+    }
+    
+    /// Open and start in one go, the row's Resume.
+    ///
+    /// With `clear_stop_tag` (see [`Torrent::has_stop_tag`]) a "done seeding" or
+    /// auto-stopped torrent becomes an ordinary one again; otherwise the extra
+    /// `d.custom.set` is skipped.
+    pub async fn resume_torrent(&self, hash: &str, clear_stop_tag: bool) -> Result<()> {
+        if clear_stop_tag {
+            self.set_custom(hash, STATE_KEY, "").await?;
+        }
+        self.open_torrent(hash).await?;
+        self.start_torrent(hash).await
+    }
+    
+    /// Hash-check a torrent's data already on disk, then start it.
+    ///
+    /// rTorrent runs the check before the start takes effect, so a torrent
+    /// added over complete data seeds it instead of downloading it again.
+    pub async fn check_and_start(&self, hash: &str) -> Result<()> {
+        let xml = Self::build_single_param_xml("d.check_hash", hash);
+        let response = self.send_request(&xml).await?;
+        if let Some(fault) = parse_fault(&response) {
+            return Err(AppError::XmlRpcError(fault));
+        }
+        self.resume_torrent(hash, false).await
+    }
+    
+    /// Start a torrent regardless of queue and global active-download limits.
+    ///
+    /// `d.ignore_commands` makes rTorrent's scheduler and ratio groups skip the
+    /// torrent, so it isn't stopped again on the next scheduling pass.
+    /// `clear_stop_tag` is passed on to [`Self::resume_torrent`].
+    pub async fn force_start(&self, hash: &str, clear_stop_tag: bool) -> Result<()> {
+        self.set_ignore_commands(hash, true).await?;
+        self.resume_torrent(hash, clear_stop_tag).await
+    }
+    
+    /// Stop a finished torrent for good but keep it (and its data) in the list.
+    ///
+    /// Unlike a pause this is tagged in `d.custom`, so the UI can tell "done
+    /// seeding" apart from a download paused halfway.
+    pub async fn stop_seeding(&self, hash: &str) -> Result<()> {
+        self.stop_and_close(hash).await?;
+        self.set_custom(hash, STATE_KEY, DONE_SEEDING).await
+    }
+    
+    /// Stop a torrent on behalf of automation (a ratio or idle rule, a script),
+    /// tagged with `reason` so it reads as "Auto-stopped" rather than a stop by
+    /// hand. A manual resume clears the tag.
+    pub async fn auto_stop(&self, hash: &str, reason: &str) -> Result<()> {
+        self.stop_and_close(hash).await?;
+        let value = if reason.is_empty() {
+            AUTO_STOPPED.to_string()
+        } else {
+            format!("{}:{}", AUTO_STOPPED, reason)
+        };
+        self.set_custom(hash, STATE_KEY, &value).await
+    }
+    
+    /// Give an errored torrent a fresh start: clear `d.message`, close and reopen
+    /// it (storage errors are re-checked on open), start it and announce right
+    /// away. If the cause persists, rTorrent sets the message again.
+    pub async fn retry_torrent(&self, hash: &str) -> Result<()> {
+        self.stop_and_close(hash).await?;
+        let xml = Self::build_call_xml("d.message.set", &[XmlRpcParam::Str(hash), XmlRpcParam::Str("")])?;
+        let response = self.send_request(&xml).await?;
+        if let Some(fault) = parse_fault(&response) {
+            return Err(AppError::XmlRpcError(fault));
+        }
+        self.resume_torrent(hash, false).await?;
+        
+        // Starting announces too, but only once the scheduler gets to it
+        let xml = Self::build_single_param_xml("d.tracker_announce", hash);
+        let response = self.send_request(&xml).await?;
+        if let Some(fault) = parse_fault(&response) {
+            tracing::warn!("retry_torrent: announce failed for {}: {}", hash, fault);
+        }
         Ok(())
     }
     
-    pub async fn resume_torrent(&self, hash: &str) -> Result<()> {
-        let xml = Self::build_single_param_xml("d.open", hash);
-        self.send_request(&xml).await?;
-        let xml = Self::build_single_param_xml("d.start", hash);
+    /// Set the torrent's label (`d.custom1`); an empty label clears it
+    pub async fn set_label(&self, hash: &str, label: &str) -> Result<()> {
+        let xml = Self::build_call_xml(
+            "d.custom1.set",
+            &[XmlRpcParam::Str(hash), XmlRpcParam::Str(label)],
+        )?;
+        let response = self.send_request(&xml).await?;
+        match parse_fault(&response) {
+            Some(fault) => Err(AppError::XmlRpcError(fault)),
+            None => Ok(()),
+        }
+    }
+    
+    async fn set_custom(&self, hash: &str, key: &str, value: &str) -> Result<()> {
+        let xml = Self::build_call_xml(
+            "d.custom.set",
+            &[XmlRpcParam::Str(hash), XmlRpcParam::Str(key), XmlRpcParam::Str(value)],
+        )?;
+        let response = self.send_request(&xml).await?;
+        match parse_fault(&response) {
+            Some(fault) => Err(AppError::XmlRpcError(fault)),
+            None => Ok(()),
+        }
+    }
+    
+    async fn set_ignore_commands(&self, hash: &str, ignore: bool) -> Result<()> {
+        let xml = Self::build_call_xml(
+            "d.ignore_commands.set",
+            &[XmlRpcParam::Str(hash), XmlRpcParam::Int(ignore as i64)],
+        )?;
         self.send_request(&xml).await?;
         Ok(())
     }
     
-    pub async fn remove_torrent(&self, hash: &str) -> Result<()> {
-        let xml = Self::build_single_param_xml("d.erase", hash);
-        self.send_request(&xml).await?;
+    /// Path of the torrent's data: the file itself for single-file torrents,
+    /// otherwise its directory. Empty until the torrent has been opened.
+    pub async fn get_base_path(&self, hash: &str) -> Result<String> {
+        let xml = Self::build_call_xml("d.base_path", &[XmlRpcParam::Str(hash)])?;
+        let response = self.send_request(&xml).await?;
+        if let Some(fault) = parse_fault(&response) {
+            return Err(AppError::XmlRpcError(fault));
+        }
+        Ok(self.parse_string_response(&response).unwrap_or_default())
+    }
+    
+    /// Where the torrent's data lives, with what's needed to check it is safe
+    /// to delete; fetched in one `system.multicall`
+    pub async fn get_data_location(&self, hash: &str) -> Result<DataLocation> {
+        let target = [XmlRpcParam::Str(hash)];
+        let xml = Self::build_system_multicall_xml(&[
+            ("d.base_path", &target),
+            ("d.directory", &target),
+            ("d.name", &target),
+            ("d.is_multi_file", &target),
+            ("directory.default", &[]),
+        ]);
+        let response = self.send_request(&xml).await?;
+        let values = Self::parse_system_multicall(&response)?;
+        let [base_path, directory, name, is_multi_file, default_directory] = values.as_slice() else {
+            return Err(AppError::XmlRpcError("system.multicall returned too few results".to_string()));
+        };
+        
+        Ok(DataLocation {
+            base_path: PathBuf::from(base_path.as_str().unwrap_or_default()),
+            directory: PathBuf::from(directory.as_str().unwrap_or_default()),
+            name: name.as_str().unwrap_or_default().to_string(),
+            is_multi_file: is_multi_file.as_bool().unwrap_or(false),
+            default_directory: PathBuf::from(default_directory.as_str().unwrap_or_default()),
+        })
+    }
+    
+    /// The torrent's download directory (`d.directory`)
+    pub async fn get_directory(&self, hash: &str) -> Result<String> {
+        let xml = Self::build_call_xml("d.directory", &[XmlRpcParam::Str(hash)])?;
+        let response = self.send_request(&xml).await?;
+        if let Some(fault) = parse_fault(&response) {
+            return Err(AppError::XmlRpcError(fault));
+        }
+        Ok(self.parse_string_response(&response).unwrap_or_default())
+    }
+
+    /// The `.torrent` file the torrent was loaded from (`d.tied_to_file`); empty
+    /// for magnets and torrents whose tie was lost
+    pub async fn get_tied_file(&self, hash: &str) -> Result<String> {
+        let xml = Self::build_call_xml("d.tied_to_file", &[XmlRpcParam::Str(hash)])?;
+        let response = self.send_request(&xml).await?;
+        if let Some(fault) = parse_fault(&response) {
+            return Err(AppError::XmlRpcError(fault));
+        }
+        Ok(self.parse_string_response(&response).unwrap_or_default())
+    }
+    
+    pub async fn set_tied_file(&self, hash: &str, path: &str) -> Result<()> {
+        let xml = Self::build_call_xml("d.tied_to_file.set", &[XmlRpcParam::Str(hash), XmlRpcParam::Str(path)])?;
+        let response = self.send_request(&xml).await?;
+        match parse_fault(&response) {
+            Some(fault) => Err(AppError::XmlRpcError(fault)),
+            None => Ok(()),
+        }
+    }
+
+    /// Point the torrent at another download directory without moving anything.
+    ///
+    /// rTorrent only changes the directory of a closed torrent, so it's stopped
+    /// first and started again if it was running. Data already downloaded stays
+    /// where it is and is no longer seen by rTorrent.
+    pub async fn set_directory(&self, hash: &str, dir: &str) -> Result<()> {
+        let xml = Self::build_call_xml("d.state", &[XmlRpcParam::Str(hash)])?;
+        let response = self.send_request(&xml).await?;
+        let was_started = xmlrpc::parse_response(&response)?.as_i64().unwrap_or(0) == 1;
+
+        self.stop_and_close(hash).await?;
+        let xml = Self::build_call_xml("d.directory.set", &[XmlRpcParam::Str(hash), XmlRpcParam::Str(dir)])?;
+        let response = self.send_request(&xml).await?;
+        if let Some(fault) = parse_fault(&response) {
+            return Err(AppError::XmlRpcError(fault));
+        }
+        if was_started {
+            self.resume_torrent(hash, false).await?;
+        }
         Ok(())
     }
+
+    pub async fn remove_torrent(&self, hash: &str) -> Result<()> {
+        self.send_hash_command("d.erase", hash).await
+    }
+    
+    pub async fn get_trackers(&self, hash: &str) -> Result<Vec<Tracker>> {
+        let xml = Self::build_call_xml(
+            "t.multicall",
+            &[
+                XmlRpcParam::Str(hash),
+                XmlRpcParam::Str(""),
+                XmlRpcParam::Str("t.url="),
+                XmlRpcParam::Str("t.is_enabled="),
+            ],
+        )?;
+        let response = self.send_request(&xml).await?;
+        
+        Ok(Self::multicall_rows(&response)?
+            .into_iter()
+            .enumerate()
+            .filter(|(_, values)| !values.is_empty())
+            .map(|(index, values)| Tracker {
+                index,
+                url: values[0].as_str().unwrap_or_default().to_string(),
+                is_enabled: values.get(1).and_then(Value::as_bool).unwrap_or(true),
+            })
+            .collect())
+    }
     
+    pub async fn get_files(&self, hash: &str) -> Result<Vec<TorrentFile>> {
+        let xml = Self::build_call_xml(
+            "f.multicall",
+            &[
+                XmlRpcParam::Str(hash),
+                XmlRpcParam::Str(""),
+                XmlRpcParam::Str("f.path="),
+                XmlRpcParam::Str("f.size_bytes="),
+                XmlRpcParam::Str("f.completed_chunks="),
+                XmlRpcParam::Str("f.size_chunks="),
+                XmlRpcParam::Str("f.priority="),
+            ],
+        )?;
+        let response = self.send_request(&xml).await?;
+        if let Some(fault) = parse_fault(&response) {
+            return Err(AppError::XmlRpcError(fault));
+        }
+        
+        let int = |values: &[Value], i: usize| values.get(i).and_then(Value::as_i64).unwrap_or(0);
+        Ok(Self::multicall_rows(&response)?
+            .into_iter()
+            .enumerate()
+            .filter(|(_, values)| !values.is_empty())
+            .map(|(index, values)| TorrentFile {
+                index,
+                path: values[0].as_str().unwrap_or_default().to_string(),
+                size_bytes: int(&values, 1),
+                completed_chunks: int(&values, 2),
+                size_chunks: int(&values, 3),
+                priority: int(&values, 4),
+            })
+            .collect())
+    }
+    
+    pub async fn get_peers(&self, hash: &str) -> Result<Vec<Peer>> {
+        let xml = Self::build_call_xml(
+            "p.multicall",
+            &[
+                XmlRpcParam::Str(hash),
+                XmlRpcParam::Str(""),
+                XmlRpcParam::Str("p.address="),
+                XmlRpcParam::Str("p.port="),
+                XmlRpcParam::Str("p.client_version="),
+                XmlRpcParam::Str("p.completed_percent="),
+                XmlRpcParam::Str("p.down_rate="),
+                XmlRpcParam::Str("p.up_rate="),
+                XmlRpcParam::Str("p.is_encrypted="),
+                XmlRpcParam::Str("p.is_incoming="),
+            ],
+        )?;
+        let response = self.send_request(&xml).await?;
+        if let Some(fault) = parse_fault(&response) {
+            return Err(AppError::XmlRpcError(fault));
+        }
+        
+        let int = |values: &[Value], i: usize| values.get(i).and_then(Value::as_i64).unwrap_or(0);
+        let flag = |values: &[Value], i: usize| values.get(i).and_then(Value::as_bool).unwrap_or(false);
+        Ok(Self::multicall_rows(&response)?
+            .into_iter()
+            .filter(|values| !values.is_empty())
+            .map(|values| Peer {
+                address: values[0].as_str().unwrap_or_default().to_string(),
+                port: int(&values, 1),
+                client: values.get(2).and_then(Value::as_str).unwrap_or_default().to_string(),
+                completed_percent: int(&values, 3),
+                down_rate: int(&values, 4),
+                up_rate: int(&values, 5),
+                is_encrypted: flag(&values, 6),
+                is_incoming: flag(&values, 7),
+            })
+            .collect())
+    }
+    
+    /// Per-tracker scrape counts for a torrent
+    pub async fn get_tracker_scrape(&self, hash: &str) -> Result<Vec<TrackerScrape>> {
+        let xml = Self::build_call_xml(
+            "t.multicall",
+            &[
+                XmlRpcParam::Str(hash),
+                XmlRpcParam::Str(""),
+                XmlRpcParam::Str("t.url="),
+                XmlRpcParam::Str("t.is_enabled="),
+                XmlRpcParam::Str("t.scrape_complete="),
+                XmlRpcParam::Str("t.scrape_incomplete="),
+                XmlRpcParam::Str("t.scrape_time_last="),
+            ],
+        )?;
+        let response = self.send_request(&xml).await?;
+        if let Some(fault) = parse_fault(&response) {
+            return Err(AppError::XmlRpcError(fault));
+        }
+        
+        let int = |values: &[Value], i: usize| values.get(i).and_then(Value::as_i64).unwrap_or(0);
+        Ok(Self::multicall_rows(&response)?
+            .into_iter()
+            .filter(|values| !values.is_empty())
+            .map(|values| TrackerScrape {
+                url: values[0].as_str().unwrap_or_default().to_string(),
+                is_enabled: values.get(1).and_then(Value::as_bool).unwrap_or(true),
+                seeders: int(&values, 2),
+                leechers: int(&values, 3),
+                last_scrape: int(&values, 4),
+            })
+            .collect())
+    }
+    
+    /// Ask rTorrent to scrape the torrent's trackers now.
+    ///
+    /// The answer arrives asynchronously; re-read with [`Self::get_tracker_scrape`].
+    /// Returns `false` on builds without `d.tracker.send_scrape`.
+    pub async fn send_scrape(&self, hash: &str) -> Result<bool> {
+        let xml = Self::build_call_xml(
+            "d.tracker.send_scrape",
+            &[XmlRpcParam::Str(hash), XmlRpcParam::Int(0)],
+        )?;
+        let response = self.send_request(&xml).await?;
+        match parse_fault(&response) {
+            Some(fault) if is_method_missing_fault(&fault) => Ok(false),
+            Some(fault) => Err(AppError::XmlRpcError(fault)),
+            None => Ok(true),
+        }
+    }
+    
+    /// Add an announce URL to the torrent's first tier
+    pub async fn add_tracker(&self, hash: &str, url: &str) -> Result<()> {
+        let xml = Self::build_call_xml(
+            "d.tracker.insert",
+            &[XmlRpcParam::Str(hash), XmlRpcParam::Int(0), XmlRpcParam::Str(url)],
+        )?;
+        let response = self.send_request(&xml).await?;
+        match parse_fault(&response) {
+            Some(fault) => Err(AppError::XmlRpcError(fault)),
+            None => Ok(()),
+        }
+    }
+    
+    /// Take the tracker at `index` out of use.
+    ///
+    /// rTorrent can't delete trackers from a loaded torrent, so it is disabled:
+    /// no longer announced to, but still listed until the torrent is reloaded.
+    pub async fn remove_tracker(&self, hash: &str, index: usize) -> Result<()> {
+        let target = format!("{}:t{}", hash, index);
+        let xml = Self::build_call_xml("t.disable", &[XmlRpcParam::Str(&target)])?;
+        let response = self.send_request(&xml).await?;
+        match parse_fault(&response) {
+            Some(fault) => Err(AppError::XmlRpcError(fault)),
+            None => Ok(()),
+        }
+    }
+    
+    /// Put a tracker taken out of use by [`Self::remove_tracker`] back in use
+    pub async fn enable_tracker(&self, hash: &str, index: usize) -> Result<()> {
+        let target = format!("{}:t{}", hash, index);
+        let xml = Self::build_call_xml("t.enable", &[XmlRpcParam::Str(&target)])?;
+        let response = self.send_request(&xml).await?;
+        match parse_fault(&response) {
+            Some(fault) => Err(AppError::XmlRpcError(fault)),
+            None => Ok(()),
+        }
+    }
+    
+    /// Swap a tracker URL on a torrent.
+    ///
+    /// Returns `false` if the torrent has no tracker with `old_url`. See
+    /// [`Self::replace_trackers`] for what replacing does.
+    pub async fn replace_tracker(&self, hash: &str, old_url: &str, new_url: &str) -> Result<bool> {
+        let trackers = self.get_trackers(hash).await?;
+        self.replace_trackers(hash, &trackers, |t| t.url == old_url, new_url).await
+    }
+    
+    /// Replace every tracker in `trackers` matching `old` with `new_url`.
+    ///
+    /// `trackers` is the torrent's current list, so callers that already fetched
+    /// it don't pay for a second round-trip. Replacing is disable + insert: the
+    /// old trackers are disabled (see [`Self::remove_tracker`]) and `new_url` is
+    /// inserted into the first tier once, unless the torrent already has it.
+    /// Returns `false` if nothing matched.
+    pub async fn replace_trackers(
+        &self,
+        hash: &str,
+        trackers: &[Tracker],
+        old: impl Fn(&Tracker) -> bool,
+        new_url: &str,
+    ) -> Result<bool> {
+        let matching: Vec<&Tracker> = trackers.iter().filter(|t| old(t)).collect();
+        if matching.is_empty() {
+            return Ok(false);
+        }
+        
+        for tracker in &matching {
+            self.remove_tracker(hash, tracker.index).await?;
+        }
+        if !trackers.iter().any(|t| t.url == new_url) {
+            self.add_tracker(hash, new_url).await?;
+        }
+        
+        for tracker in matching {
+            tracing::info!("Replaced tracker {} with {} on {}", tracker.url, new_url, hash);
+        }
+        Ok(true)
+    }
+    
+    /// Add a torrent by magnet or `.torrent` URL.
+    ///
+    /// Connection and SCGI errors are retried with exponential backoff, up to
+    /// the configured attempts; an XML-RPC fault (rTorrent refused it) is not.
     pub async fn add_torrent_url(&self, url: &str) -> Result<()> {
+        self.add_torrent_url_with(url, &AddOptions::default()).await
+    }
+    
+    /// [`Self::add_torrent_url`], with a label, directory or paused start
+    /// applied by rTorrent as it loads the torrent. Unlike setting them
+    /// afterwards, this also works for `.torrent` URLs, whose hash isn't known
+    /// until rTorrent has fetched them.
+    pub async fn add_torrent_url_with(&self, url: &str, options: &AddOptions) -> Result<()> {
+        const FIRST_BACKOFF: std::time::Duration = std::time::Duration::from_millis(250);
+        
+        let mut backoff = FIRST_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            match self.load_url(url, options).await {
+                Err(e) if attempt < self.add_url_attempts && is_transient(&e) => {
+                    tracing::warn!(
+                        "Adding torrent URL failed (attempt {}/{}), retrying in {:?}: {}",
+                        attempt, self.add_url_attempts, backoff, e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+    
+    async fn load_url(&self, url: &str, options: &AddOptions) -> Result<()> {
         tracing::info!("Adding torrent from URL: {}", url);
-        // Escape XML special characters in the URL
-        let escaped_url = url
-            .replace('&', "&amp;")
-            .replace('<', "&lt;")
-            .replace('>', "&gt;")
-            .replace('"', "&quot;")
-            .replace('\'', "&apos;");
-        // load.start needs empty string as first param (for view), then the URL
-        let xml = format!(
-            r#"<?xml version="1.0"?>
-<methodCall>
-<methodName>load.start</methodName>
-<params>
-<param><value><string></string></value></param>
-<param><value><string>{}</string></value></param>
-</params>
-</methodCall>"#,
-            escaped_url
-        );
+        let method = if options.start_paused { "load.normal" } else { "load.start" };
+        let commands = options.load_commands();
+        // load.* needs empty string as first param (for view), then the URL,
+        // then commands to run on the new download
+        let mut params = vec![XmlRpcParam::Str(""), XmlRpcParam::Str(url)];
+        params.extend(commands.iter().map(|command| XmlRpcParam::Str(command)));
+        let xml = Self::build_call_xml(method, &params)?;
         let response = self.send_request(&xml).await?;
         tracing::trace!("add_torrent_url response length: {} bytes", response.len());
-        Ok(())
+        match parse_fault(&response) {
+            Some(fault) => Err(AppError::XmlRpcError(fault)),
+            None => Ok(()),
+        }
     }
     
-    pub async fn add_torrent_file(&self, data: &[u8]) -> Result<()> {
+    /// Add a .torrent from its contents; with `start` false it's loaded stopped
+    /// (`load.raw`), e.g. to be checked with [`Self::check_and_start`] first
+    pub async fn add_torrent_file(&self, data: &[u8], start: bool) -> Result<()> {
         tracing::info!("Adding torrent from file, size: {} bytes", data.len());
-        // For file uploads, we use load.raw_start with base64 encoded data
+        // For file uploads, we use load.raw(_start) with base64 encoded data
+        let method = if start { "load.raw_start" } else { "load.raw" };
         let encoder = base64_encode(data);
         let xml = format!(
             r#"<?xml version="1.0"?>
 <methodCall>
-<methodName>load.raw_start</methodName>
+<methodName>{}</methodName>
 <params>
 <param><value><string></string></value></param>
 <param><value><base64>{}</base64></value></param>
 </params>
 </methodCall>"#,
-            encoder
+            method, encoder
         );
         let response = self.send_request(&xml).await?;
         tracing::trace!("add_torrent_file response length: {} bytes", response.len());
@@ -622,6 +2169,90 @@ impl RtorrentClient {
     }
 }
 
+/// Make a socket path independent of the working directory it was used from.
+///
+/// Relative paths are anchored at the current directory once, at startup, and
+/// symlinks are followed. A path that can't be canonicalized yet (rTorrent not
+/// started, broken symlink) is kept as-is so the connect error can explain it.
+fn resolve_socket_path(socket_path: &str) -> PathBuf {
+    let path = Path::new(socket_path);
+    let absolute = if path.is_relative() {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    } else {
+        path.to_path_buf()
+    };
+    std::fs::canonicalize(&absolute).unwrap_or(absolute)
+}
+
+/// Turn a failed socket connect into a message saying what's actually wrong
+fn diagnose_socket(path: &Path, error: &std::io::Error) -> String {
+    if error.kind() == std::io::ErrorKind::PermissionDenied {
+        return "permission denied (check the socket's owner and mode)".to_string();
+    }
+    
+    match std::fs::symlink_metadata(path) {
+        Err(_) => "socket file not found (is rTorrent running with network.scgi.open_local?)".to_string(),
+        Ok(meta) if meta.file_type().is_symlink() => match std::fs::read_link(path) {
+            Ok(target) => format!("broken symlink to {}", target.display()),
+            Err(_) => "broken symlink".to_string(),
+        },
+        Ok(meta) if !meta.file_type().is_socket() => "path exists but is not a socket".to_string(),
+        Ok(_) => error.to_string(),
+    }
+}
+
+/// The `methodName` of an XML-RPC call, for logging
+pub(crate) fn xml_method_name(xml: &str) -> &str {
+    xml.split_once("<methodName>")
+        .and_then(|(_, rest)| rest.split_once("</methodName>"))
+        .map(|(name, _)| name)
+        .unwrap_or("?")
+}
+
+/// The `faultString` of an XML-RPC fault response, if `xml` is one
+fn parse_fault(xml: &str) -> Option<String> {
+    if !xml.contains("<fault>") {
+        return None;
+    }
+    match xmlrpc::parse_response(xml) {
+        Err(xmlrpc::Error::Fault(message)) => Some(message),
+        _ => Some("Unknown XML-RPC fault".to_string()),
+    }
+}
+
+/// Whether a `d.message` means the torrent is in trouble. Tracker status lines
+/// ("Tracker: [Timeout was reached]") are informational: the torrent keeps
+/// working through its other trackers, DHT and PEX.
+fn is_error_message(message: &str) -> bool {
+    !message.is_empty() && message != "0" && !message.starts_with("Tracker:")
+}
+
+/// `d.custom` key holding VibeTorrent's own per-torrent state
+const STATE_KEY: &str = "vt_state";
+/// [`STATE_KEY`] value for torrents stopped with "stop seeding"
+const DONE_SEEDING: &str = "done-seeding";
+/// [`STATE_KEY`] value for torrents stopped by automation, optionally followed
+/// by `:` and the reason
+const AUTO_STOPPED: &str = "auto-stopped";
+
+/// The reason stored with [`AUTO_STOPPED`], if that's what the state says
+fn parse_auto_stopped(state: &str) -> Option<String> {
+    let rest = state.strip_prefix(AUTO_STOPPED)?;
+    match rest.strip_prefix(':') {
+        Some(reason) => Some(reason.to_string()),
+        None if rest.is_empty() => Some(String::new()),
+        None => None,
+    }
+}
+
+/// xmlrpc-c reports unknown methods as "Method 'x' not defined" (fault -506)
+fn is_method_missing_fault(fault: &str) -> bool {
+    let fault = fault.to_ascii_lowercase();
+    fault.contains("not defined") || fault.contains("not found")
+}
+
 fn base64_encode(data: &[u8]) -> String {
     const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
     let mut result = String::new();
@@ -650,3 +2281,374 @@ fn base64_encode(data: &[u8]) -> String {
     
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRtorrent};
+
+    /// Text of each `<param>` in a method call, tags stripped
+    fn call_params(xml: &str) -> Vec<String> {
+        xml.split("<param>")
+            .skip(1)
+            .map(|param| {
+                let param = param.split("</param>").next().unwrap_or_default();
+                let mut text = String::new();
+                let mut in_tag = false;
+                for c in param.chars() {
+                    match c {
+                        '<' => in_tag = true,
+                        '>' => in_tag = false,
+                        c if !in_tag => text.push(c),
+                        _ => {}
+                    }
+                }
+                text
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn torrent_list_uses_multicall2_with_an_empty_target() {
+        let mock = MockRtorrent::start(|_| {
+            testing::torrents_response(&[testing::torrent_row("AAAA", "one")])
+        });
+        let client = RtorrentClient::new(mock.socket_path());
+
+        let torrents = client.get_torrents().await.unwrap();
+
+        assert_eq!(torrents.len(), 1);
+        assert_eq!(mock.methods(), ["d.multicall2"]);
+        let params = call_params(&mock.requests()[0]);
+        assert_eq!(params[..3], ["", "main", "d.hash="]);
+        assert_eq!(params.len(), RtorrentClient::TORRENT_FIELDS.len() + 2);
+    }
+
+    #[tokio::test]
+    async fn torrent_list_falls_back_to_legacy_multicall_and_remembers_it() {
+        let mock = MockRtorrent::start(|xml| {
+            if xml_method_name(xml) == "d.multicall2" {
+                testing::fault(-506, "Method 'd.multicall2' not defined")
+            } else {
+                testing::torrents_response(&[testing::torrent_row("AAAA", "one")])
+            }
+        });
+        let client = RtorrentClient::new(mock.socket_path());
+
+        assert_eq!(client.get_torrents().await.unwrap().len(), 1);
+        // The second poll goes straight to the variant that worked
+        assert_eq!(client.clone().get_torrents().await.unwrap().len(), 1);
+
+        assert_eq!(mock.methods(), ["d.multicall2", "d.multicall", "d.multicall"]);
+        let params = call_params(&mock.requests()[1]);
+        assert_eq!(params[..2], ["main", "d.hash="]);
+        assert_eq!(params.len(), RtorrentClient::TORRENT_FIELDS.len() + 1);
+    }
+
+    fn version_response(_: &str) -> String {
+        testing::response("<string>0.9.8</string>")
+    }
+
+    #[tokio::test]
+    async fn socket_symlink_is_followed_again_when_its_target_goes_away() {
+        let dir = tempfile::tempdir().unwrap();
+        let link = dir.path().join("rtorrent.sock");
+        let first = MockRtorrent::start(version_response);
+        std::os::unix::fs::symlink(first.path(), &link).unwrap();
+        let client = RtorrentClient::new(link.display().to_string());
+        assert_eq!(client.get_client_version().await.unwrap(), "0.9.8");
+
+        // rTorrent restarts on a new socket and the symlink is repointed
+        drop(first);
+        let second = MockRtorrent::start(version_response);
+        std::fs::remove_file(&link).unwrap();
+        std::os::unix::fs::symlink(second.path(), &link).unwrap();
+
+        assert_eq!(client.get_client_version().await.unwrap(), "0.9.8");
+        assert_eq!(second.methods(), ["system.client_version"]);
+    }
+
+    #[tokio::test]
+    async fn socket_errors_say_what_is_wrong() {
+        let dir = tempfile::tempdir().unwrap();
+        let error = |path: &Path| {
+            let client = RtorrentClient::new(path.display().to_string());
+            async move { client.get_client_version().await.unwrap_err().to_string() }
+        };
+
+        let missing = dir.path().join("missing.sock");
+        assert!(error(&missing).await.contains("socket file not found"));
+
+        let file = dir.path().join("file.sock");
+        std::fs::write(&file, "").unwrap();
+        assert!(error(&file).await.contains("not a socket"));
+
+        let broken = dir.path().join("broken.sock");
+        std::os::unix::fs::symlink(&missing, &broken).unwrap();
+        assert!(error(&broken).await.contains("broken symlink to"));
+
+        // Root ignores socket modes, so check the mapping directly
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert!(diagnose_socket(&file, &denied).contains("permission denied"));
+    }
+
+    #[test]
+    fn torrent_fields_parse_from_mixed_value_types() {
+        let mut row = testing::torrent_row("ABCDEF", "Mixed &amp; Matched");
+        row[2] = "<i8>4294967296</i8>".to_string();
+        row[3] = "<string>1073741824</string>".to_string();
+        row[4] = "<i4>2048</i4>".to_string();
+        row[5] = "512".to_string();
+        row[6] = "<boolean>1</boolean>".to_string();
+        row[7] = "<boolean>1</boolean>".to_string();
+        row[9] = "<boolean>0</boolean>".to_string();
+        row[11] = "<i8>1500</i8>".to_string();
+        row[13] = "<boolean>1</boolean>".to_string();
+        row[16] = "<string>auto-stopped:ratio</string>".to_string();
+        row[17] = "<string> movies </string>".to_string();
+        row[20] = "<string>1</string>".to_string();
+        row[25] = "<i8>7</i8>".to_string();
+        row[26] = "<i4>3</i4>".to_string();
+        let client = RtorrentClient::new(String::new());
+
+        let torrents = client.parse_torrents_response(&testing::torrents_response(&[row])).unwrap();
+
+        let torrent = &torrents[0];
+        assert_eq!(torrent.hash, "ABCDEF");
+        assert_eq!(torrent.name, "Mixed & Matched");
+        assert_eq!(torrent.size_bytes, 4_294_967_296);
+        assert_eq!(torrent.completed_bytes, 1_073_741_824);
+        assert_eq!(torrent.down_rate, 2048);
+        assert_eq!(torrent.up_rate, 512);
+        assert!(torrent.is_active && torrent.is_open && !torrent.complete);
+        assert_eq!(torrent.state, TorrentState::Downloading);
+        assert_eq!(torrent.ratio, 1.5);
+        assert!(torrent.is_forced);
+        assert_eq!(torrent.auto_stopped.as_deref(), Some("ratio"));
+        assert_eq!(torrent.label, "movies");
+        assert!(torrent.is_private);
+        assert_eq!((torrent.peers_connected, torrent.peers_complete), (7, 3));
+    }
+
+    #[test]
+    fn negative_sizes_and_durations_read_as_zero() {
+        assert_eq!(format_bytes(-1), "0 B");
+        assert_eq!(format_bytes(i64::MIN), "0 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_duration(-3600), "0s");
+        assert_eq!(format_duration(i64::MIN), "0s");
+        assert_eq!(format_duration(3900), "1h 5m");
+        assert_eq!(format_rate(i64::MIN, RateUnit::Bits), "0 bps");
+    }
+
+    #[test]
+    fn byte_rates_convert_to_bit_rates() {
+        // 1.25 MB/s is 10 megabits, in powers of 1000
+        assert_eq!(format_rate(1_250_000, RateUnit::Bits), "10.0 Mbps");
+        assert_eq!(format_rate(125_000_000, RateUnit::Bits), "1.0 Gbps");
+        assert_eq!(format_rate(2_000, RateUnit::Bits), "16.0 Kbps");
+        assert_eq!(format_rate(100, RateUnit::Bits), "800 bps");
+        assert_eq!(format_rate(1_048_576, RateUnit::Bytes), "1.0 MB/s");
+
+        let mut torrent = testing::torrent("A", "a");
+        torrent.rate_unit = RateUnit::Bits;
+        torrent.down_rate = 1_250_000;
+        torrent.up_rate = 62_500;
+        assert_eq!(torrent.down_rate_formatted(), "10.0 Mbps");
+        assert_eq!(torrent.up_rate_formatted(), "500.0 Kbps");
+    }
+
+    #[test]
+    fn eta_of_an_overcomplete_torrent_is_zero() {
+        let mut torrent = testing::torrent("A", "a");
+        torrent.completed_bytes = torrent.size_bytes + 4096;
+        torrent.avg_down_rate = 100;
+        assert_eq!(torrent.eta().as_deref(), Some("0s"));
+
+        torrent.size_bytes = i64::MIN;
+        assert_eq!(torrent.eta().as_deref(), Some("0s"));
+
+        torrent.avg_down_rate = i64::MIN;
+        assert_eq!(torrent.eta(), None);
+    }
+
+    #[test]
+    fn open_and_active_flags_map_to_states() {
+        let flag = |on: bool| if on { "<i8>1</i8>" } else { "<i8>0</i8>" }.to_string();
+        let row = |hash: &str, is_open: bool, is_active: bool, complete: bool| {
+            let mut row = testing::torrent_row(hash, hash);
+            row[6] = flag(is_active);
+            row[7] = flag(is_open);
+            row[9] = flag(complete);
+            row
+        };
+        let rows = [
+            row("OPEN_ACTIVE", true, true, false),
+            row("OPEN_ACTIVE_DONE", true, true, true),
+            row("OPEN_INACTIVE", true, false, false),
+            row("CLOSED_INACTIVE", false, false, false),
+            // rTorrent can briefly report a closed torrent as active
+            row("CLOSED_ACTIVE", false, true, false),
+        ];
+        let client = RtorrentClient::new(String::new());
+
+        let torrents = client.parse_torrents_response(&testing::torrents_response(&rows)).unwrap();
+
+        let states: Vec<_> = torrents.iter().map(|t| (t.hash.as_str(), t.state)).collect();
+        assert_eq!(
+            states,
+            [
+                ("OPEN_ACTIVE", TorrentState::Downloading),
+                ("OPEN_ACTIVE_DONE", TorrentState::Seeding),
+                ("OPEN_INACTIVE", TorrentState::Paused),
+                ("CLOSED_INACTIVE", TorrentState::Stopped),
+                ("CLOSED_ACTIVE", TorrentState::Stopped),
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_hashes_keep_the_first_row_and_warn_once() {
+        let mut second = testing::torrent_row("DUP", "second");
+        second[2] = "<i8>2000</i8>".to_string();
+        let rows = [testing::torrent_row("DUP", "first"), second, testing::torrent_row("ONE", "one")];
+        let client = RtorrentClient::new(String::new());
+
+        let torrents = client.parse_torrents_response(&testing::torrents_response(&rows)).unwrap();
+
+        let names: Vec<_> = torrents.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["first", "one"]);
+        // The parse above already warned about DUP, and clones share what was warned
+        let clone = client.clone();
+        assert!(clone.report_duplicates(vec!["DUP".to_string()]).is_empty());
+        assert_eq!(client.report_duplicates(vec!["DUP".to_string(), "NEW".to_string()]), ["NEW"]);
+        assert!(client.report_duplicates(vec!["NEW".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn scgi_content_length_counts_bytes_of_multibyte_bodies() {
+        let client = RtorrentClient::new(String::new());
+        let body = "<string>Café 日本</string>";
+        assert_ne!(body.len(), body.chars().count());
+
+        let request = client.scgi_request(body.as_bytes());
+
+        let (len, rest) = request.split_at(request.iter().position(|&b| b == b':').unwrap());
+        let len: usize = std::str::from_utf8(len).unwrap().parse().unwrap();
+        let headers = &rest[1..=len];
+        let fields: Vec<_> = headers.split(|&b| b == 0).collect();
+        assert_eq!(fields[..2], [&b"CONTENT_LENGTH"[..], body.len().to_string().as_bytes()]);
+        assert_eq!(rest[len + 1], b',');
+        assert_eq!(&rest[len + 2..], body.as_bytes());
+    }
+
+    fn location(base_path: &str, directory: &str, name: &str, is_multi_file: bool) -> DataLocation {
+        DataLocation {
+            base_path: PathBuf::from(base_path),
+            directory: PathBuf::from(directory),
+            name: name.to_string(),
+            is_multi_file,
+            default_directory: PathBuf::from("/downloads"),
+        }
+    }
+
+    #[test]
+    fn data_named_after_the_torrent_may_be_deleted() {
+        let single = location("/downloads/movie.mkv", "/downloads", "movie.mkv", false);
+        assert_eq!(single.deletable_path(), Ok(Path::new("/downloads/movie.mkv")));
+        let multi = location("/downloads/album", "/downloads/album", "album", true);
+        assert_eq!(multi.deletable_path(), Ok(Path::new("/downloads/album")));
+    }
+
+    #[test]
+    fn nameless_torrents_never_delete_their_download_directory() {
+        // A magnet still fetching metadata: rTorrent's path is the directory itself
+        let magnet = location("/downloads", "/downloads", "", false);
+        assert!(magnet.deletable_path().unwrap_err().contains("no name"));
+        let closed = location("", "/downloads", "movie.mkv", false);
+        assert!(closed.deletable_path().is_err());
+    }
+
+    #[test]
+    fn torrents_pointed_at_a_shared_directory_never_delete_it() {
+        // `d.directory_base.set /downloads/shared` on a multi-file torrent
+        let shared = location("/downloads/shared", "/downloads/shared", "album", true);
+        assert!(shared.deletable_path().is_err());
+        // Named like the torrent, but the download directory or one above it
+        let default = location("/downloads", "/srv/other", "downloads", true);
+        assert!(default.deletable_path().is_err());
+        let above = location("/data", "/data/incoming", "data", false);
+        assert!(above.deletable_path().is_err());
+        let single_as_directory = location("/downloads/movie.mkv", "/downloads/movie.mkv", "movie.mkv", false);
+        assert!(single_as_directory.deletable_path().is_err());
+    }
+
+    #[tokio::test]
+    async fn data_location_is_read_in_one_call() {
+        let mock = MockRtorrent::start(|_| {
+            let result = |value: &str| testing::array(&[value.to_string()]);
+            testing::response(&testing::array(&[
+                result("<string>/downloads/album</string>"),
+                result("<string>/downloads/album</string>"),
+                result("<string>album</string>"),
+                result("<i8>1</i8>"),
+                result("<string>/downloads</string>"),
+            ]))
+        });
+        let client = RtorrentClient::new(mock.socket_path());
+
+        let location = client.get_data_location("AAAA").await.unwrap();
+
+        assert_eq!(mock.methods(), ["system.multicall"]);
+        assert_eq!(location.deletable_path(), Ok(Path::new("/downloads/album")));
+        assert_eq!(location.default_directory, Path::new("/downloads"));
+    }
+
+    #[tokio::test]
+    async fn remove_fails_when_rtorrent_refuses_the_erase() {
+        let mock = MockRtorrent::start(|_| testing::fault(-501, "Could not find info-hash."));
+        let client = RtorrentClient::new(mock.socket_path());
+
+        let error = client.remove_torrent("AAAA").await.unwrap_err();
+
+        assert!(error.to_string().contains("Could not find info-hash"));
+    }
+
+    #[test]
+    fn magnet_hashes_read_in_hex_base32_and_percent_encoded() {
+        let hex = "C12FE1C06BBA254A9DC9F519B335AA7C1367A88A";
+        let magnet = |xt: &str| format!("magnet:?dn=Some%20name&{}&tr=udp%3A%2F%2Ft.example%3A80", xt);
+
+        assert_eq!(magnet_info_hash(&magnet(&format!("xt=urn:btih:{}", hex.to_lowercase()))).as_deref(), Some(hex));
+        assert_eq!(magnet_info_hash(&magnet("xt=urn:btih:YEX6DQDLXISUVHOJ6UM3GNNKPQJWPKEK")).as_deref(), Some(hex));
+        assert_eq!(magnet_info_hash(&magnet("xt=urn:btih:yex6dqdlxisuvhoj6um3gnnkpqjwpkek")).as_deref(), Some(hex));
+        assert_eq!(magnet_info_hash(&magnet(&format!("xt=urn%3Abtih%3A{}", hex))).as_deref(), Some(hex));
+        assert_eq!(magnet_info_hash(&magnet(&format!("xt=urn%3abtih%3a{}", hex))).as_deref(), Some(hex));
+    }
+
+    #[test]
+    fn magnets_without_a_usable_hash_have_none() {
+        assert_eq!(magnet_info_hash("https://example.com/a.torrent"), None);
+        assert_eq!(magnet_info_hash("magnet:?dn=no-hash"), None);
+        assert_eq!(magnet_info_hash("magnet:?xt=urn:btih:TOOSHORT"), None);
+        assert_eq!(magnet_info_hash("magnet:?xt=urn:btmh:1220abcd"), None);
+        assert_eq!(magnet_info_hash("magnet:?xt=urn%3Abtih%3"), None);
+        // Base32 uses A-Z and 2-7 only
+        assert_eq!(magnet_info_hash("magnet:?xt=urn:btih:YEX6DQDLXISUVHOJ6UM3GNNKPQJWPKE1"), None);
+    }
+
+    #[test]
+    fn optional_methods_turn_off_features_without_a_warning() {
+        let mut available: Vec<String> = REQUIRED_METHODS.iter().map(|m| m.to_string()).collect();
+        available.push("d.multicall2".to_string());
+
+        assert!(RtorrentClient::missing_from(&available).is_empty());
+        let unsupported = RtorrentClient::unsupported_features(&available);
+        assert_eq!(unsupported.len(), OPTIONAL_METHODS.len());
+        assert!(unsupported.contains(&"DHT settings"));
+
+        available.extend(OPTIONAL_METHODS.iter().map(|(method, _)| method.to_string()));
+        assert!(RtorrentClient::unsupported_features(&available).is_empty());
+        assert_eq!(optional_feature("p.multicall"), "peer lists");
+    }
+}
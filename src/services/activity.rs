@@ -0,0 +1,43 @@
+//! Idle time: how long since each torrent last transferred data
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::rtorrent::Torrent;
+
+/// When each torrent last moved data, as seen in the rates of each snapshot.
+///
+/// rTorrent keeps no such timestamp, so a torrent already idle when VibeTorrent
+/// started counts from the first time it was seen.
+#[derive(Debug, Default)]
+pub struct ActivityTracker {
+    last_active: Mutex<HashMap<String, Instant>>,
+}
+
+impl ActivityTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note which torrents are transferring and set each torrent's `idle_time`.
+    ///
+    /// `torrents` must be the full list; hashes missing from it are forgotten.
+    pub fn apply(&self, torrents: &mut [Torrent]) {
+        let now = Instant::now();
+        let mut last_active = self.last_active.lock().unwrap_or_else(|e| e.into_inner());
+        let mut next = HashMap::with_capacity(torrents.len());
+
+        for torrent in torrents.iter_mut() {
+            let at = if torrent.down_rate > 0 || torrent.up_rate > 0 {
+                now
+            } else {
+                last_active.get(&torrent.hash).copied().unwrap_or(now)
+            };
+            torrent.idle_time = Some(now.duration_since(at).as_secs() as i64);
+            next.insert(torrent.hash.clone(), at);
+        }
+
+        *last_active = next;
+    }
+}
@@ -0,0 +1,11 @@
+//! Rules every automation follows before acting on a torrent by itself
+
+use std::collections::HashSet;
+
+/// Whether automation may pause, stop or remove the torrent with `hash`.
+///
+/// Starred torrents are the user's favourites: poller rules skip them and
+/// `/torrent/{hash}/auto-stop` refuses them, whatever the rule says.
+pub fn may_act_on(hash: &str, starred: &HashSet<String>) -> bool {
+    !starred.contains(hash)
+}
@@ -0,0 +1,68 @@
+//! User-configured commands run on torrent events
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use tokio::process::Command;
+
+use crate::rtorrent::{RtorrentClient, Torrent};
+
+/// Run `on_complete_command` for a finished torrent in the background.
+///
+/// The command goes through `sh -c` with `VT_HASH`, `VT_NAME` and `VT_PATH` set.
+/// Output is logged; a command still running after `timeout` is killed.
+pub fn spawn_on_complete(rtorrent: RtorrentClient, command: String, timeout: Duration, torrent: Torrent) {
+    tokio::spawn(async move {
+        let path = match rtorrent.get_base_path(&torrent.hash).await {
+            Ok(path) => path,
+            Err(e) => {
+                tracing::warn!("on_complete: get_base_path failed for {}: {}", torrent.hash, e);
+                String::new()
+            }
+        };
+
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .env("VT_HASH", &torrent.hash)
+            .env("VT_NAME", &torrent.name)
+            .env("VT_PATH", &path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn();
+        let child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                tracing::error!("on_complete: failed to start command for {}: {}", torrent.name, e);
+                return;
+            }
+        };
+
+        match tokio::time::timeout(timeout, child.wait_with_output()).await {
+            Ok(Ok(output)) => {
+                if output.status.success() {
+                    tracing::info!("on_complete for {} finished", torrent.name);
+                } else {
+                    tracing::warn!("on_complete for {} exited with {}", torrent.name, output.status);
+                }
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                if !stdout.trim().is_empty() {
+                    tracing::info!("on_complete for {} stdout: {}", torrent.name, stdout.trim());
+                }
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if !stderr.trim().is_empty() {
+                    tracing::warn!("on_complete for {} stderr: {}", torrent.name, stderr.trim());
+                }
+            }
+            Ok(Err(e)) => {
+                tracing::error!("on_complete for {} failed: {}", torrent.name, e);
+            }
+            Err(_) => {
+                // Dropping the future kills the child (kill_on_drop)
+                tracing::warn!("on_complete for {} timed out after {}s, killed", torrent.name, timeout.as_secs());
+            }
+        }
+    });
+}
@@ -0,0 +1,176 @@
+//! Opt-in removal of torrents that have been seeding without any traffic
+
+use std::collections::HashSet;
+
+use crate::config::Config;
+use crate::rtorrent::{RtorrentClient, Torrent, TorrentState};
+use crate::services::automation;
+use crate::services::torrents::delete_torrent_data;
+
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// The `idle_remove_*` policy, checked against every poll.
+///
+/// Only torrents rTorrent is seeding qualify, so ones stopped by hand or by
+/// VibeTorrent ("Done", "Auto-stopped") stay, and starred torrents are never
+/// touched (see [`automation::may_act_on`]). Idle time counts from
+/// VibeTorrent's start at the earliest, see
+/// [`ActivityTracker`](crate::services::activity::ActivityTracker).
+pub struct IdleRemover {
+    idle_secs: i64,
+    delete_data: bool,
+    dry_run: bool,
+    /// Torrents already removed or reported, so each is handled once while
+    /// it is still listed
+    handled: HashSet<String>,
+}
+
+impl IdleRemover {
+    /// `None` unless `idle_remove_days` is set to at least one day
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let days = config.idle_remove_days.filter(|days| *days > 0)?;
+        tracing::info!(
+            "Torrents seeding idle for {} days are removed{}{}",
+            days,
+            if config.idle_remove_delete_data { " with their data" } else { "" },
+            if config.idle_remove_dry_run { " (dry run: only logged)" } else { "" },
+        );
+        Some(Self {
+            idle_secs: i64::try_from(days).unwrap_or(i64::MAX / SECS_PER_DAY).saturating_mul(SECS_PER_DAY),
+            delete_data: config.idle_remove_delete_data,
+            dry_run: config.idle_remove_dry_run,
+            handled: HashSet::new(),
+        })
+    }
+
+    /// Remove (or in a dry run, log) every torrent in the full list `torrents`
+    /// that is due. Removals run in the background.
+    pub fn apply(&mut self, rtorrent: &RtorrentClient, torrents: &[Torrent], starred: &HashSet<String>) {
+        self.handled.retain(|hash| torrents.iter().any(|t| &t.hash == hash));
+
+        for torrent in torrents {
+            let due = torrent.state == TorrentState::Seeding
+                && torrent.idle_time.is_some_and(|idle| idle >= self.idle_secs)
+                && automation::may_act_on(&torrent.hash, starred);
+            if !due || !self.handled.insert(torrent.hash.clone()) {
+                continue;
+            }
+
+            let days = torrent.idle_time.unwrap_or(0) / SECS_PER_DAY;
+            if self.dry_run {
+                tracing::warn!(
+                    "idle_remove: would remove {} ({}), idle for {} days{}",
+                    torrent.name,
+                    torrent.hash,
+                    days,
+                    if self.delete_data { " with its data" } else { "" },
+                );
+                continue;
+            }
+            tracing::warn!("idle_remove: removing {} ({}), idle for {} days", torrent.name, torrent.hash, days);
+            tokio::spawn(remove(rtorrent.clone(), torrent.clone(), self.delete_data));
+        }
+    }
+}
+
+async fn remove(rtorrent: RtorrentClient, torrent: Torrent, delete_data: bool) {
+    // Located first: once erased, rTorrent can no longer say where the data is
+    let location = if delete_data {
+        match rtorrent.get_data_location(&torrent.hash).await {
+            Ok(location) => Some(location),
+            Err(e) => {
+                tracing::error!("idle_remove: not removing {}, its data path is unknown: {}", torrent.name, e);
+                return;
+            }
+        }
+    } else {
+        None
+    };
+
+    if let Err(e) = rtorrent.remove_torrent(&torrent.hash).await {
+        tracing::error!("idle_remove: removing {} failed: {}", torrent.name, e);
+        return;
+    }
+    let Some(location) = location else {
+        return;
+    };
+    match delete_torrent_data(&location).await {
+        Ok(()) => tracing::warn!("idle_remove: deleted data of {} at {}", torrent.name, location.base_path.display()),
+        Err(e) => tracing::error!(
+            "idle_remove: removed {} but deleting {} failed: {}",
+            torrent.name,
+            location.base_path.display(),
+            e
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, MockRtorrent};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn starred_torrents_are_not_removed_when_idle() {
+        let mock = MockRtorrent::start(|_| testing::response("<i8>0</i8>"));
+        let rtorrent = RtorrentClient::new(mock.socket_path());
+        let config = Config { idle_remove_days: Some(1), ..Config::default() };
+        let mut remover = IdleRemover::from_config(&config).unwrap();
+        let idle = |hash: &str| {
+            let mut torrent = testing::torrent(hash, hash);
+            torrent.state = TorrentState::Seeding;
+            torrent.idle_time = Some(3 * SECS_PER_DAY);
+            torrent
+        };
+        let starred = HashSet::from(["STARRED".to_string()]);
+
+        remover.apply(&rtorrent, &[idle("STARRED"), idle("PLAIN")], &starred);
+
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while mock.requests().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let erased = mock.requests();
+        assert_eq!(mock.methods(), ["d.erase"]);
+        assert!(erased[0].contains("PLAIN"));
+    }
+
+    #[tokio::test]
+    async fn data_whose_path_is_its_directory_is_kept_while_the_torrent_goes() {
+        let downloads = tempfile::tempdir().unwrap();
+        std::fs::write(downloads.path().join("other.iso"), "keep").unwrap();
+        let path = format!("<string>{}</string>", downloads.path().display());
+        let mock = MockRtorrent::start(move |xml| match crate::rtorrent::xml_method_name(xml) {
+            "system.multicall" => {
+                let results = [path.clone(), path.clone(), "<string>one</string>".to_string(), "<i8>0</i8>".to_string(), path.clone()];
+                let results: Vec<String> = results.iter().map(|value| testing::array(std::slice::from_ref(value))).collect();
+                testing::response(&testing::array(&results))
+            }
+            _ => testing::response("<i8>0</i8>"),
+        });
+        let rtorrent = RtorrentClient::new(mock.socket_path());
+        let config = Config { idle_remove_days: Some(1), idle_remove_delete_data: true, ..Config::default() };
+        let mut remover = IdleRemover::from_config(&config).unwrap();
+        let mut torrent = testing::torrent("AAAA", "one");
+        torrent.state = TorrentState::Seeding;
+        torrent.idle_time = Some(3 * SECS_PER_DAY);
+
+        remover.apply(&rtorrent, &[torrent], &HashSet::new());
+
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while mock.methods().len() < 2 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        // Give the removal a moment to get to the data after the erase
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(mock.methods(), ["system.multicall", "d.erase"]);
+        assert!(downloads.path().join("other.iso").exists());
+    }
+}
@@ -1 +1,9 @@
+pub mod activity;
+pub mod automation;
+pub mod hooks;
+pub mod idle_remove;
+pub mod pieces;
+pub mod rates;
 pub mod torrents;
+pub mod transitions;
+pub mod webhooks;
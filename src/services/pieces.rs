@@ -0,0 +1,135 @@
+//! Piece rate: how many chunks each downloading torrent completes per second
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::rtorrent::Torrent;
+
+/// Time constant of the moving average, as for the smoothed byte rate
+const SMOOTHING: Duration = Duration::from_secs(20);
+
+/// Moving average of each torrent's `completed_chunks` growth.
+///
+/// A byte rate can't tell a few rare pieces trickling in from many common ones;
+/// the piece rate next to it can. Time-weighted like
+/// [`RateSmoother`](crate::services::rates::RateSmoother), and torrents that
+/// aren't downloading drop their history.
+#[derive(Debug, Default)]
+pub struct PieceRateTracker {
+    samples: Mutex<HashMap<String, Sample>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    chunks: i64,
+    at: Instant,
+    /// `None` until a second sample
+    average: Option<f64>,
+}
+
+impl PieceRateTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fold this snapshot's chunk counts in and set each torrent's `chunk_rate`.
+    ///
+    /// `torrents` must be the full list; hashes missing from it are forgotten.
+    pub fn apply(&self, torrents: &mut [Torrent]) {
+        let now = Instant::now();
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        let mut next = HashMap::with_capacity(torrents.len());
+
+        for torrent in torrents.iter_mut() {
+            if !torrent.is_active || torrent.complete || torrent.is_hashing {
+                continue;
+            }
+            let average = match samples.get(&torrent.hash) {
+                // A recheck can lower the count; start over from there
+                Some(&Sample { chunks, at, average }) if torrent.completed_chunks >= chunks => {
+                    let elapsed = now.duration_since(at).as_secs_f64();
+                    if elapsed <= 0.0 {
+                        average
+                    } else {
+                        let rate = (torrent.completed_chunks - chunks) as f64 / elapsed;
+                        let alpha = 1.0 - (-elapsed / SMOOTHING.as_secs_f64()).exp();
+                        Some(average.map_or(rate, |previous| previous + alpha * (rate - previous)))
+                    }
+                }
+                _ => None,
+            };
+            torrent.chunk_rate = average;
+            next.insert(torrent.hash.clone(), Sample { chunks: torrent.completed_chunks, at: now, average });
+        }
+
+        *samples = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn downloading(hash: &str, completed_chunks: i64) -> Torrent {
+        Torrent { completed_chunks, ..testing::torrent(hash, hash) }
+    }
+
+    /// Apply one snapshot after a short pause, so the samples are apart in time
+    fn apply(tracker: &PieceRateTracker, mut torrents: Vec<Torrent>) -> Vec<Torrent> {
+        std::thread::sleep(Duration::from_millis(20));
+        tracker.apply(&mut torrents);
+        torrents
+    }
+
+    #[test]
+    fn the_first_sample_has_no_rate() {
+        let tracker = PieceRateTracker::new();
+
+        let torrents = apply(&tracker, vec![downloading("AAAA", 10)]);
+
+        assert_eq!(torrents[0].chunk_rate, None);
+    }
+
+    #[test]
+    fn growing_chunk_counts_give_a_rate() {
+        let tracker = PieceRateTracker::new();
+        apply(&tracker, vec![downloading("AAAA", 10)]);
+
+        let torrents = apply(&tracker, vec![downloading("AAAA", 20)]);
+
+        assert!(torrents[0].chunk_rate.is_some_and(|rate| rate > 0.0), "{:?}", torrents[0].chunk_rate);
+        // No growth pulls the average down but keeps it
+        let torrents = apply(&tracker, vec![downloading("AAAA", 20)]);
+        assert!(torrents[0].chunk_rate.is_some());
+    }
+
+    #[test]
+    fn a_recheck_lowering_the_count_starts_over() {
+        let tracker = PieceRateTracker::new();
+        apply(&tracker, vec![downloading("AAAA", 10)]);
+        apply(&tracker, vec![downloading("AAAA", 20)]);
+
+        let torrents = apply(&tracker, vec![downloading("AAAA", 5)]);
+        assert_eq!(torrents[0].chunk_rate, None);
+        let torrents = apply(&tracker, vec![downloading("AAAA", 8)]);
+        assert!(torrents[0].chunk_rate.is_some_and(|rate| rate > 0.0));
+    }
+
+    #[test]
+    fn torrents_gone_from_the_list_are_forgotten() {
+        let tracker = PieceRateTracker::new();
+        apply(&tracker, vec![downloading("AAAA", 10), downloading("BBBB", 10)]);
+
+        apply(&tracker, vec![downloading("BBBB", 20)]);
+
+        let samples = tracker.samples.lock().unwrap();
+        assert!(!samples.contains_key("AAAA"));
+        assert!(samples.contains_key("BBBB"));
+        drop(samples);
+        // Back again, it starts without a rate
+        let torrents = apply(&tracker, vec![downloading("AAAA", 30)]);
+        assert_eq!(torrents[0].chunk_rate, None);
+    }
+}
@@ -0,0 +1,54 @@
+//! Smoothed transfer rates for stable ETAs
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::rtorrent::Torrent;
+
+/// Time constant of the moving average: a rate change is ~63% reflected after this long
+const SMOOTHING: Duration = Duration::from_secs(20);
+
+/// Exponential moving average of each torrent's download rate.
+///
+/// Time-weighted, so extra samples from action refreshes between polls don't
+/// skew it. Paused or finished torrents drop their history.
+#[derive(Debug, Default)]
+pub struct RateSmoother {
+    averages: Mutex<HashMap<String, (f64, Instant)>>,
+}
+
+impl RateSmoother {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fold this snapshot's rates in and set each torrent's `avg_down_rate`.
+    ///
+    /// `torrents` must be the full list; hashes missing from it are forgotten.
+    pub fn apply(&self, torrents: &mut [Torrent]) {
+        let now = Instant::now();
+        let mut averages = self.averages.lock().unwrap_or_else(|e| e.into_inner());
+        let mut next = HashMap::with_capacity(torrents.len());
+
+        for torrent in torrents.iter_mut() {
+            if !torrent.is_active || torrent.complete {
+                torrent.avg_down_rate = torrent.down_rate;
+                continue;
+            }
+            let rate = torrent.down_rate as f64;
+            let average = match averages.get(&torrent.hash) {
+                Some(&(previous, at)) => {
+                    let elapsed = now.duration_since(at).as_secs_f64();
+                    let alpha = 1.0 - (-elapsed / SMOOTHING.as_secs_f64()).exp();
+                    previous + alpha * (rate - previous)
+                }
+                None => rate,
+            };
+            torrent.avg_down_rate = average.round() as i64;
+            next.insert(torrent.hash.clone(), (average, now));
+        }
+
+        *averages = next;
+    }
+}
@@ -1,11 +1,12 @@
 use askama::Template;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use crate::error::AppError;
 use crate::routes::FilterQuery;
-use crate::rtorrent::{Torrent, TorrentState};
+use crate::rtorrent::{DataLocation, Torrent, TorrentState};
 use crate::state::AppState;
-use crate::templates::{SidebarCountsTemplate, TorrentListTemplate, TorrentView};
+use crate::templates::{GroupView, SidebarCountsTemplate, TorrentListTemplate, TorrentView};
 
 /// Render torrent list + sidebar counts from a shared snapshot, applying optional filter/search/sort.
 ///
@@ -18,25 +19,42 @@ pub async fn render_torrents_html(
     filter: Option<&str>,
     all_torrents: &[Torrent],
 ) -> Result<String, AppError> {
-    let torrents = apply_filter_sort(all_torrents, filter, query);
+    let positions = state.queue_positions(all_torrents).await;
+    let hidden = state.hidden_torrents.read().await.clone();
+    let torrents = apply_filter_sort(all_torrents, filter, query, &positions, &hidden);
+    // Totals cover the whole group, whatever the status, search and progress
+    // filters and the row limit leave of it
+    let group = query.label.as_deref()
+        .filter(|label| !label.is_empty())
+        .map(|label| {
+            let whole_group = FilterQuery { label: Some(label.to_string()), ..FilterQuery::default() };
+            let hidden_only = filter.filter(|filter| *filter == "hidden");
+            GroupView::from_torrents(label, &apply_filter_sort(all_torrents, hidden_only, &whole_group, &positions, &hidden))
+        });
+    let (shown, hidden_count) = apply_limit(&torrents, query.limit.or(state.config.default_limit));
 
-    // Starred set snapshot (avoid per-row await)
     let starred = state.starred_torrents.read().await.clone();
+    let torrent_views = torrent_views(shown, &starred, &positions);
 
-    let mut torrent_views = Vec::with_capacity(torrents.len());
-    for t in &torrents {
-        let is_starred = starred.contains(&t.hash);
-        torrent_views.push(TorrentView::from_torrent(t, is_starred));
-    }
-
-    let counts = calculate_counts(all_torrents);
+    let counts = calculate_counts(all_torrents, &hidden);
 
-    let list_template = TorrentListTemplate { torrents: torrent_views };
+    let list_template = TorrentListTemplate {
+        torrents: torrent_views,
+        hidden_count,
+        group,
+        read_only: state.config.read_only,
+        compact: query.is_mobile(),
+    };
     let counts_template = SidebarCountsTemplate {
         total_count: counts.total,
         downloading_count: counts.downloading,
         seeding_count: counts.seeding,
         paused_count: counts.paused,
+        stopped_count: counts.stopped,
+        errored_count: counts.errored,
+        hidden_torrents_count: counts.hidden,
+        private_count: counts.private,
+        public_count: counts.total - counts.private,
     };
 
     let list_html = list_template
@@ -49,43 +67,138 @@ pub async fn render_torrents_html(
     Ok(format!("{}{}", list_html, counts_html))
 }
 
+/// Row views for `torrents`, given a snapshot of the starred set (read once
+/// rather than per row) and the queue positions
+pub fn torrent_views(
+    torrents: &[Torrent],
+    starred: &HashSet<String>,
+    positions: &HashMap<String, usize>,
+) -> Vec<TorrentView> {
+    torrents
+        .iter()
+        .map(|t| {
+            let position = positions.get(&t.hash).copied().unwrap_or(0);
+            TorrentView::from_torrent(t, starred.contains(&t.hash)).with_queue_position(position)
+        })
+        .collect()
+}
+
 pub fn apply_filter_sort(
     all_torrents: &[Torrent],
     filter: Option<&str>,
     query: &FilterQuery,
+    queue_positions: &HashMap<String, usize>,
+    hidden: &HashSet<String>,
 ) -> Vec<Torrent> {
     let mut torrents = all_torrents.to_vec();
 
+    // Hidden torrents only show up under their own filter
+    if filter != Some("hidden") {
+        torrents.retain(|t| !hidden.contains(&t.hash));
+    }
+
     // Status filter
     if let Some(filter) = filter {
         match filter {
             "downloading" => torrents.retain(|t| t.state == TorrentState::Downloading),
             "seeding" => torrents.retain(|t| t.state == TorrentState::Seeding),
             "paused" => torrents.retain(|t| t.state == TorrentState::Paused),
+            "stopped" => torrents.retain(|t| t.state == TorrentState::Stopped),
+            "errored" => torrents.retain(|t| t.state == TorrentState::Error),
+            "private" => torrents.retain(|t| t.is_private),
+            "public" => torrents.retain(|t| !t.is_private),
+            "hidden" => torrents.retain(|t| hidden.contains(&t.hash)),
             _ => {}
         }
     }
 
+    // Label group
+    if let Some(label) = query.label.as_deref().filter(|label| !label.is_empty()) {
+        torrents.retain(|t| t.label == label);
+    }
+
+    // Progress range
+    if let Some((min, max)) = query.progress_range() {
+        torrents.retain(|t| (min..=max).contains(&t.progress_percent()));
+    }
+
     // Search filter
     if let Some(search) = &query.search {
-        let search_lower = search.to_lowercase();
-        torrents.retain(|t| t.name.to_lowercase().contains(&search_lower));
+        let (scope, term) = parse_search(search);
+        torrents.retain(|t| matches_search(t, scope, &term));
     }
 
     // Sorting
-    apply_sorting(&mut torrents, query);
+    apply_sorting(&mut torrents, query, queue_positions);
 
     torrents
 }
 
-struct TorrentCounts {
-    total: usize,
-    downloading: usize,
-    seeding: usize,
-    paused: usize,
+/// Field a search is limited to with a `name:`, `hash:` or `label:` prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SearchScope {
+    Name,
+    Hash,
+    Label,
 }
 
-fn calculate_counts(torrents: &[Torrent]) -> TorrentCounts {
+/// Split a known scope prefix off the search box text and lowercase the rest.
+/// Anything else, `udp:` included, is searched as typed.
+fn parse_search(search: &str) -> (Option<SearchScope>, String) {
+    let search = search.trim();
+    let scoped = search.split_once(':').and_then(|(prefix, term)| {
+        let scope = match prefix.to_ascii_lowercase().as_str() {
+            "name" => SearchScope::Name,
+            "hash" => SearchScope::Hash,
+            "label" => SearchScope::Label,
+            _ => return None,
+        };
+        Some((scope, term.trim()))
+    });
+    match scoped {
+        Some((scope, term)) => (Some(scope), term.to_lowercase()),
+        None => (None, search.to_lowercase()),
+    }
+}
+
+/// Case-insensitive substring match on the scoped field, or on the name, hash
+/// and label when unscoped. Tracker hosts aren't part of the list snapshot, so
+/// they can't be searched here.
+fn matches_search(torrent: &Torrent, scope: Option<SearchScope>, term: &str) -> bool {
+    let contains = |field: &str| field.to_lowercase().contains(term);
+    match scope {
+        Some(SearchScope::Name) => contains(torrent.display_name()),
+        Some(SearchScope::Hash) => contains(&torrent.hash),
+        Some(SearchScope::Label) => contains(&torrent.label),
+        None => contains(torrent.display_name()) || contains(&torrent.hash) || contains(&torrent.label),
+    }
+}
+
+/// The first `limit` torrents (all of them for `None` or 0) and how many were
+/// left out
+pub fn apply_limit(torrents: &[Torrent], limit: Option<usize>) -> (&[Torrent], usize) {
+    match limit {
+        Some(limit) if limit > 0 && limit < torrents.len() => (&torrents[..limit], torrents.len() - limit),
+        _ => (torrents, 0),
+    }
+}
+
+/// Sidebar counts, always over the whole list. Hidden torrents are only
+/// counted in `hidden`.
+pub struct TorrentCounts {
+    pub total: usize,
+    pub downloading: usize,
+    pub seeding: usize,
+    pub paused: usize,
+    pub stopped: usize,
+    pub errored: usize,
+    pub hidden: usize,
+    pub private: usize,
+}
+
+pub fn calculate_counts(all_torrents: &[Torrent], hidden: &HashSet<String>) -> TorrentCounts {
+    let (hidden_torrents, torrents): (Vec<&Torrent>, Vec<&Torrent>) =
+        all_torrents.iter().partition(|t| hidden.contains(&t.hash));
     TorrentCounts {
         total: torrents.len(),
         downloading: torrents
@@ -94,48 +207,216 @@ fn calculate_counts(torrents: &[Torrent]) -> TorrentCounts {
             .count(),
         seeding: torrents.iter().filter(|t| t.state == TorrentState::Seeding).count(),
         paused: torrents.iter().filter(|t| t.state == TorrentState::Paused).count(),
+        stopped: torrents.iter().filter(|t| t.state == TorrentState::Stopped).count(),
+        errored: torrents.iter().filter(|t| t.state == TorrentState::Error).count(),
+        hidden: hidden_torrents.len(),
+        private: torrents.iter().filter(|t| t.is_private).count(),
     }
 }
 
-fn apply_sorting(torrents: &mut [Torrent], query: &FilterQuery) {
-    let is_desc = query.order.as_deref() != Some("asc");
-
-    if let Some(sort) = &query.sort {
-        match sort.as_str() {
-            "name" => {
-                torrents.sort_by(|a, b| {
-                    let cmp = a.name.to_lowercase().cmp(&b.name.to_lowercase());
-                    if is_desc { cmp.reverse() } else { cmp }
-                });
-            }
-            "size" => {
-                torrents.sort_by(|a, b| {
-                    let cmp = a.size_bytes.cmp(&b.size_bytes);
-                    if is_desc { cmp.reverse() } else { cmp }
-                });
-            }
-            "progress" => {
-                torrents.sort_by(|a, b| {
-                    let cmp = a
-                        .progress_percent()
-                        .partial_cmp(&b.progress_percent())
-                        .unwrap_or(std::cmp::Ordering::Equal);
-                    if is_desc { cmp.reverse() } else { cmp }
-                });
-            }
-            "down_rate" => {
-                torrents.sort_by(|a, b| {
-                    let cmp = a.down_rate.cmp(&b.down_rate);
-                    if is_desc { cmp.reverse() } else { cmp }
-                });
-            }
-            "up_rate" => {
-                torrents.sort_by(|a, b| {
-                    let cmp = a.up_rate.cmp(&b.up_rate);
-                    if is_desc { cmp.reverse() } else { cmp }
-                });
-            }
-            _ => {}
+/// Full queue order: explicitly ordered hashes that still exist, then the rest
+/// in the order rTorrent loaded them.
+pub fn effective_queue(order: &[String], torrents: &[Torrent]) -> Vec<String> {
+    let present: HashSet<&str> = torrents.iter().map(|t| t.hash.as_str()).collect();
+    let mut queue: Vec<String> = order
+        .iter()
+        .filter(|hash| present.contains(hash.as_str()))
+        .cloned()
+        .collect();
+
+    let listed: HashSet<String> = queue.iter().cloned().collect();
+    let mut rest: Vec<&Torrent> = torrents.iter().filter(|t| !listed.contains(&t.hash)).collect();
+    rest.sort_by_key(|t| t.load_date);
+    queue.extend(rest.into_iter().map(|t| t.hash.clone()));
+    queue
+}
+
+fn apply_sorting(torrents: &mut [Torrent], query: &FilterQuery, queue_positions: &HashMap<String, usize>) {
+    let Some(sort) = &query.sort else {
+        return;
+    };
+    // Queue positions read 1, 2, 3 from the top; every other column starts
+    // with the biggest value
+    let is_desc = match query.order.as_deref() {
+        Some("asc") => false,
+        Some("desc") => true,
+        _ => sort != "queue",
+    };
+
+    match sort.as_str() {
+        "name" => sort_by(torrents, is_desc, |a, b| {
+            a.display_name().to_lowercase().cmp(&b.display_name().to_lowercase())
+        }),
+        "size" => sort_by(torrents, is_desc, |a, b| a.size_bytes.cmp(&b.size_bytes)),
+        "progress" => sort_by(torrents, is_desc, |a, b| {
+            a.progress_percent()
+                .partial_cmp(&b.progress_percent())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        }),
+        "down_rate" => sort_by(torrents, is_desc, |a, b| a.down_rate.cmp(&b.down_rate)),
+        "up_rate" => sort_by(torrents, is_desc, |a, b| a.up_rate.cmp(&b.up_rate)),
+        "queue" => {
+            let position = |t: &Torrent| queue_positions.get(&t.hash).copied().unwrap_or(usize::MAX);
+            sort_by(torrents, is_desc, |a, b| position(a).cmp(&position(b)));
+        }
+        "uploaded" => sort_by(torrents, is_desc, |a, b| a.bytes_uploaded.cmp(&b.bytes_uploaded)),
+        // Torrents without a value (not finished, not yet seen) sort as the shortest
+        "seeding_time" => sort_by(torrents, is_desc, |a, b| a.seeding_time.cmp(&b.seeding_time)),
+        "idle_time" => sort_by(torrents, is_desc, |a, b| a.idle_time.cmp(&b.idle_time)),
+        _ => {}
+    }
+}
+
+/// Sort by `cmp`, then by hash, so torrents with equal keys (say, every idle
+/// torrent at 0 B/s) keep their places from one poll to the next instead of
+/// following rTorrent's order. Descending mirrors ascending exactly.
+fn sort_by(torrents: &mut [Torrent], is_desc: bool, cmp: impl Fn(&Torrent, &Torrent) -> std::cmp::Ordering) {
+    torrents.sort_by(|a, b| {
+        let ordering = cmp(a, b).then_with(|| a.hash.cmp(&b.hash));
+        if is_desc { ordering.reverse() } else { ordering }
+    });
+}
+
+/// Delete a torrent's file or folder, if [`DataLocation::deletable_path`]
+/// allows it. Already missing data counts as deleted.
+pub async fn delete_torrent_data(location: &DataLocation) -> std::result::Result<(), String> {
+    let path = location.deletable_path()?;
+    
+    let result = match tokio::fs::symlink_metadata(path).await {
+        Ok(metadata) if metadata.is_dir() => tokio::fs::remove_dir_all(path).await,
+        Ok(_) => tokio::fs::remove_file(path).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn sorted_hashes(torrents: &[Torrent], query: &FilterQuery, positions: &HashMap<String, usize>) -> Vec<String> {
+        apply_filter_sort(torrents, None, query, positions, &HashSet::new())
+            .into_iter()
+            .map(|t| t.hash)
+            .collect()
+    }
+
+    #[test]
+    fn queue_sort_starts_at_position_one_unless_asked_otherwise() {
+        let torrents = vec![testing::torrent("B", "b"), testing::torrent("A", "a"), testing::torrent("C", "c")];
+        let positions = HashMap::from([("A".to_string(), 1), ("B".to_string(), 2), ("C".to_string(), 3)]);
+        let query = |order: Option<&str>| FilterQuery {
+            sort: Some("queue".to_string()),
+            order: order.map(str::to_string),
+            ..FilterQuery::default()
+        };
+
+        assert_eq!(sorted_hashes(&torrents, &query(None), &positions), ["A", "B", "C"]);
+        assert_eq!(sorted_hashes(&torrents, &query(Some("asc")), &positions), ["A", "B", "C"]);
+        assert_eq!(sorted_hashes(&torrents, &query(Some("desc")), &positions), ["C", "B", "A"]);
+    }
+
+    #[test]
+    fn equal_keys_fall_back_to_hash_order_whatever_rtorrent_sent() {
+        let idle = |hash: &str| testing::torrent(hash, hash);
+        let mut busy = testing::torrent("A", "a");
+        busy.down_rate = 1000;
+        let query = |order: &str| FilterQuery {
+            sort: Some("down_rate".to_string()),
+            order: Some(order.to_string()),
+            ..FilterQuery::default()
+        };
+
+        for torrents in [vec![idle("C"), idle("B"), busy.clone(), idle("D")], vec![idle("D"), busy.clone(), idle("B"), idle("C")]] {
+            assert_eq!(sorted_hashes(&torrents, &query("asc"), &HashMap::new()), ["B", "C", "D", "A"]);
+            assert_eq!(sorted_hashes(&torrents, &query("desc"), &HashMap::new()), ["A", "D", "C", "B"]);
         }
     }
+
+    #[test]
+    fn paused_and_stopped_filter_separately() {
+        let mut paused = testing::torrent("PAUSED", "paused");
+        paused.state = TorrentState::Paused;
+        let mut stopped = testing::torrent("STOPPED", "stopped");
+        stopped.state = TorrentState::Stopped;
+        let torrents = vec![paused, stopped, testing::torrent("RUNNING", "running")];
+        let filtered = |filter: &str| -> Vec<String> {
+            apply_filter_sort(&torrents, Some(filter), &FilterQuery::default(), &HashMap::new(), &HashSet::new())
+                .into_iter()
+                .map(|t| t.hash)
+                .collect()
+        };
+
+        assert_eq!(filtered("paused"), ["PAUSED"]);
+        assert_eq!(filtered("stopped"), ["STOPPED"]);
+        let counts = calculate_counts(&torrents, &HashSet::new());
+        assert_eq!((counts.paused, counts.stopped), (1, 1));
+    }
+
+    #[test]
+    fn search_matches_names_hashes_and_labels() {
+        let mut ubuntu = testing::torrent("ABCDEF0123", "Ubuntu 24.04 Desktop");
+        ubuntu.label = "linux".to_string();
+        let mut debian = testing::torrent("FEDCBA9876", "debian-12-netinst");
+        debian.label = "ubuntu-mirror".to_string();
+        let torrents = vec![ubuntu, debian, testing::torrent("0011223344", "Some Film")];
+        let positions = HashMap::new();
+        let search = |text: &str| FilterQuery { search: Some(text.to_string()), ..FilterQuery::default() };
+
+        // Unscoped terms match any field, ignoring case
+        assert_eq!(sorted_hashes(&torrents, &search("ubuntu"), &positions), ["ABCDEF0123", "FEDCBA9876"]);
+        assert_eq!(sorted_hashes(&torrents, &search("abcdef"), &positions), ["ABCDEF0123"]);
+        assert_eq!(sorted_hashes(&torrents, &search("  some film "), &positions), ["0011223344"]);
+        // A prefix limits the search to one field
+        assert_eq!(sorted_hashes(&torrents, &search("name:ubuntu"), &positions), ["ABCDEF0123"]);
+        assert_eq!(sorted_hashes(&torrents, &search("Label: Ubuntu"), &positions), ["FEDCBA9876"]);
+        assert_eq!(sorted_hashes(&torrents, &search("hash:fedcba"), &positions), ["FEDCBA9876"]);
+        assert!(sorted_hashes(&torrents, &search("hash:film"), &positions).is_empty());
+        // Unknown prefixes are part of the term
+        assert!(sorted_hashes(&torrents, &search("udp:ubuntu"), &positions).is_empty());
+    }
+
+    #[test]
+    fn unnamed_torrents_show_and_search_by_the_start_of_their_hash() {
+        let mut unnamed = testing::torrent("0123456789ABCDEF", "");
+        assert_eq!(unnamed.display_name(), "01234567");
+        assert_eq!(TorrentView::from_torrent(&unnamed, false).name, "01234567");
+        assert_eq!(unnamed.name, "");
+
+        unnamed.name = "  ".to_string();
+        assert_eq!(unnamed.display_name(), "01234567");
+        let mut short = testing::torrent("ABC", "");
+        assert_eq!(short.display_name(), "ABC");
+        short.name = "Named".to_string();
+        assert_eq!(short.display_name(), "Named");
+
+        let torrents = vec![unnamed, testing::torrent("FFFFFFFF", "other")];
+        let query = FilterQuery { search: Some("name:01234".to_string()), ..FilterQuery::default() };
+        assert_eq!(sorted_hashes(&torrents, &query, &HashMap::new()), ["0123456789ABCDEF"]);
+    }
+
+    #[tokio::test]
+    async fn group_totals_cover_the_whole_label_whatever_the_filters() {
+        let mock = testing::MockRtorrent::start(|_| testing::response("<i8>0</i8>"));
+        let dir = tempfile::tempdir().unwrap();
+        let state = testing::app_state(&mock, dir.path());
+        let mut torrents = vec![testing::torrent("A", "alpha"), testing::torrent("B", "beta"), testing::torrent("C", "gamma")];
+        torrents[0].state = TorrentState::Seeding;
+        for torrent in &mut torrents[..2] {
+            torrent.label = "linux".to_string();
+        }
+        let query = FilterQuery { label: Some("linux".to_string()), search: Some("beta".to_string()), ..FilterQuery::default() };
+
+        let html = render_torrents_html(&state, &query, Some("downloading"), &torrents).await.unwrap();
+
+        assert!(html.contains("2 torrents"), "{}", html);
+        // 2 × 1000 B
+        assert!(html.contains(">2.0 KB<"), "{}", html);
+        assert!(html.contains("id=\"torrent-B\""));
+        assert!(!html.contains("id=\"torrent-A\""));
+    }
 }
@@ -0,0 +1,95 @@
+//! Torrent state transitions, found by diffing consecutive poller snapshots
+
+use std::collections::HashMap;
+
+use crate::rtorrent::{Torrent, TorrentState};
+
+/// Something that happened to a torrent between two polls
+#[derive(Debug, Clone)]
+pub enum Transition {
+    /// Appeared in rTorrent since the last poll
+    Added(Torrent),
+    /// Finished downloading (`complete` went from false to true)
+    Completed(Torrent),
+    /// Entered the error state, e.g. a tracker or storage failure
+    Errored(Torrent),
+}
+
+impl Transition {
+    /// Every [`Self::kind`], the names `webhook_events` accepts
+    pub const KINDS: &'static [&'static str] = &["added", "completed", "error"];
+
+    /// Event name used in webhook payloads and config
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Transition::Added(_) => "added",
+            Transition::Completed(_) => "completed",
+            Transition::Errored(_) => "error",
+        }
+    }
+
+    pub fn torrent(&self) -> &Torrent {
+        match self {
+            Transition::Added(torrent) | Transition::Completed(torrent) | Transition::Errored(torrent) => torrent,
+        }
+    }
+}
+
+/// What we remember about each torrent between polls
+#[derive(Debug, Clone, Copy)]
+struct Seen {
+    complete: bool,
+    errored: bool,
+}
+
+impl Seen {
+    fn of(torrent: &Torrent) -> Self {
+        Self {
+            complete: torrent.complete,
+            errored: torrent.state == TorrentState::Error,
+        }
+    }
+}
+
+/// Remembers the previous snapshot and reports what changed.
+///
+/// The first snapshot only sets the baseline, so a restart doesn't replay
+/// events for everything that is already there.
+#[derive(Debug, Default)]
+pub struct TransitionTracker {
+    seen: Option<HashMap<String, Seen>>,
+}
+
+impl TransitionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Diff `torrents` against the previous snapshot and make it the new baseline
+    pub fn update(&mut self, torrents: &[Torrent]) -> Vec<Transition> {
+        let current: HashMap<String, Seen> = torrents
+            .iter()
+            .map(|t| (t.hash.clone(), Seen::of(t)))
+            .collect();
+
+        let mut transitions = Vec::new();
+        if let Some(previous) = &self.seen {
+            for torrent in torrents {
+                let now = current[&torrent.hash];
+                let Some(before) = previous.get(&torrent.hash) else {
+                    transitions.push(Transition::Added(torrent.clone()));
+                    continue;
+                };
+                if !before.complete && now.complete {
+                    transitions.push(Transition::Completed(torrent.clone()));
+                }
+                if !before.errored && now.errored {
+                    transitions.push(Transition::Errored(torrent.clone()));
+                }
+            }
+        }
+
+        self.seen = Some(current);
+        transitions
+    }
+}
@@ -0,0 +1,88 @@
+//! Outbound webhook for torrent events (Discord/Slack/Home Assistant bridges)
+
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::services::transitions::Transition;
+
+/// Per-attempt request timeout
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Pause before the single retry
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Clone)]
+pub struct Webhook {
+    client: reqwest::Client,
+    url: String,
+    events: Vec<String>,
+}
+
+impl Webhook {
+    /// `None` when no `webhook_url` is configured
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let url = config.webhook_url.as_deref().map(str::trim).filter(|u| !u.is_empty())?;
+        let client = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .user_agent(concat!("vibetorrent/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .inspect_err(|e| tracing::error!("webhook: failed to build HTTP client: {}", e))
+            .ok()?;
+        Some(Self {
+            client,
+            url: url.to_string(),
+            events: config.webhook_events.clone(),
+        })
+    }
+
+    /// POST `transition` in the background if its event type is enabled.
+    ///
+    /// Best effort: one retry, then the failure is logged and dropped.
+    pub fn send(&self, transition: &Transition) {
+        if !self.events.iter().any(|e| e == transition.kind()) {
+            return;
+        }
+
+        let torrent = transition.torrent();
+        let payload = serde_json::json!({
+            "event": transition.kind(),
+            "hash": torrent.hash,
+            "name": torrent.name,
+            "size_bytes": torrent.size_bytes,
+            "completed_bytes": torrent.completed_bytes,
+            "uploaded_bytes": torrent.bytes_uploaded,
+            "downloaded_bytes": torrent.bytes_downloaded,
+            "message": torrent.message,
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+        });
+
+        let webhook = self.clone();
+        tokio::spawn(async move {
+            for attempt in 1..=2 {
+                match webhook.post(&payload).await {
+                    Ok(()) => return,
+                    Err(e) if attempt == 1 => {
+                        tracing::debug!("webhook: {} delivery failed, retrying: {}", payload["event"], e);
+                        tokio::time::sleep(RETRY_DELAY).await;
+                    }
+                    Err(e) => {
+                        tracing::warn!("webhook: giving up on {} for {}: {}", payload["event"], payload["name"], e);
+                    }
+                }
+            }
+        });
+    }
+
+    async fn post(&self, payload: &serde_json::Value) -> Result<(), String> {
+        let response = self.client
+            .post(&self.url)
+            .json(payload)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("HTTP {}", response.status()))
+        }
+    }
+}
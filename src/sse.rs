@@ -5,22 +5,129 @@
 //! - Supports filtering and sorting per-client via query parameters
 //! - Handles reconnection gracefully
 //! - Includes sidebar counts and stats updates
+//!
+//! ## Idle streams
+//!
+//! A tab in the background doesn't need live updates. When the page is hidden
+//! the client reconnects each stream with `?idle=1` (alongside any other query
+//! parameters), and reconnects without it once visible again. An idle stream
+//! still sends the current state on connect, then forwards at most one update
+//! every [`IDLE_INTERVAL`]; updates in between are dropped before rendering.
+//!
+//! ## Coalescing
+//!
+//! List and stats streams render the newest snapshot waiting for them, not
+//! every one. When the poller broadcasts faster than a client takes events
+//! (an aggressive poll interval, a slow connection, a long render), the
+//! snapshots queued in between are dropped for that client, so each client
+//! renders at most as often as it can be sent. Single-row updates aren't
+//! coalesced, since each may be about a different torrent.
+//!
+//! ## Errors
+//!
+//! A render failure is sent as a `server-error` event with a JSON payload
+//! (`stream`, `message`) instead of the usual event, and the stream carries on.
+//! The name keeps it apart from EventSource's own `error`, a dropped connection.
+//!
+//! ## Stats
+//!
+//! The stats stream sends the whole bar as a `stats` event on connect, then
+//! `stats-delta` events carrying out-of-band swaps for only the values whose
+//! text changed, so an idle header isn't redrawn every poll. With a
+//! `--templates-dir` override of the stats partial, whose markup may lack the
+//! ids, every update is a full `stats` event instead.
+//!
+//! ## Notifications
+//!
+//! The stats stream also carries `notification` events (JSON with `hash`,
+//! `name` and `size`) when a torrent finishes downloading, but only for
+//! clients that connect with `?notify=1`.
 
 use axum::{
     extract::{Query, State},
-    response::sse::{Event, KeepAlive, Sse},
+    http::{header::HeaderName, HeaderValue},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
 };
 use futures::stream::{self, Stream};
 use futures::StreamExt;
-use std::{convert::Infallible, sync::Arc, time::Duration};
+use serde::Deserialize;
+use std::{
+    convert::Infallible,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::broadcast::{self, error::RecvError, error::TryRecvError};
 use tokio_stream::wrappers::BroadcastStream;
 
 use crate::routes::FilterQuery;
 use crate::services::torrents as torrents_service;
 use crate::state::AppState;
-use crate::templates::StatsTemplate;
+use crate::rtorrent::GlobalStats;
+use crate::templates::{StatsDeltaTemplate, StatsTemplate, TorrentOobTemplate, TorrentView};
 use askama::Template;
 
+/// How often an idle (hidden-tab) stream is updated
+pub const IDLE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Per-subscriber stream options, see the module docs
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StreamParams {
+    pub idle: Option<String>,
+    /// Opt in to `notification` events on the stats stream
+    pub notify: Option<String>,
+}
+
+impl StreamParams {
+    fn is_idle(&self) -> bool {
+        matches!(self.idle.as_deref(), Some("1" | "true"))
+    }
+
+    fn wants_notifications(&self) -> bool {
+        matches!(self.notify.as_deref(), Some("1" | "true"))
+    }
+}
+
+/// Stream filter that lets everything through for active subscribers and one
+/// item per [`IDLE_INTERVAL`] for idle ones
+fn cadence<T>(params: &StreamParams) -> impl FnMut(&T) -> futures::future::Ready<bool> {
+    let idle = params.is_idle();
+    let mut last_sent: Option<Instant> = None;
+    move |_| {
+        let now = Instant::now();
+        let due = !idle || last_sent.is_none_or(|sent| now.duration_since(sent) >= IDLE_INTERVAL);
+        if due {
+            last_sent = Some(now);
+        }
+        futures::future::ready(due)
+    }
+}
+
+/// Broadcast messages, each yielded as the newest one queued at that point,
+/// see "Coalescing" in the module docs
+fn coalesced<T: Clone + Send + 'static>(rx: broadcast::Receiver<T>) -> impl Stream<Item = T> {
+    stream::unfold(rx, |mut rx| async move {
+        let mut item = loop {
+            match rx.recv().await {
+                Ok(item) => break item,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        };
+        // Anything that arrived while the last one was rendered is newer
+        loop {
+            match rx.try_recv() {
+                Ok(newer) => item = newer,
+                Err(TryRecvError::Lagged(_)) => continue,
+                Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+            }
+        }
+        Some((item, rx))
+    })
+}
+
 /// SSE endpoint for torrent list updates
 /// 
 /// Clients connect with optional filter/sort parameters:
@@ -28,46 +135,32 @@ use askama::Template;
 pub async fn torrent_events(
     State(state): State<Arc<AppState>>,
     Query(query): Query<FilterQuery>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    Query(params): Query<StreamParams>,
+) -> impl IntoResponse {
     let initial = match state.latest_torrents().await {
         Some(torrents) => {
-            let html = match torrents_service::render_torrents_html(&state, &query, None, &torrents).await {
-                Ok(html) => html,
-                Err(_) => String::from("<div class=\"text-red-400\">Error loading torrents</div>"),
-            };
-            Some(Ok(Event::default().event("torrents").data(html)))
+            let rendered = torrents_service::render_torrents_html(&state, &query, None, &torrents).await;
+            Some(Ok(render_event("torrents", rendered)))
         }
         None => None,
     };
 
-    let updates = BroadcastStream::new(state.subscribe_torrents()).filter_map({
+    let updates = coalesced(state.subscribe_torrents()).filter(cadence(&params)).filter_map({
         let state = state.clone();
         let query = query.clone();
-        move |msg| {
+        move |torrents: Arc<Vec<_>>| {
             let state = state.clone();
             let query = query.clone();
             async move {
-                match msg {
-                    Ok(torrents) => {
-                        let html = match torrents_service::render_torrents_html(&state, &query, None, &torrents).await {
-                            Ok(html) => html,
-                            Err(_) => String::from("<div class=\"text-red-400\">Error loading torrents</div>"),
-                        };
-                        Some(Ok(Event::default().event("torrents").data(html)))
-                    }
-                    Err(_) => None,
-                }
+                let rendered = torrents_service::render_torrents_html(&state, &query, None, &torrents).await;
+                Some(Ok(render_event("torrents", rendered)))
             }
         }
     });
 
-    let stream = stream::iter(initial.into_iter()).chain(updates);
+    let stream = stream::iter(initial).chain(updates);
 
-    Sse::new(stream).keep_alive(
-        KeepAlive::new()
-            .interval(Duration::from_secs(15))
-            .text("keep-alive"),
-    )
+    sse_response(&state, stream)
 }
 
 /// SSE endpoint for filtered torrent list updates
@@ -75,80 +168,173 @@ pub async fn torrent_filtered_events(
     State(state): State<Arc<AppState>>,
     axum::extract::Path(filter): axum::extract::Path<String>,
     Query(query): Query<FilterQuery>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    Query(params): Query<StreamParams>,
+) -> impl IntoResponse {
     let initial = match state.latest_torrents().await {
         Some(torrents) => {
-            let html = match torrents_service::render_torrents_html(&state, &query, Some(&filter), &torrents).await {
-                Ok(html) => html,
-                Err(_) => String::from("<div class=\"text-red-400\">Error loading torrents</div>"),
-            };
-            Some(Ok(Event::default().event("torrents").data(html)))
+            let rendered = torrents_service::render_torrents_html(&state, &query, Some(&filter), &torrents).await;
+            Some(Ok(render_event("torrents", rendered)))
         }
         None => None,
     };
 
-    let updates = BroadcastStream::new(state.subscribe_torrents()).filter_map({
+    let updates = coalesced(state.subscribe_torrents()).filter(cadence(&params)).filter_map({
         let state = state.clone();
         let query = query.clone();
         let filter = filter.clone();
-        move |msg| {
+        move |torrents: Arc<Vec<_>>| {
             let state = state.clone();
             let query = query.clone();
             let filter = filter.clone();
             async move {
-                match msg {
-                    Ok(torrents) => {
-                        let html = match torrents_service::render_torrents_html(&state, &query, Some(&filter), &torrents).await {
-                            Ok(html) => html,
-                            Err(_) => String::from("<div class=\"text-red-400\">Error loading torrents</div>"),
-                        };
-                        Some(Ok(Event::default().event("torrents").data(html)))
-                    }
-                    Err(_) => None,
-                }
+                let rendered = torrents_service::render_torrents_html(&state, &query, Some(&filter), &torrents).await;
+                Some(Ok(render_event("torrents", rendered)))
             }
         }
     });
 
-    let stream = stream::iter(initial.into_iter()).chain(updates);
+    let stream = stream::iter(initial).chain(updates);
 
-    Sse::new(stream).keep_alive(
-        KeepAlive::new()
-            .interval(Duration::from_secs(15))
-            .text("keep-alive"),
-    )
+    sse_response(&state, stream)
+}
+
+/// SSE endpoint for single-torrent updates published by action handlers.
+///
+/// Each event is one row marked `hx-swap-oob`, so the client replaces just that
+/// torrent in whichever list it is showing. `mobile=1` sends the compact rows
+/// a `mobile=1` list shows.
+pub async fn torrent_update_events(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<FilterQuery>,
+    Query(params): Query<StreamParams>,
+) -> impl IntoResponse {
+    let compact = query.is_mobile();
+    let updates = BroadcastStream::new(state.subscribe_torrent_events()).filter(cadence(&params)).filter_map({
+        let state = state.clone();
+        move |msg| {
+            let state = state.clone();
+            async move {
+                let torrent = msg.ok()?;
+                let is_starred = state.is_starred(&torrent.hash).await;
+                let position = state.queue_position(&torrent.hash).await;
+                let template = TorrentOobTemplate {
+                    torrent: TorrentView::from_torrent(&torrent, is_starred).with_queue_position(position),
+                    read_only: state.config.read_only,
+                    compact,
+                };
+                let html = template.render().ok()?;
+                Some(Ok(Event::default().event("torrent").data(html)))
+            }
+        }
+    });
+
+    sse_response(&state, updates)
 }
 
 /// SSE endpoint for stats updates (download/upload speed, disk space, peers)
 pub async fn stats_events(
     State(state): State<Arc<AppState>>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    let initial = match state.latest_stats().await {
-        Some(stats) => {
-            let template = StatsTemplate { stats: (*stats).clone() };
-            let html = template.render().unwrap_or_default();
-            Some(Ok(Event::default().event("stats").data(html)))
-        }
-        None => None,
-    };
+    Query(params): Query<StreamParams>,
+) -> impl IntoResponse {
+    let latest = state.latest_stats().await;
+    let initial = latest.as_ref().map(|stats| {
+        let template = StatsTemplate { stats: (**stats).clone() };
+        Ok(render_event("stats", template.render()))
+    });
 
-    let updates = BroadcastStream::new(state.subscribe_stats()).filter_map(|msg| async move {
-        match msg {
-            Ok(stats) => {
-                let template = StatsTemplate { stats: (*stats).clone() };
-                let html = template.render().unwrap_or_default();
-                Some(Ok(Event::default().event("stats").data(html)))
+    // What this client currently shows, to diff each update against
+    let mut shown = latest.map(|stats| stat_values(&stats));
+    let full_only = crate::theme::overrides_stats();
+    let updates = coalesced(state.subscribe_stats()).filter(cadence(&params)).filter_map(move |stats| {
+        let values = stat_values(&stats);
+        let event = match shown.as_ref() {
+            Some(previous) if !full_only => {
+                let changed: Vec<_> = values.iter()
+                    .zip(previous)
+                    .filter(|(new, old)| new.1 != old.1)
+                    .map(|(new, _)| new.clone())
+                    .collect();
+                (!changed.is_empty())
+                    .then(|| render_event("stats-delta", StatsDeltaTemplate { changed }.render()))
             }
-            Err(_) => None,
+            _ => Some(render_event("stats", StatsTemplate { stats: (*stats).clone() }.render())),
+        };
+        if event.is_some() {
+            shown = Some(values);
         }
+        futures::future::ready(event.map(Ok))
     });
 
-    let stream = stream::iter(initial.into_iter()).chain(updates);
+    // Completions are rare and matter most in hidden tabs, so they skip the
+    // idle cadence; only transitions seen while connected are sent, never replayed
+    let notifications = params.wants_notifications().then(|| {
+        BroadcastStream::new(state.subscribe_completions()).filter_map(|msg| async move {
+            let torrent = msg.ok()?;
+            let data = serde_json::json!({
+                "hash": torrent.hash,
+                "name": torrent.display_name(),
+                "size": torrent.size_formatted(),
+            });
+            Some(Ok(Event::default().event("notification").data(data.to_string())))
+        })
+    });
+
+    let stream = stream::select(
+        stream::iter(initial).chain(updates),
+        stream::iter(notifications).flatten(),
+    );
+
+    sse_response(&state, stream)
+}
+
+/// Element ids in `stats.html` with the text each shows
+fn stat_values(stats: &GlobalStats) -> [(&'static str, String); 6] {
+    [
+        ("stat-down-rate", stats.down_rate_formatted()),
+        ("stat-up-rate", stats.up_rate_formatted()),
+        ("stat-free-disk", stats.free_disk_formatted()),
+        ("stat-active-peers", stats.active_peers.to_string()),
+        ("stat-connected-seeds", stats.connected_seeds.to_string()),
+        ("stat-connected-leechers", stats.connected_leechers.to_string()),
+    ]
+}
 
-    Sse::new(stream).keep_alive(
+/// A `name` event with the rendered HTML, or a `server-error` event if rendering
+/// failed. It isn't called `error`, which EventSource fires for a dropped
+/// connection too.
+///
+/// The error carries JSON (`stream`, `message`) for the client to show as a
+/// banner, so broken markup never lands in the page. The stream stays open and
+/// the next successful render replaces it.
+fn render_event<E: std::fmt::Display>(name: &'static str, rendered: Result<String, E>) -> Event {
+    match rendered {
+        Ok(html) => Event::default().event(name).data(html),
+        Err(e) => {
+            tracing::warn!("SSE {} render failed: {}", name, e);
+            let payload = serde_json::json!({ "stream": name, "message": e.to_string() });
+            Event::default().event("server-error").data(payload.to_string())
+        }
+    }
+}
+
+/// Wrap an event stream in an SSE response that reverse proxies won't buffer.
+///
+/// The keep-alive interval and comment text come from the config. The stream
+/// ends when `state` is shut down (config change), so the client's automatic
+/// reconnect lands on the new state.
+fn sse_response<S>(state: &AppState, stream: S) -> impl IntoResponse
+where
+    S: Stream<Item = Result<Event, Infallible>> + Send + 'static,
+{
+    let config = &state.config;
+    let stream = stream.take_until(state.stopped());
+    let sse = Sse::new(stream).keep_alive(
         KeepAlive::new()
-            .interval(Duration::from_secs(15))
-            .text("keep-alive"),
+            .interval(Duration::from_secs(config.sse_keepalive_secs.max(1)))
+            .text(config.sse_keepalive_text.as_str()),
+    );
+    (
+        [(HeaderName::from_static("x-accel-buffering"), HeaderValue::from_static("no"))],
+        sse,
     )
 }
-
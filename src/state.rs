@@ -1,60 +1,402 @@
 use tokio::sync::{broadcast, watch, RwLock};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::time::interval;
 
+use crate::config::Config;
 use crate::rtorrent::RtorrentClient;
 use crate::rtorrent::{GlobalStats, Torrent};
+use crate::services::activity::ActivityTracker;
+use crate::services::hooks;
+use crate::services::idle_remove::IdleRemover;
+use crate::services::pieces::PieceRateTracker;
+use crate::services::rates::RateSmoother;
+use crate::services::torrents::effective_queue;
+use crate::services::transitions::{Transition, TransitionTracker};
+use crate::services::webhooks::Webhook;
+use crate::store::{Store, StoreData};
+
+/// How often the poller fetches torrents and stats
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Cached snapshots older than this are refetched by [`AppState::fresh_torrents`],
+/// in case the poller is stuck on a slow rTorrent.
+const MAX_CACHE_AGE: Duration = POLL_INTERVAL.saturating_mul(2);
+
+/// How long the poller trusts a fetched rTorrent version. It only changes
+/// when rTorrent restarts, which a reconnect catches sooner.
+const VERSION_TTL: Duration = Duration::from_secs(300);
+
+/// Quiet period before stars and queue order are written, so a burst of
+/// clicks is one write
+const STORE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// A torrent list snapshot, when it was fetched and its generation
+#[derive(Clone)]
+struct CachedTorrents {
+    snapshot: Arc<Vec<Torrent>>,
+    fetched_at: Instant,
+    /// Taken from [`AppState::generation`] before the fetch started
+    generation: u64,
+}
+
+impl CachedTorrents {
+    fn new(snapshot: Arc<Vec<Torrent>>, generation: u64) -> Self {
+        Self { snapshot, fetched_at: Instant::now(), generation }
+    }
+
+    /// Cache `self` unless a snapshot whose fetch started later is already
+    /// cached, then broadcast it on `tx`. Both happen under the write lock, so
+    /// SSE clients get snapshots in generation order. Returns whether it was kept.
+    async fn store(
+        self,
+        cache: &RwLock<Option<CachedTorrents>>,
+        tx: Option<&broadcast::Sender<Arc<Vec<Torrent>>>>,
+    ) -> bool {
+        let mut cached = cache.write().await;
+        if let Some(newer) = cached.as_ref().filter(|cached| cached.generation > self.generation) {
+            tracing::debug!("Dropping torrent snapshot {}, {} is newer", self.generation, newer.generation);
+            return false;
+        }
+        if let Some(tx) = tx {
+            let _ = tx.send(self.snapshot.clone());
+        }
+        *cached = Some(self);
+        true
+    }
+}
+
+/// The generation for a fetch that's about to start
+fn next_generation(counter: &AtomicU64) -> u64 {
+    counter.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// `system.client_version` and when it was fetched
+struct CachedVersion {
+    version: String,
+    fetched_at: Instant,
+}
+
+/// Where [`AppState::move_in_queue`] moves a torrent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueMove {
+    /// One place towards position 1
+    Up,
+    /// One place towards the end
+    Down,
+    /// To position 1, everything before it shifts down
+    Top,
+    /// To the last position, everything after it shifts up
+    Bottom,
+}
 
 pub struct AppState {
     pub rtorrent: RtorrentClient,
-    pub starred_torrents: RwLock<HashSet<String>>,
+    pub config: Config,
+    pub starred_torrents: Arc<RwLock<HashSet<String>>>,
+    /// Torrents kept out of the lists except the `hidden` filter
+    pub hidden_torrents: RwLock<HashSet<String>>,
+    /// Explicit queue order (hashes); see [`effective_queue`]
+    queue_order: RwLock<Vec<String>>,
+    store: Arc<Store>,
 
     torrents_tx: broadcast::Sender<Arc<Vec<Torrent>>>,
+    torrent_events_tx: broadcast::Sender<Arc<Torrent>>,
     stats_tx: broadcast::Sender<Arc<GlobalStats>>,
+    completions_tx: broadcast::Sender<Arc<Torrent>>,
 
-    last_torrents: Arc<RwLock<Option<Arc<Vec<Torrent>>>>>,
+    last_torrents: Arc<RwLock<Option<CachedTorrents>>>,
+    /// Counter ordering torrent snapshots: the poller and manual refreshes
+    /// fetch concurrently, and a slower fetch mustn't replace a newer one
+    generation: Arc<AtomicU64>,
     last_stats: Arc<RwLock<Option<Arc<GlobalStats>>>>,
+    missing_methods: Arc<RwLock<Vec<&'static str>>>,
+    /// `system.listMethods`, fetched once per state
+    method_list: Arc<RwLock<Option<Arc<Vec<String>>>>>,
+    rtorrent_version: Arc<RwLock<Option<CachedVersion>>>,
+    connected: Arc<AtomicBool>,
+    rates: Arc<RateSmoother>,
+    activity: Arc<ActivityTracker>,
+    pieces: Arc<PieceRateTracker>,
 
     shutdown_tx: watch::Sender<bool>,
 }
 
 impl AppState {
-    pub fn new(scgi_socket: String) -> Self {
+    pub fn new(config: Config, store: Store) -> Self {
         let (torrents_tx, _torrents_rx) = broadcast::channel(16);
+        let (torrent_events_tx, _torrent_events_rx) = broadcast::channel(64);
         let (stats_tx, _stats_rx) = broadcast::channel(16);
+        let (completions_tx, _completions_rx) = broadcast::channel(16);
         let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let stored = store.load();
 
         let state = Self {
-            rtorrent: RtorrentClient::new(scgi_socket),
-            starred_torrents: RwLock::new(HashSet::new()),
+            rtorrent: RtorrentClient::new(config.scgi_socket.clone())
+                .with_scgi_env(&config.scgi_env)
+                .with_add_url_attempts(config.add_url_attempts)
+                .with_progress_mode(config.progress_mode)
+                .with_rate_unit(config.rate_unit),
+            config,
+            starred_torrents: Arc::new(RwLock::new(stored.starred.into_iter().collect())),
+            hidden_torrents: RwLock::new(stored.hidden.into_iter().collect()),
+            queue_order: RwLock::new(stored.queue),
+            store: Arc::new(store),
 
             torrents_tx,
+            torrent_events_tx,
             stats_tx,
+            completions_tx,
 
             last_torrents: Arc::new(RwLock::new(None)),
+            generation: Arc::new(AtomicU64::new(0)),
             last_stats: Arc::new(RwLock::new(None)),
+            missing_methods: Arc::new(RwLock::new(Vec::new())),
+            method_list: Arc::new(RwLock::new(None)),
+            rtorrent_version: Arc::new(RwLock::new(None)),
+            connected: Arc::new(AtomicBool::new(false)),
+            rates: Arc::new(RateSmoother::new()),
+            activity: Arc::new(ActivityTracker::new()),
+            pieces: Arc::new(PieceRateTracker::new()),
 
             shutdown_tx,
         };
 
+        state.spawn_capability_probe();
+        state.spawn_store_writer();
         state.spawn_poller(shutdown_rx);
         state
     }
+
+    /// rTorrent's version as last seen by the poller; `None` while disconnected.
+    ///
+    /// Cached for [`VERSION_TTL`] and re-read on every reconnect. A config
+    /// change builds a new `AppState`, so it starts out empty again.
+    pub async fn rtorrent_version(&self) -> Option<String> {
+        if !self.is_connected() {
+            return None;
+        }
+        self.rtorrent_version.read().await.as_ref().map(|cached| cached.version.clone())
+    }
+
+    /// Whether the poller's last torrent fetch succeeded
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Required rTorrent methods found missing by the startup probe
+    pub async fn missing_methods(&self) -> Vec<&'static str> {
+        self.missing_methods.read().await.clone()
+    }
+
+    /// Every method rTorrent exposes. The list only changes with the rTorrent
+    /// build, so it's fetched once (usually by the capability probe) and kept.
+    pub async fn method_list(&self) -> crate::error::Result<Arc<Vec<String>>> {
+        if let Some(methods) = self.method_list.read().await.clone() {
+            return Ok(methods);
+        }
+        let methods = Arc::new(self.rtorrent.list_methods().await?);
+        *self.method_list.write().await = Some(methods.clone());
+        Ok(methods)
+    }
+
+    /// Whether rTorrent has `method`, one of [`OPTIONAL_METHODS`]. Assumed
+    /// when the method list can't be had, so the call itself reports why.
+    ///
+    /// [`OPTIONAL_METHODS`]: crate::rtorrent::OPTIONAL_METHODS
+    pub async fn supports(&self, method: &str) -> bool {
+        self.method_list().await.map_or(true, |methods| methods.iter().any(|name| name == method))
+    }
+
+    /// Check once that the connected rTorrent supports every method we call.
+    ///
+    /// AppState is rebuilt whenever the config is saved, so this also runs after
+    /// pointing VibeTorrent at a different rTorrent.
+    fn spawn_capability_probe(&self) {
+        let rtorrent = self.rtorrent.clone();
+        let missing_methods = self.missing_methods.clone();
+        let method_list = self.method_list.clone();
+
+        tokio::spawn(async move {
+            let available = rtorrent.list_methods().await;
+            if let Ok(available) = &available {
+                method_list.write().await.get_or_insert_with(|| Arc::new(available.clone()));
+                let unsupported = RtorrentClient::unsupported_features(available);
+                if !unsupported.is_empty() {
+                    tracing::info!("rTorrent lacks optional methods, turned off: {}", unsupported.join(", "));
+                }
+            }
+            match available.map(|available| RtorrentClient::missing_from(&available)) {
+                Ok(missing) if missing.is_empty() => {
+                    tracing::debug!("rTorrent supports all required methods");
+                }
+                Ok(missing) => {
+                    tracing::warn!("rTorrent is missing required methods: {}", missing.join(", "));
+                    *missing_methods.write().await = missing;
+                }
+                Err(err) => {
+                    tracing::warn!("capability probe: system.listMethods failed: {}", err);
+                }
+            }
+        });
+    }
+    
+    /// Stop the background poller, even while other `Arc` clones are alive.
+    /// Streams built with [`AppState::stopped`] end as well, and pending store
+    /// writes are flushed in the background.
+    pub fn shutdown(&self) {
+        let _ = self.shutdown_tx.send(true);
+    }
+
+    /// Resolves once this state has been shut down.
+    ///
+    /// Long-lived SSE streams end on it, releasing their `Arc` of a replaced state
+    /// and letting the client reconnect to the current one.
+    pub fn stopped(&self) -> impl std::future::Future<Output = ()> + Send + 'static {
+        let mut shutdown_rx = self.shutdown_tx.subscribe();
+        async move {
+            let _ = shutdown_rx.wait_for(|stopped| *stopped).await;
+        }
+    }
     
     pub async fn is_starred(&self, hash: &str) -> bool {
         self.starred_torrents.read().await.contains(hash)
     }
     
-    pub async fn toggle_star(&self, hash: &str) -> bool {
+    /// Flip the star on `hash` and hand the new value to `render` while the write
+    /// lock is still held, so rapid concurrent toggles can't render a row that
+    /// disagrees with the starred set.
+    pub async fn toggle_star_with<R>(&self, hash: &str, render: impl FnOnce(bool) -> R) -> R {
         let mut starred = self.starred_torrents.write().await;
-        if starred.contains(hash) {
+        let is_starred = if starred.contains(hash) {
             starred.remove(hash);
             false
         } else {
             starred.insert(hash.to_string());
             true
+        };
+        let rendered = render(is_starred);
+        drop(starred);
+        self.persist().await;
+        rendered
+    }
+
+    /// Where re-tie uploads are saved, see [`Store::torrents_dir`]
+    pub fn torrents_dir(&self) -> std::path::PathBuf {
+        self.store.torrents_dir()
+    }
+
+    pub async fn is_hidden(&self, hash: &str) -> bool {
+        self.hidden_torrents.read().await.contains(hash)
+    }
+    
+    /// Hide or unhide `hash`; returns `false` if it already was.
+    ///
+    /// Hashes no longer in `torrents`, the full list, are dropped on the way,
+    /// so removed torrents don't stay in the store forever.
+    pub async fn set_hidden(&self, hash: &str, hidden: bool, torrents: &[Torrent]) -> bool {
+        let mut hidden_torrents = self.hidden_torrents.write().await;
+        let before = hidden_torrents.len();
+        hidden_torrents.retain(|hidden| torrents.iter().any(|t| &t.hash == hidden));
+        let pruned = hidden_torrents.len() != before;
+        let changed = if hidden {
+            hidden_torrents.insert(hash.to_string())
+        } else {
+            hidden_torrents.remove(hash)
+        };
+        drop(hidden_torrents);
+        if changed || pruned {
+            self.persist().await;
+        }
+        changed
+    }
+
+    /// 1-based queue position of every torrent in `torrents`
+    pub async fn queue_positions(&self, torrents: &[Torrent]) -> HashMap<String, usize> {
+        let order = self.queue_order.read().await;
+        effective_queue(&order, torrents)
+            .into_iter()
+            .enumerate()
+            .map(|(index, hash)| (hash, index + 1))
+            .collect()
+    }
+
+    /// 1-based queue position of `hash` in the cached list, or 0 if unknown
+    pub async fn queue_position(&self, hash: &str) -> usize {
+        let torrents = self.latest_torrents().await.unwrap_or_default();
+        self.queue_positions(&torrents).await.get(hash).copied().unwrap_or(0)
+    }
+
+    /// Move `hash` within the queue.
+    ///
+    /// Returns `false` if it's already at that end or not in `torrents`.
+    pub async fn move_in_queue(&self, hash: &str, to: QueueMove, torrents: &[Torrent]) -> bool {
+        let mut order = self.queue_order.write().await;
+        // Materialize the full order so implicit positions become explicit
+        let mut queue = effective_queue(&order, torrents);
+        let Some(index) = queue.iter().position(|h| h == hash) else {
+            return false;
+        };
+        let target = match to {
+            QueueMove::Up => index.checked_sub(1),
+            QueueMove::Down => Some(index + 1),
+            QueueMove::Top => Some(0),
+            QueueMove::Bottom => queue.len().checked_sub(1),
+        };
+        let Some(target) = target.filter(|&t| t < queue.len() && t != index) else {
+            return false;
+        };
+        let moved = queue.remove(index);
+        queue.insert(target, moved);
+        *order = queue;
+        drop(order);
+        self.persist().await;
+        true
+    }
+
+    /// Write stars, hidden torrents and queue order to the store file
+    async fn persist(&self) {
+        let data = StoreData {
+            starred: self.starred_torrents.read().await.iter().cloned().collect(),
+            hidden: self.hidden_torrents.read().await.iter().cloned().collect(),
+            queue: self.queue_order.read().await.clone(),
+        };
+        self.store.schedule(data);
+    }
+
+    /// Write stars and queue order now instead of after [`STORE_DEBOUNCE`]
+    pub async fn flush(&self) {
+        self.store.flush().await;
+    }
+
+    /// Write scheduled store data once changes settle, and a final time on shutdown
+    fn spawn_store_writer(&self) {
+        let store = self.store.clone();
+        let mut shutdown_rx = self.shutdown_tx.subscribe();
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = store.scheduled() => {}
+                    _ = shutdown_rx.wait_for(|stopped| *stopped) => break,
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(STORE_DEBOUNCE) => {}
+                    _ = shutdown_rx.wait_for(|stopped| *stopped) => {}
+                }
+                store.flush().await;
+            }
+            store.flush().await;
+        });
+    }
+
+    /// Re-send the cached list to SSE clients, e.g. after a local reorder
+    pub async fn rebroadcast(&self) {
+        // Sent under the lock so a newer snapshot can't go out in between
+        if let Some(cached) = self.last_torrents.read().await.as_ref() {
+            let _ = self.torrents_tx.send(cached.snapshot.clone());
         }
     }
 
@@ -62,12 +404,61 @@ impl AppState {
         self.torrents_tx.subscribe()
     }
 
+    /// Per-torrent updates published by action handlers
+    pub fn subscribe_torrent_events(&self) -> broadcast::Receiver<Arc<Torrent>> {
+        self.torrent_events_tx.subscribe()
+    }
+
+    /// Push one torrent's current state to SSE clients without a full-list render
+    pub fn publish_torrent(&self, torrent: Torrent) {
+        let _ = self.torrent_events_tx.send(Arc::new(torrent));
+    }
+
     pub fn subscribe_stats(&self) -> broadcast::Receiver<Arc<GlobalStats>> {
         self.stats_tx.subscribe()
     }
 
+    /// Torrents that just finished downloading, as seen by the poller
+    pub fn subscribe_completions(&self) -> broadcast::Receiver<Arc<Torrent>> {
+        self.completions_tx.subscribe()
+    }
+
     pub async fn latest_torrents(&self) -> Option<Arc<Vec<Torrent>>> {
-        self.last_torrents.read().await.clone()
+        self.last_torrents.read().await.as_ref().map(|cached| cached.snapshot.clone())
+    }
+
+    /// The cached snapshot if it's recent enough, otherwise a live fetch.
+    ///
+    /// Falls back to the stale snapshot (or an empty list) if rTorrent can't be reached.
+    pub async fn fresh_torrents(&self) -> Arc<Vec<Torrent>> {
+        let cached = self.last_torrents.read().await.clone();
+        if let Some(cached) = &cached {
+            if cached.fetched_at.elapsed() <= MAX_CACHE_AGE {
+                return cached.snapshot.clone();
+            }
+        }
+
+        let generation = next_generation(&self.generation);
+        match self.fetch_torrents().await {
+            Ok(torrents) => {
+                let snapshot = Arc::new(torrents);
+                CachedTorrents::new(snapshot.clone(), generation).store(&self.last_torrents, None).await;
+                snapshot
+            }
+            Err(err) => {
+                tracing::warn!("fresh_torrents: get_torrents failed: {}", err);
+                cached.map(|cached| cached.snapshot).unwrap_or_default()
+            }
+        }
+    }
+
+    /// Live torrent list with smoothed rates, idle times and piece rates applied
+    async fn fetch_torrents(&self) -> crate::error::Result<Vec<Torrent>> {
+        let mut torrents = self.rtorrent.get_torrents().await?;
+        self.rates.apply(&mut torrents);
+        self.activity.apply(&mut torrents);
+        self.pieces.apply(&mut torrents);
+        Ok(torrents)
     }
 
     pub async fn latest_stats(&self) -> Option<Arc<GlobalStats>> {
@@ -77,11 +468,12 @@ impl AppState {
     /// Refresh the torrent cache immediately and broadcast to SSE clients.
     /// Call this after torrent operations (add/remove/pause/resume) to update UI instantly.
     pub async fn refresh_cache(&self) {
-        match self.rtorrent.get_torrents().await {
+        let generation = next_generation(&self.generation);
+        match self.fetch_torrents().await {
             Ok(torrents) => {
-                let snapshot = Arc::new(torrents);
-                *self.last_torrents.write().await = Some(snapshot.clone());
-                let _ = self.torrents_tx.send(snapshot);
+                CachedTorrents::new(Arc::new(torrents), generation)
+                    .store(&self.last_torrents, Some(&self.torrents_tx))
+                    .await;
             }
             Err(err) => {
                 tracing::warn!("refresh_cache: get_torrents failed: {}", err);
@@ -89,15 +481,49 @@ impl AppState {
         }
     }
 
+    /// Refresh the torrent cache after an action on a single torrent.
+    ///
+    /// Only `hash` is published to SSE clients; the full list is left to the
+    /// periodic poll so large lists aren't re-rendered for every click.
+    pub async fn refresh_torrent(&self, hash: &str) {
+        let generation = next_generation(&self.generation);
+        match self.fetch_torrents().await {
+            Ok(torrents) => {
+                let changed = torrents.iter().find(|t| t.hash == hash).cloned();
+                let kept = CachedTorrents::new(Arc::new(torrents), generation).store(&self.last_torrents, None).await;
+                // A newer snapshot already went out; this one would roll the row back
+                if let Some(torrent) = changed.filter(|_| kept) {
+                    self.publish_torrent(torrent);
+                }
+            }
+            Err(err) => {
+                tracing::warn!("refresh_torrent: get_torrents failed: {}", err);
+            }
+        }
+    }
+
     fn spawn_poller(&self, mut shutdown_rx: watch::Receiver<bool>) {
         let rtorrent = self.rtorrent.clone();
         let torrents_tx = self.torrents_tx.clone();
         let stats_tx = self.stats_tx.clone();
+        let completions_tx = self.completions_tx.clone();
         let last_torrents = self.last_torrents.clone();
+        let generation = self.generation.clone();
         let last_stats = self.last_stats.clone();
+        let rtorrent_version = self.rtorrent_version.clone();
+        let connected = self.connected.clone();
+        let rates = self.rates.clone();
+        let activity = self.activity.clone();
+        let pieces = self.pieces.clone();
+        let on_complete = self.config.on_complete_command.clone().filter(|c| !c.trim().is_empty());
+        let on_complete_timeout = Duration::from_secs(self.config.on_complete_timeout_secs);
+        let webhook = Webhook::from_config(&self.config);
+        let mut idle_remover = IdleRemover::from_config(&self.config);
+        let starred_torrents = self.starred_torrents.clone();
 
         tokio::spawn(async move {
-            let mut ticker = interval(Duration::from_secs(2));
+            let mut ticker = interval(POLL_INTERVAL);
+            let mut transitions = TransitionTracker::new();
 
             loop {
                 tokio::select! {
@@ -106,32 +532,76 @@ impl AppState {
                         let need_stats = stats_tx.receiver_count() > 0;
 
                         // Always fetch torrents to get accurate speed data
-                        let torrents_result = rtorrent.get_torrents().await;
+                        let fetch_generation = next_generation(&generation);
+                        let torrents_result = rtorrent.get_torrents().await.map(|mut torrents| {
+                            rates.apply(&mut torrents);
+                            activity.apply(&mut torrents);
+                            pieces.apply(&mut torrents);
+                            torrents
+                        });
+                        let was_connected = connected.swap(torrents_result.is_ok(), Ordering::Relaxed);
+                        
+                        // A restarted rTorrent may be a new version, so ask again on
+                        // every reconnect as well as when the cached one expires
+                        if torrents_result.is_ok() {
+                            let expired = rtorrent_version.read().await.as_ref()
+                                .is_none_or(|cached| cached.fetched_at.elapsed() >= VERSION_TTL);
+                            if !was_connected || expired {
+                                *rtorrent_version.write().await = rtorrent.get_client_version().await
+                                    .map(|version| CachedVersion { version, fetched_at: Instant::now() })
+                                    .ok();
+                            }
+                        }
                         
                         if let Ok(ref torrents) = torrents_result {
-                            if need_torrents {
-                                let snapshot = Arc::new(torrents.clone());
-                                *last_torrents.write().await = Some(snapshot.clone());
-                                let _ = torrents_tx.send(snapshot);
+                            for transition in transitions.update(torrents) {
+                                if let Some(webhook) = &webhook {
+                                    webhook.send(&transition);
+                                }
+                                match transition {
+                                    Transition::Completed(torrent) => {
+                                        tracing::info!("Torrent completed: {}", torrent.name);
+                                        if let Some(command) = &on_complete {
+                                            hooks::spawn_on_complete(
+                                                rtorrent.clone(),
+                                                command.clone(),
+                                                on_complete_timeout,
+                                                torrent.clone(),
+                                            );
+                                        }
+                                        let _ = completions_tx.send(Arc::new(torrent));
+                                    }
+                                    Transition::Errored(torrent) => {
+                                        tracing::warn!("Torrent errored: {}: {}", torrent.name, torrent.message);
+                                    }
+                                    Transition::Added(_) => {}
+                                }
                             }
+
+                            if let Some(idle_remover) = &mut idle_remover {
+                                let starred = starred_torrents.read().await.clone();
+                                idle_remover.apply(&rtorrent, torrents, &starred);
+                            }
+
+                            // The caches are always kept current for readers like
+                            // the widget; only the broadcasts wait for SSE clients
+                            CachedTorrents::new(Arc::new(torrents.clone()), fetch_generation)
+                                .store(&last_torrents, need_torrents.then_some(&torrents_tx))
+                                .await;
                             
-                            // Calculate global rates from individual torrent rates
-                            if need_stats {
-                                let total_down_rate: i64 = torrents.iter().map(|t| t.down_rate).sum();
-                                let total_up_rate: i64 = torrents.iter().map(|t| t.up_rate).sum();
-                                
-                                // Get base stats (disk space, peers) and add calculated rates
-                                match rtorrent.get_global_stats().await {
-                                    Ok(mut stats) => {
-                                        stats.down_rate = total_down_rate;
-                                        stats.up_rate = total_up_rate;
-                                        let snapshot = Arc::new(stats);
-                                        *last_stats.write().await = Some(snapshot.clone());
+                            // Global rates and peer counts are sums over the torrents;
+                            // get base stats (disk space) and add the totals
+                            match rtorrent.get_global_stats().await {
+                                Ok(mut stats) => {
+                                    stats.add_torrent_totals(torrents);
+                                    let snapshot = Arc::new(stats);
+                                    *last_stats.write().await = Some(snapshot.clone());
+                                    if need_stats {
                                         let _ = stats_tx.send(snapshot);
                                     }
-                                    Err(err) => {
-                                        tracing::warn!("poller: get_global_stats failed: {}", err);
-                                    }
+                                }
+                                Err(err) => {
+                                    tracing::warn!("poller: get_global_stats failed: {}", err);
                                 }
                             }
                         } else if let Err(err) = torrents_result {
@@ -148,12 +618,184 @@ impl AppState {
                     }
                 }
             }
+            tracing::debug!("poller stopped");
         });
     }
 }
 
 impl Drop for AppState {
     fn drop(&mut self) {
-        let _ = self.shutdown_tx.send(true);
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use askama::Template;
+
+    /// A state whose rTorrent socket doesn't exist, storing into `dir`
+    fn offline_state(dir: &std::path::Path) -> AppState {
+        let config = Config {
+            scgi_socket: dir.join("missing.sock").display().to_string(),
+            ..Config::default()
+        };
+        AppState::new(config, Store::new(dir.join("state.json")))
+    }
+
+    fn snapshot(hash: &str) -> Arc<Vec<Torrent>> {
+        Arc::new(vec![crate::testing::torrent(hash, hash)])
+    }
+
+    #[tokio::test]
+    async fn interleaved_fetches_cache_and_broadcast_in_generation_order() {
+        let counter = AtomicU64::new(0);
+        let cache = RwLock::new(None);
+        let (tx, mut rx) = broadcast::channel(16);
+
+        // A poll starts, then an action's refresh starts and finishes first
+        let poll = next_generation(&counter);
+        let refresh = next_generation(&counter);
+        assert!(CachedTorrents::new(snapshot("REFRESH"), refresh).store(&cache, Some(&tx)).await);
+        assert!(!CachedTorrents::new(snapshot("POLL"), poll).store(&cache, Some(&tx)).await);
+        // The next poll replaces it
+        let next_poll = next_generation(&counter);
+        assert!(CachedTorrents::new(snapshot("NEXT"), next_poll).store(&cache, Some(&tx)).await);
+
+        let cached = cache.read().await.clone().unwrap();
+        assert_eq!((cached.generation, cached.snapshot[0].hash.as_str()), (next_poll, "NEXT"));
+        assert_eq!(rx.recv().await.unwrap()[0].hash, "REFRESH");
+        assert_eq!(rx.recv().await.unwrap()[0].hash, "NEXT");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_stores_broadcast_increasing_generations() {
+        let cache = Arc::new(RwLock::new(None));
+        let (tx, mut rx) = broadcast::channel(64);
+
+        let stores: Vec<_> = (1..=32u64)
+            .rev()
+            .map(|generation| {
+                let cache = cache.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let snapshot = snapshot(&generation.to_string());
+                    CachedTorrents::new(snapshot, generation).store(&cache, Some(&tx)).await;
+                })
+            })
+            .collect();
+        for store in stores {
+            store.await.unwrap();
+        }
+
+        assert_eq!(cache.read().await.as_ref().unwrap().generation, 32);
+        let mut broadcast = Vec::new();
+        while let Ok(snapshot) = rx.try_recv() {
+            broadcast.push(snapshot[0].hash.parse::<u64>().unwrap());
+        }
+        assert!(broadcast.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", broadcast);
+        assert_eq!(broadcast.last(), Some(&32));
+    }
+
+    #[tokio::test]
+    async fn concurrent_star_toggles_render_what_is_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = Arc::new(offline_state(dir.path()));
+        let rendered = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let toggles: Vec<_> = (0..51)
+            .map(|_| {
+                let state = state.clone();
+                let rendered = rendered.clone();
+                tokio::spawn(async move {
+                    state
+                        .toggle_star_with("ABC", |is_starred| {
+                            rendered.lock().unwrap().push(star_row(is_starred));
+                        })
+                        .await;
+                })
+            })
+            .collect();
+        for toggle in toggles {
+            toggle.await.unwrap();
+        }
+
+        // Rows render in toggle order, so their stars must alternate from the start
+        let rendered = rendered.lock().unwrap().clone();
+        assert_eq!(rendered.len(), 51);
+        for (i, html) in rendered.iter().enumerate() {
+            assert_eq!(html.contains(STARRED_ICON), i % 2 == 0, "row {} after {} toggles", i, i + 1);
+        }
+        // An odd number of toggles leaves it starred, as the last row shows
+        assert!(state.is_starred("ABC").await);
+        assert_eq!(rendered.last(), Some(&star_row(state.is_starred("ABC").await)));
+    }
+
+    /// Class of the filled star, only rendered for starred torrents
+    const STARRED_ICON: &str = "fill-yellow-400";
+
+    /// The row of torrent ABC as the star toggle renders it
+    fn star_row(is_starred: bool) -> String {
+        let torrent = crate::testing::torrent("ABC", "abc");
+        crate::templates::TorrentRowTemplate {
+            torrent: crate::templates::TorrentView::from_torrent(&torrent, is_starred),
+            read_only: false,
+            compact: false,
+        }
+        .render()
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn shutdown_writes_pending_changes_before_the_debounce() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = offline_state(dir.path());
+        let store = Store::new(dir.path().join("state.json"));
+
+        state.toggle_star_with("ABC", |_| ()).await;
+        state.set_hidden("DEF", true, &[testing::torrent("DEF", "def")]).await;
+        state.shutdown();
+
+        // Well inside STORE_DEBOUNCE, so only the shutdown can have written it
+        tokio::time::timeout(STORE_DEBOUNCE / 2, async {
+            while store.load().starred.is_empty() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let stored = store.load();
+        assert_eq!(stored.starred, ["ABC"]);
+        assert_eq!(stored.hidden, ["DEF"]);
+    }
+
+    #[tokio::test]
+    async fn flush_writes_pending_changes_immediately() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = offline_state(dir.path());
+
+        state.toggle_star_with("ABC", |_| ()).await;
+        state.flush().await;
+
+        assert_eq!(Store::new(dir.path().join("state.json")).load().starred, ["ABC"]);
+    }
+
+    #[tokio::test]
+    async fn hiding_drops_hashes_of_removed_torrents() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = offline_state(dir.path());
+        let both = [testing::torrent("GONE", "gone"), testing::torrent("KEPT", "kept")];
+
+        state.set_hidden("GONE", true, &both).await;
+        state.set_hidden("KEPT", true, &both).await;
+        // GONE was removed from rTorrent since
+        assert!(!state.set_hidden("KEPT", true, &both[1..]).await);
+        state.flush().await;
+
+        assert!(!state.is_hidden("GONE").await);
+        assert!(state.is_hidden("KEPT").await);
+        assert_eq!(Store::new(dir.path().join("state.json")).load().hidden, ["KEPT"]);
     }
 }
@@ -0,0 +1,152 @@
+//! Persisted UI state
+//!
+//! Stars, hidden torrents and queue order are VibeTorrent's own layer over
+//! rTorrent, so they are kept in a small JSON file next to the config and
+//! survive restarts.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StoreData {
+    /// Hashes of starred torrents
+    #[serde(default)]
+    pub starred: Vec<String>,
+    /// Hashes of torrents left out of every list but the hidden filter
+    #[serde(default)]
+    pub hidden: Vec<String>,
+    /// Explicit queue order, first = position 1. Torrents not listed follow in
+    /// the order rTorrent loaded them.
+    #[serde(default)]
+    pub queue: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Store {
+    path: PathBuf,
+    /// Latest data handed to [`Store::schedule`] and not yet written, with
+    /// its sequence number
+    pending: Mutex<Option<(u64, StoreData)>>,
+    /// Sequence number of the next [`Store::schedule`]
+    next_seq: Mutex<u64>,
+    /// Sequence number of the data on disk. Held across each write, so a
+    /// flush that took older data can't write it over a newer one.
+    written: tokio::sync::Mutex<u64>,
+    scheduled: Notify,
+}
+
+impl Store {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            pending: Mutex::new(None),
+            next_seq: Mutex::new(1),
+            written: tokio::sync::Mutex::new(0),
+            scheduled: Notify::new(),
+        }
+    }
+
+    /// Read the stored data; a missing or unreadable file starts empty
+    pub fn load(&self) -> StoreData {
+        let content = match std::fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return StoreData::default(),
+            Err(e) => {
+                tracing::warn!("Failed to read {}: {}", self.path.display(), e);
+                return StoreData::default();
+            }
+        };
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring malformed {}: {}", self.path.display(), e);
+            StoreData::default()
+        })
+    }
+
+    /// Directory next to the state file for `.torrent` files uploaded to re-tie
+    /// torrents, which rTorrent then reads from
+    pub fn torrents_dir(&self) -> PathBuf {
+        self.path.with_file_name("torrents")
+    }
+
+    /// Queue `data` for the next [`Store::flush`]; newer data replaces older
+    pub fn schedule(&self, data: StoreData) {
+        let mut next_seq = self.next_seq.lock().unwrap_or_else(|e| e.into_inner());
+        let seq = *next_seq;
+        *next_seq += 1;
+        // Replaced under the sequence lock, so pending always holds the newest
+        *self.pending.lock().unwrap_or_else(|e| e.into_inner()) = Some((seq, data));
+        drop(next_seq);
+        self.scheduled.notify_one();
+    }
+
+    /// Resolves once data has been scheduled since the last call
+    pub async fn scheduled(&self) {
+        self.scheduled.notified().await;
+    }
+
+    /// Write scheduled data now, if there is any, off the async runtime
+    pub async fn flush(&self) {
+        let Some((seq, data)) = self.pending.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+            return;
+        };
+        let mut written = self.written.lock().await;
+        if seq <= *written {
+            return;
+        }
+        let path = self.path.clone();
+        match tokio::task::spawn_blocking(move || save(&path, &data)).await {
+            Ok(Ok(())) => *written = seq,
+            Ok(Err(e)) => tracing::warn!("{}", e),
+            Err(e) => tracing::warn!("Failed to write {}: {}", self.path.display(), e),
+        }
+    }
+}
+
+/// Write atomically: a crash mid-save must not wipe everyone's stars
+fn save(path: &Path, data: &StoreData) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(data)
+        .map_err(|e| format!("Failed to serialize state: {}", e))?;
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+
+    let tmp = tmp_path(path);
+    std::fs::write(&tmp, content)
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn starred(hash: &str) -> StoreData {
+        StoreData { starred: vec![hash.to_string()], ..StoreData::default() }
+    }
+
+    #[tokio::test]
+    async fn a_flush_of_older_data_does_not_overwrite_newer() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path().join("state.json"));
+
+        store.schedule(starred("OLD"));
+        // What a flush that started before the next schedule would be holding
+        let stale = store.pending.lock().unwrap().take().unwrap();
+        store.schedule(starred("NEW"));
+        store.flush().await;
+        *store.pending.lock().unwrap() = Some(stale);
+        store.flush().await;
+
+        assert_eq!(store.load().starred, ["NEW"]);
+    }
+}
@@ -1,5 +1,5 @@
 use askama::Template;
-use crate::rtorrent::{Torrent, GlobalStats, TorrentState};
+use crate::rtorrent::{format_bytes, Torrent, GlobalStats, NetworkInfo, PeerDiscovery, SessionInfo, TorrentState, Tracker, TrackerScrape, TransferLimits};
 use std::sync::LazyLock;
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -28,18 +28,40 @@ pub struct SetupTemplate {
 pub struct IndexTemplate {
     pub stats: GlobalStats,
     pub torrents: Vec<TorrentView>,
+    /// Torrents past `default_limit`, behind "Show all"
+    pub hidden_count: usize,
+    /// Totals for the label being viewed, if any
+    pub group: Option<GroupView>,
     pub total_count: usize,
     pub downloading_count: usize,
     pub seeding_count: usize,
     pub paused_count: usize,
+    pub stopped_count: usize,
+    pub errored_count: usize,
+    /// Torrents hidden by hand, listed only under the `hidden` filter
+    pub hidden_torrents_count: usize,
+    pub private_count: usize,
+    pub public_count: usize,
     pub rtorrent_version: String,
     pub cache_version: String,
+    pub read_only: bool,
+    /// Stacked, touch-friendly rows (`?mobile=1`) instead of the responsive grid
+    pub compact: bool,
+    /// Required rTorrent methods the connected client lacks
+    pub missing_methods: Vec<&'static str>,
 }
 
 #[derive(Template)]
 #[template(path = "partials/torrent_list.html")]
 pub struct TorrentListTemplate {
     pub torrents: Vec<TorrentView>,
+    /// Torrents past the limit, behind "Show all"
+    pub hidden_count: usize,
+    /// Totals for the label being viewed, if any
+    pub group: Option<GroupView>,
+    pub read_only: bool,
+    /// Stacked, touch-friendly rows instead of the responsive grid
+    pub compact: bool,
 }
 
 #[derive(Template)]
@@ -48,16 +70,204 @@ pub struct StatsTemplate {
     pub stats: GlobalStats,
 }
 
+/// Out-of-band updates for just the header stats that changed
+#[derive(Template)]
+#[template(path = "partials/stats_delta.html")]
+pub struct StatsDeltaTemplate {
+    /// Element id in `stats.html` and its new text
+    pub changed: Vec<(&'static str, String)>,
+}
+
+/// Self-contained status fragment for embedding elsewhere
+#[derive(Template)]
+#[template(path = "partials/widget.html")]
+pub struct WidgetTemplate {
+    pub total_count: usize,
+    /// `None` until the poller has produced stats
+    pub stats: Option<GlobalStats>,
+    pub connected: bool,
+}
+
 #[derive(Template)]
 #[template(path = "partials/torrent_row.html")]
 pub struct TorrentRowTemplate {
     pub torrent: TorrentView,
+    pub read_only: bool,
+    /// Set when the action came from a compact card (`?mobile=1`), so the
+    /// row swapped back in is compact too
+    pub compact: bool,
+}
+
+/// A single row swapped out-of-band into whatever list the client shows
+#[derive(Template)]
+#[template(path = "partials/torrent_oob.html")]
+pub struct TorrentOobTemplate {
+    pub torrent: TorrentView,
+    pub read_only: bool,
+    pub compact: bool,
 }
 
 #[derive(Template)]
 #[template(path = "partials/add_torrent_modal.html")]
 pub struct AddTorrentModalTemplate;
 
+/// Asks before removing, optionally with the downloaded data
+#[derive(Template)]
+#[template(path = "partials/confirm_remove_modal.html")]
+pub struct ConfirmRemoveModalTemplate {
+    pub torrent: TorrentView,
+}
+
+/// Outcome of one input submitted through the add modal
+#[derive(Debug, Clone)]
+pub struct AddOutcome {
+    /// File name or (shortened) URL, as the user would recognise it
+    pub input: String,
+    pub accepted: bool,
+    /// Rejection reason; empty when accepted
+    pub reason: String,
+}
+
+/// Per-input results shown inside the add modal
+#[derive(Template)]
+#[template(path = "partials/add_torrent_result.html")]
+pub struct AddTorrentResultTemplate {
+    pub outcomes: Vec<AddOutcome>,
+    /// Rendered out-of-band next to a list response
+    pub oob: bool,
+}
+
+#[derive(Template)]
+#[template(path = "partials/torrent_details_modal.html")]
+pub struct TorrentDetailsTemplate {
+    pub torrent: TorrentView,
+    pub scrape: ScrapeView,
+    pub tracker_list: TrackerListView,
+    pub tie: TieView,
+    /// Current download directory, offered for editing while incomplete;
+    /// `None` when read-only, complete or unknown
+    pub directory: Option<String>,
+    pub is_hidden: bool,
+    pub read_only: bool,
+}
+
+/// Tracker-reported swarm size for the detail view
+#[derive(Debug, Clone)]
+pub struct ScrapeView {
+    pub hash: String,
+    /// e.g. "12 seeds / 3 leechers (tracker)"
+    pub summary: String,
+    /// Whether any tracker reported counts (dims the summary otherwise)
+    pub reported: bool,
+    pub can_refresh: bool,
+}
+
+impl ScrapeView {
+    /// Summarize per-tracker scrapes, taking the largest counts any tracker reports
+    pub fn new(hash: &str, scrapes: Option<&[TrackerScrape]>, can_refresh: bool) -> Self {
+        let reporting: Vec<&TrackerScrape> = scrapes.unwrap_or_default().iter().filter(|s| s.has_data()).collect();
+        let summary = match scrapes {
+            None => "Tracker scrape unavailable".to_string(),
+            Some([]) => "No trackers".to_string(),
+            Some(_) if reporting.is_empty() => "Trackers haven't reported seeds/leechers".to_string(),
+            Some(_) => {
+                let seeders = reporting.iter().map(|s| s.seeders).max().unwrap_or(0);
+                let leechers = reporting.iter().map(|s| s.leechers).max().unwrap_or(0);
+                format!(
+                    "{} seed{} / {} leecher{} (tracker)",
+                    seeders,
+                    if seeders == 1 { "" } else { "s" },
+                    leechers,
+                    if leechers == 1 { "" } else { "s" },
+                )
+            }
+        };
+        Self {
+            hash: hash.to_string(),
+            summary,
+            reported: !reporting.is_empty(),
+            can_refresh,
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "partials/tracker_scrape.html")]
+pub struct TrackerScrapeTemplate {
+    pub scrape: ScrapeView,
+}
+
+/// The `.torrent` file a torrent is tied to, for the detail view
+#[derive(Debug, Clone)]
+pub struct TieView {
+    pub hash: String,
+    /// `None` when untied (added from a magnet, or the tie was lost) or unknown
+    pub path: Option<String>,
+    /// Tied to a file VibeTorrent can't find
+    pub missing: bool,
+    /// Offer uploading a `.torrent` to tie to; off when read-only
+    pub can_retie: bool,
+}
+
+impl TieView {
+    /// `missing` says whether `tied_to_file` is gone from disk; the handler
+    /// checks, so rendering never blocks on the filesystem
+    pub fn new(hash: &str, tied_to_file: Option<&str>, missing: bool, read_only: bool) -> Self {
+        let path = tied_to_file.filter(|path| !path.is_empty()).map(str::to_string);
+        let missing = path.is_some() && missing;
+        Self {
+            hash: hash.to_string(),
+            can_retie: !read_only && (path.is_none() || missing),
+            path,
+            missing,
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "partials/tied_file.html")]
+pub struct TiedFileTemplate {
+    pub tie: TieView,
+}
+
+/// A torrent's announce URLs for the detail view's tracker editor
+#[derive(Debug, Clone)]
+pub struct TrackerListView {
+    pub hash: String,
+    /// `None` when rTorrent couldn't list them
+    pub trackers: Option<Vec<Tracker>>,
+    /// Offer adding and removing; off when read-only
+    pub editable: bool,
+}
+
+#[derive(Template)]
+#[template(path = "partials/tracker_list.html")]
+pub struct TrackerListTemplate {
+    pub tracker_list: TrackerListView,
+}
+
+#[derive(Template)]
+#[template(path = "partials/settings_modal.html")]
+pub struct SettingsModalTemplate {
+    pub limits: TransferLimits,
+    /// `None` when rTorrent couldn't be asked or has no DHT
+    pub discovery: Option<PeerDiscovery>,
+    /// `None` when rTorrent couldn't be asked
+    pub network: Option<NetworkInfo>,
+    /// `None` when rTorrent couldn't be asked
+    pub session: Option<SessionInfo>,
+    pub rtorrent_version: String,
+    pub read_only: bool,
+}
+
+/// DHT/PEX section of the settings modal, re-rendered after each toggle
+#[derive(Template)]
+#[template(path = "partials/peer_discovery.html")]
+pub struct PeerDiscoveryTemplate {
+    pub discovery: Option<PeerDiscovery>,
+    pub read_only: bool,
+}
+
 #[derive(Template)]
 #[template(path = "partials/sidebar_counts.html")]
 pub struct SidebarCountsTemplate {
@@ -65,43 +275,337 @@ pub struct SidebarCountsTemplate {
     pub downloading_count: usize,
     pub seeding_count: usize,
     pub paused_count: usize,
+    pub stopped_count: usize,
+    pub errored_count: usize,
+    /// Torrents hidden by hand, listed only under the `hidden` filter
+    pub hidden_torrents_count: usize,
+    pub private_count: usize,
+    pub public_count: usize,
 }
 
-/// View model for torrent display
+/// Totals for a group of torrents (a label), shown above its list
 #[derive(Clone)]
+pub struct GroupView {
+    pub name: String,
+    pub count: usize,
+    pub total_size: String,
+    pub uploaded: String,
+    /// Mean of the torrents' ratios
+    pub avg_ratio: String,
+}
+
+impl GroupView {
+    pub fn from_torrents(name: &str, torrents: &[Torrent]) -> Self {
+        let avg_ratio = if torrents.is_empty() {
+            0.0
+        } else {
+            torrents.iter().map(|t| t.ratio).sum::<f64>() / torrents.len() as f64
+        };
+        Self {
+            name: name.to_string(),
+            count: torrents.len(),
+            total_size: format_bytes(torrents.iter().map(|t| t.size_bytes).sum()),
+            uploaded: format_bytes(torrents.iter().map(|t| t.bytes_uploaded).sum()),
+            avg_ratio: format!("{:.2}", avg_ratio),
+        }
+    }
+}
+
+/// View model for torrent display
+#[derive(Clone, serde::Serialize)]
 pub struct TorrentView {
     pub hash: String,
     pub name: String,
+    /// Empty when the torrent has no label
+    pub label: String,
     pub size: String,
+    /// Hash check progress while `is_hashing`, download progress otherwise
     pub progress: f64,
     pub progress_rounded: i32,
     pub status: String,
     pub progress_bar_class: String,
     pub down_rate: String,
     pub up_rate: String,
+    pub uploaded: String,
+    pub downloaded: String,
     pub eta: String,
+    /// ETA at the current (unsmoothed) rate, for the tooltip
+    pub eta_instant: String,
     pub ratio: String,
+    /// Both progress measures, for the detail view
+    pub progress_total: String,
+    pub progress_wanted: String,
     pub is_paused: bool,
+    pub is_stopped: bool,
+    pub is_hashing: bool,
+    /// rTorrent's `d.message`: the error reason, or tracker status
+    pub message: String,
+    /// `message` explains the Error state rather than being informational
+    pub message_is_error: bool,
     pub is_starred: bool,
+    pub is_forced: bool,
+    pub is_private: bool,
+    pub is_done_seeding: bool,
+    pub is_auto_stopped: bool,
+    /// Empty when no reason was given or it wasn't auto-stopped
+    pub auto_stop_reason: String,
+    /// 1-based position in VibeTorrent's queue, 0 when unknown
+    pub queue_position: usize,
+    pub awaiting_metadata: bool,
+    pub metadata_pending: String,
+    /// "—" until the download has finished
+    pub seeding_time: String,
+    pub idle_time: String,
+    /// Detail view only; "—" while not downloading
+    pub piece_rate: String,
 }
 
 impl TorrentView {
     pub fn from_torrent(torrent: &Torrent, is_starred: bool) -> Self {
-        let progress = torrent.progress_percent();
+        let progress = torrent.hashing_percent().unwrap_or_else(|| torrent.progress_percent());
+        // Without metadata there's no size, so size and ETA would read "0 B" / "∞"
+        let awaiting_metadata = torrent.is_awaiting_metadata();
+        let placeholder = || "—".to_string();
         Self {
             hash: torrent.hash.clone(),
-            name: torrent.name.clone(),
-            size: torrent.size_formatted(),
+            name: torrent.display_name().to_string(),
+            label: torrent.label.clone(),
+            size: if awaiting_metadata { placeholder() } else { torrent.size_formatted() },
             progress,
             progress_rounded: progress.round() as i32,
             status: torrent.status_text().to_string(),
             progress_bar_class: torrent.progress_bar_class().to_string(),
             down_rate: torrent.down_rate_formatted(),
             up_rate: torrent.up_rate_formatted(),
-            eta: torrent.eta().unwrap_or_else(|| "∞".to_string()),
+            uploaded: torrent.uploaded_formatted(),
+            downloaded: torrent.downloaded_formatted(),
+            eta: if awaiting_metadata {
+                placeholder()
+            } else {
+                torrent.eta().unwrap_or_else(|| "∞".to_string())
+            },
+            eta_instant: if awaiting_metadata {
+                placeholder()
+            } else {
+                torrent.eta_instant().unwrap_or_else(|| "∞".to_string())
+            },
             ratio: format!("{:.1}", torrent.ratio),
+            progress_total: format!("{:.1}%", torrent.total_progress_percent()),
+            progress_wanted: format!("{:.1}%", torrent.wanted_progress_percent()),
             is_paused: torrent.state == TorrentState::Paused,
+            is_stopped: torrent.state == TorrentState::Stopped,
+            is_hashing: torrent.is_hashing,
+            message: if torrent.message == "0" { String::new() } else { torrent.message.clone() },
+            message_is_error: torrent.state == TorrentState::Error,
+            is_private: torrent.is_private,
+            is_done_seeding: torrent.is_done_seeding(),
+            is_auto_stopped: torrent.is_auto_stopped(),
+            auto_stop_reason: torrent.auto_stopped.clone().unwrap_or_default(),
             is_starred,
+            is_forced: torrent.is_forced,
+            queue_position: 0,
+            awaiting_metadata,
+            metadata_pending: torrent.metadata_pending_for().unwrap_or_default(),
+            seeding_time: torrent.seeding_time_formatted().unwrap_or_else(placeholder),
+            idle_time: torrent.idle_time_formatted().unwrap_or_else(placeholder),
+            piece_rate: torrent.chunk_rate_formatted().unwrap_or_else(placeholder),
+        }
+    }
+
+    pub fn with_queue_position(mut self, position: usize) -> Self {
+        self.queue_position = position;
+        self
+    }
+}
+
+/// Render every template once with sample data.
+///
+/// Askama checks syntax at compile time, but view methods and
+/// `--templates-dir` overrides can still fail while rendering. Running this
+/// at startup turns that into a startup error instead of failing requests.
+/// Returns how many renders were checked.
+pub fn self_test() -> Result<usize, String> {
+    let downloading = sample_torrent(TorrentState::Downloading);
+    let hashing = sample_torrent(TorrentState::Hashing);
+    let view = TorrentView::from_torrent(&downloading, true).with_queue_position(1);
+    let views = vec![view.clone(), TorrentView::from_torrent(&hashing, false).with_queue_position(2)];
+    let stats = GlobalStats::empty(downloading.rate_unit);
+    crate::theme::check(&view, &stats)?;
+
+    let hash = downloading.hash.clone();
+    let scrapes = [TrackerScrape {
+        url: "udp://tracker.example.org:1337/announce".to_string(),
+        is_enabled: true,
+        seeders: 12,
+        leechers: 3,
+        last_scrape: 1_700_000_000,
+    }];
+    let trackers = vec![Tracker { index: 0, url: scrapes[0].url.clone(), is_enabled: true }];
+    let discovery = PeerDiscovery { dht_mode: "auto".to_string(), pex: true };
+
+    let results = [
+        ("setup.html", SetupTemplate {
+            scgi_socket: "/tmp/rtorrent.sock".to_string(),
+            bind_address: "0.0.0.0:3000".to_string(),
+            error: Some("Sample error".to_string()),
+            cache_version: CACHE_VERSION.clone(),
+        }.render()),
+        ("index.html", IndexTemplate {
+            stats: stats.clone(),
+            torrents: views.clone(),
+            hidden_count: 1,
+            group: Some(GroupView::from_torrents(&downloading.label, std::slice::from_ref(&downloading))),
+            total_count: 2,
+            downloading_count: 1,
+            seeding_count: 0,
+            paused_count: 0,
+            stopped_count: 0,
+            errored_count: 0,
+            hidden_torrents_count: 0,
+            private_count: 0,
+            public_count: 2,
+            rtorrent_version: "0.9.8".to_string(),
+            cache_version: CACHE_VERSION.clone(),
+            read_only: false,
+            compact: false,
+            missing_methods: vec!["d.multicall2"],
+        }.render()),
+        ("partials/torrent_list.html", TorrentListTemplate {
+            torrents: views,
+            hidden_count: 1,
+            group: None,
+            read_only: false,
+            compact: true,
+        }.render()),
+        ("partials/stats.html", StatsTemplate { stats: stats.clone() }.render()),
+        ("partials/stats_delta.html", StatsDeltaTemplate {
+            changed: vec![("stat-down-rate", stats.down_rate_formatted())],
+        }.render()),
+        ("partials/widget.html", WidgetTemplate { total_count: 2, stats: Some(stats), connected: true }.render()),
+        ("partials/torrent_row.html", TorrentRowTemplate {
+            torrent: view.clone(),
+            read_only: true,
+            compact: false,
+        }.render()),
+        ("partials/torrent_oob.html", TorrentOobTemplate {
+            torrent: view.clone(),
+            read_only: false,
+            compact: true,
+        }.render()),
+        ("partials/add_torrent_modal.html", AddTorrentModalTemplate.render()),
+        ("partials/confirm_remove_modal.html", ConfirmRemoveModalTemplate { torrent: view.clone() }.render()),
+        ("partials/add_torrent_result.html", AddTorrentResultTemplate {
+            outcomes: vec![AddOutcome {
+                input: "sample.torrent".to_string(),
+                accepted: false,
+                reason: "Not a valid .torrent file".to_string(),
+            }],
+            oob: true,
+        }.render()),
+        ("partials/torrent_details_modal.html", TorrentDetailsTemplate {
+            torrent: view,
+            scrape: ScrapeView::new(&hash, Some(&scrapes), true),
+            tracker_list: TrackerListView { hash: hash.clone(), trackers: Some(trackers), editable: true },
+            tie: TieView::new(&hash, None, false, false),
+            directory: Some("/downloads".to_string()),
+            is_hidden: false,
+            read_only: false,
+        }.render()),
+        ("partials/tracker_scrape.html", TrackerScrapeTemplate { scrape: ScrapeView::new(&hash, None, false) }.render()),
+        ("partials/tied_file.html", TiedFileTemplate { tie: TieView::new(&hash, Some("/sample.torrent"), true, false) }.render()),
+        ("partials/tracker_list.html", TrackerListTemplate {
+            tracker_list: TrackerListView { hash: hash.clone(), trackers: None, editable: false },
+        }.render()),
+        ("partials/settings_modal.html", SettingsModalTemplate {
+            limits: TransferLimits::default(),
+            discovery: Some(discovery.clone()),
+            network: Some(NetworkInfo { listen_port: 6881, listen_backlog: 128, port_open: Some(true) }),
+            session: Some(SessionInfo { path: "/session".to_string(), on_completion: true }),
+            rtorrent_version: "0.9.8".to_string(),
+            read_only: false,
+        }.render()),
+        ("partials/peer_discovery.html", PeerDiscoveryTemplate { discovery: Some(discovery), read_only: false }.render()),
+        ("partials/sidebar_counts.html", SidebarCountsTemplate {
+            total_count: 2,
+            downloading_count: 1,
+            seeding_count: 0,
+            paused_count: 0,
+            stopped_count: 0,
+            errored_count: 0,
+            hidden_torrents_count: 0,
+            private_count: 0,
+            public_count: 2,
+        }.render()),
+    ];
+
+    for (name, result) in &results {
+        if let Err(e) = result {
+            return Err(format!("{}: {}", name, e));
+        }
+    }
+    Ok(results.len())
+}
+
+/// A half-done torrent with every optional field filled in
+fn sample_torrent(state: TorrentState) -> Torrent {
+    let is_hashing = state == TorrentState::Hashing;
+    Torrent {
+        hash: if is_hashing { "B".repeat(40) } else { "A".repeat(40) },
+        name: "Sample Torrent".to_string(),
+        size_bytes: 1 << 30,
+        completed_bytes: 1 << 29,
+        down_rate: 1 << 20,
+        avg_down_rate: 1 << 20,
+        up_rate: 1 << 18,
+        state,
+        ratio: 0.5,
+        is_active: true,
+        is_open: true,
+        is_hashing,
+        complete: false,
+        message: "Tracker: [Sample message]".to_string(),
+        load_date: 1_700_000_000,
+        is_forced: false,
+        bytes_uploaded: 1 << 28,
+        bytes_downloaded: 1 << 29,
+        done_seeding: false,
+        auto_stopped: None,
+        label: "sample".to_string(),
+        is_private: false,
+        completed_chunks: 256,
+        size_chunks: 512,
+        wanted_chunks: 256,
+        chunks_hashed: 128,
+        peers_connected: 4,
+        peers_complete: 1,
+        progress_mode: Default::default(),
+        rate_unit: Default::default(),
+        seeding_time: None,
+        idle_time: Some(60),
+        chunk_rate: Some(2.5),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn self_test_renders_every_template() {
+        let source = include_str!("templates.rs");
+        let self_test_body = &source[source.find("pub fn self_test").unwrap()..source.find("fn sample_torrent").unwrap()];
+        let paths: Vec<&str> = source
+            .match_indices("#[template(path = \"")
+            .map(|(start, prefix)| {
+                let path = &source[start + prefix.len()..];
+                &path[..path.find('"').unwrap()]
+            })
+            .collect();
+
+        assert!(!paths.is_empty());
+        for path in &paths {
+            assert!(self_test_body.contains(&format!("(\"{}\", ", path)), "self_test doesn't render {}", path);
         }
+        assert_eq!(self_test(), Ok(paths.len()));
     }
 }
@@ -0,0 +1,196 @@
+//! Test helpers: a scripted rTorrent on a Unix socket and XML-RPC response builders
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+
+use crate::config::Config;
+use crate::rtorrent::{Torrent, TorrentState};
+use crate::state::AppState;
+use crate::store::Store;
+
+type Handler = dyn Fn(&str) -> String + Send + Sync;
+
+/// Answers SCGI requests with whatever `handler` returns for the XML body.
+///
+/// Listens until dropped; every request body is kept for assertions.
+pub struct MockRtorrent {
+    dir: tempfile::TempDir,
+    requests: Arc<Mutex<Vec<String>>>,
+    accept: tokio::task::JoinHandle<()>,
+}
+
+impl MockRtorrent {
+    pub fn start(handler: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let listener = UnixListener::bind(dir.path().join("rtorrent.sock")).unwrap();
+        let handler: Arc<Handler> = Arc::new(handler);
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let accept = {
+            let requests = requests.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let handler = handler.clone();
+                    let requests = requests.clone();
+                    tokio::spawn(async move {
+                        answer(stream, handler.as_ref(), &requests).await;
+                    });
+                }
+            })
+        };
+
+        Self { dir, requests, accept }
+    }
+
+    pub fn socket_path(&self) -> String {
+        self.path().display().to_string()
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.dir.path().join("rtorrent.sock")
+    }
+
+    /// XML bodies received so far
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// `methodName` of every request received so far
+    pub fn methods(&self) -> Vec<String> {
+        self.requests()
+            .iter()
+            .map(|xml| crate::rtorrent::xml_method_name(xml).to_string())
+            .collect()
+    }
+}
+
+impl Drop for MockRtorrent {
+    fn drop(&mut self) {
+        self.accept.abort();
+    }
+}
+
+/// Read one netstring-framed SCGI request, record its body and answer it
+async fn answer(mut stream: UnixStream, handler: &Handler, requests: &Mutex<Vec<String>>) -> Option<()> {
+    let mut len = Vec::new();
+    loop {
+        let byte = stream.read_u8().await.ok()?;
+        if byte == b':' {
+            break;
+        }
+        len.push(byte);
+    }
+    let len: usize = std::str::from_utf8(&len).ok()?.parse().ok()?;
+    let mut headers = vec![0; len + 1];
+    stream.read_exact(&mut headers).await.ok()?;
+
+    let content_length: usize = headers
+        .split(|&b| b == 0)
+        .collect::<Vec<_>>()
+        .windows(2)
+        .find(|pair| pair[0] == b"CONTENT_LENGTH")
+        .and_then(|pair| std::str::from_utf8(pair[1]).ok()?.parse().ok())?;
+    let mut body = vec![0; content_length];
+    stream.read_exact(&mut body).await.ok()?;
+    let body = String::from_utf8(body).ok()?;
+
+    let response = handler(&body);
+    requests.lock().unwrap().push(body);
+    let reply = format!("Status: 200 OK\r\nContent-Type: text/xml\r\n\r\n{}", response);
+    stream.write_all(reply.as_bytes()).await.ok()?;
+    stream.shutdown().await.ok()
+}
+
+/// A `methodResponse` returning `value` (a `<value>` element's contents)
+pub fn response(value: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\"?><methodResponse><params><param><value>{}</value></param></params></methodResponse>",
+        value
+    )
+}
+
+/// A `methodResponse` carrying a fault
+pub fn fault(code: i64, message: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\"?><methodResponse><fault><value><struct>\
+         <member><name>faultCode</name><value><i4>{}</i4></value></member>\
+         <member><name>faultString</name><value><string>{}</string></value></member>\
+         </struct></value></fault></methodResponse>",
+        code, message
+    )
+}
+
+/// An `<array>` of the given `<value>` contents
+pub fn array(values: &[String]) -> String {
+    let values: String = values.iter().map(|v| format!("<value>{}</value>", v)).collect();
+    format!("<array><data>{}</data></array>", values)
+}
+
+/// A torrent list row for an open, active, incomplete torrent with every other
+/// field zero or empty; tests overwrite the fields they care about by index.
+/// `hash` and `name` are inserted as XML, so escape them.
+pub fn torrent_row(hash: &str, name: &str) -> Vec<String> {
+    let mut row: Vec<String> = (0..27).map(|_| "<i8>0</i8>".to_string()).collect();
+    row[0] = format!("<string>{}</string>", hash);
+    row[1] = format!("<string>{}</string>", name);
+    row[2] = "<i8>1000</i8>".to_string();
+    row[6] = "<i8>1</i8>".to_string();
+    row[7] = "<i8>1</i8>".to_string();
+    for text in [10, 16, 17, 19] {
+        row[text] = "<string></string>".to_string();
+    }
+    row
+}
+
+/// A torrent list response holding `rows`
+pub fn torrents_response(rows: &[Vec<String>]) -> String {
+    let rows: Vec<String> = rows.iter().map(|row| array(row)).collect();
+    response(&array(&rows))
+}
+
+/// An `AppState` talking to `mock`, keeping its state file in `dir`
+pub fn app_state(mock: &MockRtorrent, dir: &Path) -> Arc<AppState> {
+    let config = Config { scgi_socket: mock.socket_path(), ..Config::default() };
+    Arc::new(AppState::new(config, Store::new(dir.join("state.json"))))
+}
+
+/// An idle, half-downloaded torrent with everything else zero or empty
+pub fn torrent(hash: &str, name: &str) -> Torrent {
+    Torrent {
+        hash: hash.to_string(),
+        name: name.to_string(),
+        size_bytes: 1000,
+        completed_bytes: 500,
+        down_rate: 0,
+        avg_down_rate: 0,
+        up_rate: 0,
+        state: TorrentState::Downloading,
+        ratio: 0.0,
+        is_active: true,
+        is_open: true,
+        is_hashing: false,
+        complete: false,
+        message: String::new(),
+        load_date: 0,
+        is_forced: false,
+        bytes_uploaded: 0,
+        bytes_downloaded: 0,
+        done_seeding: false,
+        auto_stopped: None,
+        label: String::new(),
+        is_private: false,
+        completed_chunks: 0,
+        size_chunks: 0,
+        wanted_chunks: 0,
+        chunks_hashed: 0,
+        peers_connected: 0,
+        peers_complete: 0,
+        progress_mode: Default::default(),
+        rate_unit: Default::default(),
+        seeding_time: None,
+        idle_time: None,
+        chunk_rate: None,
+    }
+}
@@ -0,0 +1,103 @@
+//! Runtime template overrides
+//!
+//! Askama templates are compiled in, so re-skinning normally means a rebuild.
+//! `--templates-dir` points at a directory laid out like `templates/`; any of the
+//! [`OVERRIDABLE`] partials found there are rendered with MiniJinja instead.
+//! Overrides see the same variables as the compiled partial, with the
+//! `*_formatted()` methods available as plain fields.
+
+use minijinja::{context, Environment, Value};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::rtorrent::GlobalStats;
+use crate::templates::TorrentView;
+
+const TORRENT_ROW: &str = "partials/torrent_row_body.html";
+const STATS: &str = "partials/stats.html";
+
+/// Partials that can be overridden, relative to the templates directory
+pub const OVERRIDABLE: [&str; 2] = [TORRENT_ROW, STATS];
+
+static OVERRIDES: OnceLock<Environment<'static>> = OnceLock::new();
+
+/// Compile the overrides in `dir`. Partials missing there keep the built-in
+/// template; one that fails to compile is an error so a typo shows at startup.
+/// Returns the names of the partials overridden.
+pub fn load(dir: &Path) -> Result<Vec<&'static str>, String> {
+    let mut env = Environment::new();
+    let mut loaded = Vec::new();
+
+    for name in OVERRIDABLE {
+        let path = dir.join(name);
+        let source = match std::fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        env.add_template_owned(name, source)
+            .map_err(|e| format!("Invalid template {}: {}", path.display(), e))?;
+        loaded.push(name);
+    }
+
+    let _ = OVERRIDES.set(env);
+    Ok(loaded)
+}
+
+/// The torrent row body from the override, if there is one
+pub fn torrent_row(torrent: &TorrentView, read_only: &bool, compact: &bool) -> Option<String> {
+    render(TORRENT_ROW, || torrent_row_context(torrent, *read_only, *compact))
+}
+
+/// The header stats from the override, if there is one
+pub fn stats(stats: &GlobalStats) -> Option<String> {
+    render(STATS, || stats_context(stats))
+}
+
+/// Render the loaded overrides once with the given data. Compiling catches
+/// syntax errors in [`load`]; this catches what only fails at render time,
+/// such as an unknown filter, which would otherwise fall back on every request.
+pub fn check(torrent: &TorrentView, stats: &GlobalStats) -> Result<(), String> {
+    let results = [
+        (TORRENT_ROW, try_render(TORRENT_ROW, || torrent_row_context(torrent, false, false))),
+        (STATS, try_render(STATS, || stats_context(stats))),
+    ];
+    for (name, result) in results {
+        if let Some(Err(e)) = result {
+            return Err(format!("template override {}: {}", name, e));
+        }
+    }
+    Ok(())
+}
+
+fn torrent_row_context(torrent: &TorrentView, read_only: bool, compact: bool) -> Value {
+    context! { torrent => Value::from_serialize(torrent), read_only => read_only, compact => compact }
+}
+
+fn stats_context(stats: &GlobalStats) -> Value {
+    let formatted = context! {
+        down_rate_formatted => stats.down_rate_formatted(),
+        up_rate_formatted => stats.up_rate_formatted(),
+        free_disk_formatted => stats.free_disk_formatted(),
+    };
+    context! { stats => context! { ..Value::from_serialize(stats), ..formatted } }
+}
+
+/// Whether the header stats come from an override, whose markup may not have
+/// the element ids that partial stat updates target
+pub fn overrides_stats() -> bool {
+    OVERRIDES.get().is_some_and(|env| env.get_template(STATS).is_ok())
+}
+
+/// Render an override, falling back to the built-in template when it errors
+fn render(name: &str, context: impl FnOnce() -> Value) -> Option<String> {
+    try_render(name, context)?
+        .map_err(|e| tracing::warn!("Template override {} failed, using the built-in one: {}", name, e))
+        .ok()
+}
+
+/// `None` when `name` isn't overridden
+fn try_render(name: &str, context: impl FnOnce() -> Value) -> Option<Result<String, minijinja::Error>> {
+    let template = OVERRIDES.get()?.get_template(name).ok()?;
+    Some(template.render(context()))
+}
@@ -0,0 +1,186 @@
+//! Optional HTTPS for the web UI
+//!
+//! With `tls_cert` and `tls_key` in the config, [`serve`] terminates TLS itself
+//! through `axum-server` so simple setups don't need a reverse proxy in front.
+//! SIGHUP re-reads both files (e.g. after a certificate renewal) without a
+//! restart.
+
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use axum_server::Handle;
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::{self, ServerConfig};
+
+/// Read a PEM certificate chain and private key into a server config.
+///
+/// Errors name the file at fault, so they can be shown as-is at startup.
+pub fn load_config(cert: &Path, key: &Path) -> Result<Arc<ServerConfig>, String> {
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Failed to read TLS certificate {}: {}", cert.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificate found in {}", cert.display()));
+    }
+
+    let private_key = PrivateKeyDer::from_pem_file(key)
+        .map_err(|e| format!("Failed to read TLS private key {}: {}", key.display(), e))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("Failed to set up TLS: {}", e))?
+        .with_no_client_auth()
+        .with_single_cert(certs, private_key)
+        .map_err(|e| {
+            format!("Invalid TLS certificate or key ({}, {}): {}", cert.display(), key.display(), e)
+        })?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+    Ok(Arc::new(config))
+}
+
+/// Serve `app` over HTTPS on `listener` until `shutdown` resolves, then wait
+/// for open connections like `axum::serve` does.
+///
+/// `config` is what [`load_config`] read at startup; SIGHUP reloads it from
+/// `cert` and `key`.
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    config: Arc<ServerConfig>,
+    cert: PathBuf,
+    key: PathBuf,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> io::Result<()> {
+    let config = RustlsConfig::from_config(config);
+    reload_on_hangup(config.clone(), cert, key);
+
+    let handle = Handle::new();
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            shutdown.await;
+            handle.graceful_shutdown(None);
+        }
+    });
+
+    axum_server::from_tcp_rustls(listener.into_std()?, config)
+        .handle(handle)
+        .serve(app.into_make_service())
+        .await
+}
+
+/// Swap in a freshly read certificate; if it can't be loaded the current one
+/// stays in use
+fn reload(config: &RustlsConfig, cert: &Path, key: &Path) {
+    match load_config(cert, key) {
+        Ok(loaded) => {
+            config.reload_from_config(loaded);
+            tracing::info!("Reloaded TLS certificate from {}", cert.display());
+        }
+        Err(e) => tracing::warn!("{}; keeping the current certificate", e),
+    }
+}
+
+#[cfg(unix)]
+fn reload_on_hangup(config: RustlsConfig, cert: PathBuf, key: PathBuf) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            tracing::warn!("Failed to listen for SIGHUP, TLS certificate won't be reloaded: {}", e);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            reload(&config, &cert, &key);
+        }
+    });
+}
+
+#[cfg(not(unix))]
+fn reload_on_hangup(_config: RustlsConfig, _cert: PathBuf, _key: PathBuf) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use tokio_rustls::rustls::pki_types::ServerName;
+    use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+    use tokio_rustls::TlsConnector;
+
+    /// Write a new self-signed certificate for localhost and its key into `dir`
+    fn write_cert(dir: &Path) -> (PathBuf, PathBuf, CertificateDer<'static>) {
+        let generated = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let (cert, key) = (dir.join("cert.pem"), dir.join("key.pem"));
+        std::fs::write(&cert, generated.cert.pem()).unwrap();
+        std::fs::write(&key, generated.key_pair.serialize_pem()).unwrap();
+        (cert, key, generated.cert.der().clone())
+    }
+
+    /// Whether a client trusting only `trusted` gets through a handshake with `addr`
+    async fn handshake(addr: SocketAddr, trusted: &CertificateDer<'static>) -> bool {
+        let mut roots = RootCertStore::empty();
+        roots.add(trusted.clone()).unwrap();
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let client = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let server_name = ServerName::try_from("localhost").unwrap();
+        TlsConnector::from(Arc::new(client)).connect(server_name, stream).await.is_ok()
+    }
+
+    #[tokio::test]
+    async fn certificate_loads_and_reloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert, key, first) = write_cert(dir.path());
+        let config = RustlsConfig::from_config(load_config(&cert, &key).unwrap());
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = Handle::new();
+        tokio::spawn(
+            axum_server::from_tcp_rustls(listener, config.clone())
+                .handle(handle.clone())
+                .serve(Router::new().into_make_service()),
+        );
+
+        assert!(handshake(addr, &first).await);
+
+        let (_, _, second) = write_cert(dir.path());
+        reload(&config, &cert, &key);
+        assert!(handshake(addr, &second).await);
+        assert!(!handshake(addr, &first).await);
+
+        // A file that no longer loads leaves the current certificate in place
+        std::fs::write(&key, "not a key").unwrap();
+        reload(&config, &cert, &key);
+        assert!(handshake(addr, &second).await);
+
+        handle.shutdown();
+    }
+
+    #[test]
+    fn load_errors_name_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cert, _, _) = write_cert(dir.path());
+        let missing = dir.path().join("missing.pem");
+
+        let err = load_config(&cert, &missing).unwrap_err();
+        assert!(err.contains("private key") && err.contains("missing.pem"), "{}", err);
+        let err = load_config(&missing, &cert).unwrap_err();
+        assert!(err.contains("certificate") && err.contains("missing.pem"), "{}", err);
+    }
+}
@@ -0,0 +1,334 @@
+//! XML-RPC response decoder
+//!
+//! Turns a `methodResponse` into a tree of typed values, so callers read
+//! multicall rows by position against real types instead of collecting text.
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    /// Also `dateTime.iso8601`, `base64`, `nil` and unknown types, as their text
+    String(String),
+    Double(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+    Struct(BTreeMap<String, Value>),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The call failed on the server; holds the `faultString`
+    #[error("{0}")]
+    Fault(String),
+
+    #[error("XML parse error: {0}")]
+    Malformed(String),
+}
+
+impl Value {
+    /// Integers, booleans and numeric strings (rTorrent isn't consistent)
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Int(i) => Some(*i),
+            Value::Bool(b) => Some(*b as i64),
+            Value::String(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Booleans and rTorrent's 0/1 integer flags
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            other => other.as_i64().map(|i| i != 0),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn into_string(self) -> Option<String> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn into_array(self) -> Option<Vec<Value>> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Look up a member if this is a struct
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Struct(members) => members.get(key),
+            _ => None,
+        }
+    }
+}
+
+/// Decode a `methodResponse`: its single return value, or the fault it carries
+pub fn parse_response(xml: &str) -> Result<Value, Error> {
+    let mut parser = Parser { reader: Reader::from_str(xml) };
+    let mut in_fault = false;
+
+    loop {
+        match parser.next()? {
+            Event::Start(e) if e.name().as_ref() == b"fault" => in_fault = true,
+            Event::Start(e) if e.name().as_ref() == b"value" => {
+                let value = parser.value(0)?;
+                if in_fault {
+                    let message = value
+                        .get("faultString")
+                        .and_then(Value::as_str)
+                        .unwrap_or("Unknown XML-RPC fault");
+                    return Err(Error::Fault(message.to_string()));
+                }
+                return Ok(value);
+            }
+            Event::Empty(e) if e.name().as_ref() == b"value" => {
+                return Ok(Value::String(String::new()));
+            }
+            Event::Eof => return Err(malformed("no value in response")),
+            _ => {}
+        }
+    }
+}
+
+/// Nesting limit so a hostile response can't overflow the stack
+const MAX_DEPTH: usize = 32;
+
+struct Parser<'a> {
+    reader: Reader<&'a [u8]>,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Result<Event<'a>, Error> {
+        self.reader.read_event().map_err(malformed)
+    }
+
+    /// Contents of a `<value>` whose start tag was just read, up to and
+    /// including `</value>`
+    fn value(&mut self, depth: usize) -> Result<Value, Error> {
+        if depth > MAX_DEPTH {
+            return Err(malformed("nesting too deep"));
+        }
+
+        // Untyped content is a string; whitespace around a type tag is not
+        let mut text = String::new();
+        loop {
+            match self.next()? {
+                Event::Start(e) => {
+                    let tag = e.name().as_ref().to_vec();
+                    let value = match tag.as_slice() {
+                        b"array" => Value::Array(self.array(depth)?),
+                        b"struct" => Value::Struct(self.members(depth)?),
+                        _ => scalar(&tag, self.text()?)?,
+                    };
+                    self.end()?;
+                    return Ok(value);
+                }
+                Event::Empty(e) => {
+                    let value = match e.name().as_ref() {
+                        b"array" => Value::Array(Vec::new()),
+                        b"struct" => Value::Struct(BTreeMap::new()),
+                        tag => scalar(tag, String::new())?,
+                    };
+                    self.end()?;
+                    return Ok(value);
+                }
+                Event::Text(e) => text.push_str(&e.unescape().map_err(malformed)?),
+                Event::CData(e) => text.push_str(&String::from_utf8_lossy(&e)),
+                Event::End(_) => return Ok(Value::String(text)),
+                Event::Eof => return Err(malformed("unexpected end of document")),
+                _ => {}
+            }
+        }
+    }
+
+    /// Items of an `<array>`, up to and including `</array>`
+    fn array(&mut self, depth: usize) -> Result<Vec<Value>, Error> {
+        let mut items = Vec::new();
+        loop {
+            match self.next()? {
+                Event::Start(e) if e.name().as_ref() == b"value" => items.push(self.value(depth + 1)?),
+                Event::Empty(e) if e.name().as_ref() == b"value" => items.push(Value::String(String::new())),
+                Event::End(e) if e.name().as_ref() == b"array" => return Ok(items),
+                Event::Eof => return Err(malformed("unterminated array")),
+                // <data>, </data> and whitespace
+                _ => {}
+            }
+        }
+    }
+
+    /// Members of a `<struct>`, up to and including `</struct>`
+    fn members(&mut self, depth: usize) -> Result<BTreeMap<String, Value>, Error> {
+        let mut members = BTreeMap::new();
+        let mut name = String::new();
+        loop {
+            match self.next()? {
+                Event::Start(e) if e.name().as_ref() == b"name" => name = self.text()?,
+                Event::Start(e) if e.name().as_ref() == b"value" => {
+                    members.insert(std::mem::take(&mut name), self.value(depth + 1)?);
+                }
+                Event::Empty(e) if e.name().as_ref() == b"value" => {
+                    members.insert(std::mem::take(&mut name), Value::String(String::new()));
+                }
+                Event::End(e) if e.name().as_ref() == b"struct" => return Ok(members),
+                Event::Eof => return Err(malformed("unterminated struct")),
+                // <member>, </member> and whitespace
+                _ => {}
+            }
+        }
+    }
+
+    /// Text up to the end tag of the element just opened, consuming it
+    fn text(&mut self) -> Result<String, Error> {
+        let mut text = String::new();
+        loop {
+            match self.next()? {
+                Event::Text(e) => text.push_str(&e.unescape().map_err(malformed)?),
+                Event::CData(e) => text.push_str(&String::from_utf8_lossy(&e)),
+                Event::End(_) => return Ok(text),
+                Event::Start(_) | Event::Empty(_) => return Err(malformed("unexpected element in scalar")),
+                Event::Eof => return Err(malformed("unexpected end of document")),
+                _ => {}
+            }
+        }
+    }
+
+    /// Skip whitespace up to `</value>`
+    fn end(&mut self) -> Result<(), Error> {
+        loop {
+            match self.next()? {
+                Event::End(e) if e.name().as_ref() == b"value" => return Ok(()),
+                Event::Text(e) if e.iter().all(u8::is_ascii_whitespace) => {}
+                Event::Comment(_) => {}
+                _ => return Err(malformed("expected </value>")),
+            }
+        }
+    }
+}
+
+fn scalar(tag: &[u8], text: String) -> Result<Value, Error> {
+    let invalid = || malformed(format!("invalid {} {:?}", String::from_utf8_lossy(tag), text));
+    match tag {
+        b"i4" | b"i8" | b"int" => text.trim().parse().map(Value::Int).map_err(|_| invalid()),
+        b"double" => text.trim().parse().map(Value::Double).map_err(|_| invalid()),
+        b"boolean" => match text.trim() {
+            "1" => Ok(Value::Bool(true)),
+            "0" => Ok(Value::Bool(false)),
+            _ => Err(invalid()),
+        },
+        _ => Ok(Value::String(text)),
+    }
+}
+
+fn malformed(reason: impl std::fmt::Display) -> Error {
+    Error::Malformed(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{fault, response};
+
+    fn parse(value: &str) -> Result<Value, Error> {
+        parse_response(&response(value))
+    }
+
+    #[test]
+    fn scalars_decode_to_their_types() {
+        assert_eq!(parse("<i4>-7</i4>").unwrap(), Value::Int(-7));
+        assert_eq!(parse("<i8>4294967296</i8>").unwrap(), Value::Int(4_294_967_296));
+        assert_eq!(parse("<int> 42 </int>").unwrap(), Value::Int(42));
+        assert_eq!(parse("<double>1.5</double>").unwrap(), Value::Double(1.5));
+        assert_eq!(parse("<boolean>1</boolean>").unwrap(), Value::Bool(true));
+        assert_eq!(parse("<boolean>0</boolean>").unwrap(), Value::Bool(false));
+        assert_eq!(parse("<string>a &amp; b</string>").unwrap(), Value::String("a & b".to_string()));
+        assert_eq!(parse("<base64>AAEC</base64>").unwrap(), Value::String("AAEC".to_string()));
+        assert_eq!(parse("<nil/>").unwrap(), Value::String(String::new()));
+        // Untyped text is a string, whitespace and all
+        assert_eq!(parse(" untyped ").unwrap(), Value::String(" untyped ".to_string()));
+        // Whitespace around a type tag is not
+        assert_eq!(parse("\n  <i8>3</i8>\n").unwrap(), Value::Int(3));
+    }
+
+    #[test]
+    fn empty_values_are_empty_strings() {
+        let xml = "<methodResponse><params><param><value/></param></params></methodResponse>";
+        assert_eq!(parse_response(xml).unwrap(), Value::String(String::new()));
+        assert_eq!(parse("").unwrap(), Value::String(String::new()));
+        assert_eq!(parse("<string/>").unwrap(), Value::String(String::new()));
+        assert_eq!(parse("<array><data><value/></data></array>").unwrap(), Value::Array(vec![Value::String(String::new())]));
+    }
+
+    #[test]
+    fn arrays_and_structs_nest() {
+        let value = parse(
+            "<array><data>\
+                <value><array><data><value><i8>1</i8></value><value><string>x</string></value></data></array></value>\
+                <value><struct>\
+                    <member><name>rows</name><value><array><data/></array></value></member>\
+                    <member><name>ok</name><value><boolean>1</boolean></value></member>\
+                </struct></value>\
+             </data></array>",
+        )
+        .unwrap();
+
+        let items = value.into_array().unwrap();
+        assert_eq!(items[0], Value::Array(vec![Value::Int(1), Value::String("x".to_string())]));
+        assert_eq!(items[1].get("rows"), Some(&Value::Array(Vec::new())));
+        assert_eq!(items[1].get("ok"), Some(&Value::Bool(true)));
+    }
+
+    #[test]
+    fn faults_carry_their_message() {
+        match parse_response(&fault(-506, "Method 'x' not defined")) {
+            Err(Error::Fault(message)) => assert_eq!(message, "Method 'x' not defined"),
+            other => panic!("expected a fault, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn nesting_past_the_limit_is_an_error() {
+        let depth = 10_000;
+        let value = format!(
+            "{}<i8>1</i8>{}",
+            "<array><data><value>".repeat(depth),
+            "</value></data></array>".repeat(depth)
+        );
+        assert!(matches!(parse(&value), Err(Error::Malformed(_))));
+
+        let within = format!(
+            "{}<i8>1</i8>{}",
+            "<array><data><value>".repeat(MAX_DEPTH),
+            "</value></data></array>".repeat(MAX_DEPTH)
+        );
+        assert!(parse(&within).is_ok());
+    }
+
+    #[test]
+    fn truncated_or_malformed_xml_is_an_error() {
+        // Cut before the value, inside a scalar and inside the array
+        let full = response("<array><data><value><i8>1</i8></value></data></array>");
+        for cut in [20, full.find("<i8>").unwrap() + 5, full.find("</data>").unwrap()] {
+            assert!(matches!(parse_response(&full[..cut]), Err(Error::Malformed(_))), "cut at {}", cut);
+        }
+        assert!(matches!(parse("<i8>one</i8>"), Err(Error::Malformed(_))));
+        assert!(matches!(parse("<boolean>yes</boolean>"), Err(Error::Malformed(_))));
+        assert!(matches!(parse("<i8><b>1</b></i8>"), Err(Error::Malformed(_))));
+        assert!(matches!(parse("<i8>1</i4>"), Err(Error::Malformed(_))));
+        assert!(matches!(parse_response("<methodResponse></methodResponse>"), Err(Error::Malformed(_))));
+    }
+}